@@ -0,0 +1,18 @@
+-- Convert REAL amounts to exact integer minor units (1/1,000,000).
+-- Values were previously rendered with 6 decimals, so rounding at that
+-- scale preserves exactly what the user saw.
+
+ALTER TABLE Card ADD COLUMN amount_minor INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE Card ADD COLUMN lockedAmount_minor INTEGER;
+UPDATE Card SET
+    amount_minor = CAST(ROUND(amount * 1000000) AS INTEGER),
+    lockedAmount_minor = CAST(ROUND(lockedAmount * 1000000) AS INTEGER);
+ALTER TABLE Card DROP COLUMN amount;
+ALTER TABLE Card DROP COLUMN lockedAmount;
+ALTER TABLE Card RENAME COLUMN amount_minor TO amount;
+ALTER TABLE Card RENAME COLUMN lockedAmount_minor TO lockedAmount;
+
+ALTER TABLE Todo ADD COLUMN amount_minor INTEGER;
+UPDATE Todo SET amount_minor = CAST(ROUND(amount * 1000000) AS INTEGER);
+ALTER TABLE Todo DROP COLUMN amount;
+ALTER TABLE Todo RENAME COLUMN amount_minor TO amount;
@@ -0,0 +1,4 @@
+-- Track which ChangeLog entries have been reverted by undo.
+-- NULL means the change is in effect; redo clears it again.
+
+ALTER TABLE ChangeLog ADD COLUMN undoneAt TEXT;
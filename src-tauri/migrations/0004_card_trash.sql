@@ -0,0 +1,6 @@
+-- Soft delete: cards with a deletedAt timestamp live in the trash until
+-- the archiver purges them.
+
+ALTER TABLE Card ADD COLUMN deletedAt TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_card_deleted ON Card(deletedAt);
@@ -0,0 +1,8 @@
+-- Foreign keys were not enforced on older builds, so deleting a card could
+-- leave todos, history and search rows pointing at nothing. Remove them once;
+-- every connection now runs with PRAGMA foreign_keys = ON.
+
+DELETE FROM Todo WHERE cardId NOT IN (SELECT id FROM Card);
+DELETE FROM ChangeLog WHERE cardId NOT IN (SELECT id FROM Card);
+DELETE FROM search_index WHERE card_id NOT IN (SELECT id FROM Card);
+DELETE FROM search_index WHERE todo_id IS NOT NULL AND todo_id NOT IN (SELECT id FROM Todo);
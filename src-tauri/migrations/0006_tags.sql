@@ -0,0 +1,66 @@
+-- Tags for todos (many-to-many) and tag names in the search index.
+
+CREATE TABLE IF NOT EXISTS Tag (
+    id TEXT PRIMARY KEY NOT NULL,
+    name TEXT NOT NULL COLLATE NOCASE UNIQUE,
+    color TEXT,
+    createdAt TEXT NOT NULL DEFAULT (datetime('now')),
+    updatedAt TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+CREATE TABLE IF NOT EXISTS TodoTag (
+    todoId TEXT NOT NULL,
+    tagId TEXT NOT NULL,
+    PRIMARY KEY (todoId, tagId),
+    FOREIGN KEY (todoId) REFERENCES Todo(id) ON DELETE CASCADE,
+    FOREIGN KEY (tagId) REFERENCES Tag(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_todotag_tag ON TodoTag(tagId);
+
+-- Todo search rows carry the todo's tag names in `content`
+DROP TRIGGER IF EXISTS search_index_todo_update;
+
+CREATE TRIGGER search_index_todo_update AFTER UPDATE ON Todo BEGIN
+    DELETE FROM search_index WHERE todo_id = NEW.id;
+    INSERT INTO search_index(card_id, todo_id, card_title, todo_title, content)
+    VALUES (NEW.cardId, NEW.id, '', NEW.title, (
+        SELECT COALESCE(group_concat(t.name, ' '), '')
+        FROM TodoTag tt JOIN Tag t ON t.id = tt.tagId
+        WHERE tt.todoId = NEW.id
+    ));
+END;
+
+CREATE TRIGGER IF NOT EXISTS search_index_todotag_insert AFTER INSERT ON TodoTag BEGIN
+    DELETE FROM search_index WHERE todo_id = NEW.todoId;
+    INSERT INTO search_index(card_id, todo_id, card_title, todo_title, content)
+    SELECT td.cardId, td.id, '', td.title, (
+        SELECT COALESCE(group_concat(t.name, ' '), '')
+        FROM TodoTag tt JOIN Tag t ON t.id = tt.tagId
+        WHERE tt.todoId = td.id
+    )
+    FROM Todo td WHERE td.id = NEW.todoId;
+END;
+
+CREATE TRIGGER IF NOT EXISTS search_index_todotag_delete AFTER DELETE ON TodoTag BEGIN
+    DELETE FROM search_index WHERE todo_id = OLD.todoId;
+    INSERT INTO search_index(card_id, todo_id, card_title, todo_title, content)
+    SELECT td.cardId, td.id, '', td.title, (
+        SELECT COALESCE(group_concat(t.name, ' '), '')
+        FROM TodoTag tt JOIN Tag t ON t.id = tt.tagId
+        WHERE tt.todoId = td.id
+    )
+    FROM Todo td WHERE td.id = OLD.todoId;
+END;
+
+CREATE TRIGGER IF NOT EXISTS search_index_tag_update AFTER UPDATE OF name ON Tag BEGIN
+    DELETE FROM search_index
+    WHERE todo_id IN (SELECT todoId FROM TodoTag WHERE tagId = NEW.id);
+    INSERT INTO search_index(card_id, todo_id, card_title, todo_title, content)
+    SELECT td.cardId, td.id, '', td.title, (
+        SELECT COALESCE(group_concat(t.name, ' '), '')
+        FROM TodoTag tt JOIN Tag t ON t.id = tt.tagId
+        WHERE tt.todoId = td.id
+    )
+    FROM Todo td WHERE td.id IN (SELECT todoId FROM TodoTag WHERE tagId = NEW.id);
+END;
@@ -0,0 +1,24 @@
+-- Recurrence rules live on a template todo; the archiver materializes due
+-- occurrences as regular todos linked back through Todo.recurrenceOf.
+
+CREATE TABLE IF NOT EXISTS Recurrence (
+    todoId TEXT PRIMARY KEY NOT NULL,
+    cardId TEXT NOT NULL,
+    frequency TEXT NOT NULL CHECK (frequency IN ('daily', 'weekly', 'monthly', 'yearly')),
+    interval INTEGER NOT NULL DEFAULT 1 CHECK (interval >= 1),
+    startAt TEXT NOT NULL,
+    endAt TEXT,
+    occurrences INTEGER NOT NULL DEFAULT 0,
+    nextAt TEXT NOT NULL,
+    createdAt TEXT NOT NULL DEFAULT (datetime('now')),
+    updatedAt TEXT NOT NULL DEFAULT (datetime('now')),
+    FOREIGN KEY (todoId) REFERENCES Todo(id) ON DELETE CASCADE,
+    FOREIGN KEY (cardId) REFERENCES Card(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_recurrence_next ON Recurrence(nextAt);
+
+ALTER TABLE Todo ADD COLUMN recurrenceOf TEXT REFERENCES Todo(id) ON DELETE SET NULL;
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_todo_occurrence
+    ON Todo(recurrenceOf, scheduledAt) WHERE recurrenceOf IS NOT NULL;
@@ -0,0 +1,34 @@
+-- Currencies: every card has one (ISO 4217); a todo without one is in its
+-- card's currency.
+ALTER TABLE Card ADD COLUMN currency TEXT NOT NULL DEFAULT 'USD';
+ALTER TABLE Todo ADD COLUMN currency TEXT;
+
+-- Locally maintained rates: one unit of `base` is worth `rate` units of `quote`.
+-- A pair is also used in reverse when only the opposite direction is known.
+CREATE TABLE IF NOT EXISTS ExchangeRate (
+    base TEXT NOT NULL,
+    quote TEXT NOT NULL,
+    rate REAL NOT NULL CHECK (rate > 0),
+    source TEXT NOT NULL DEFAULT 'manual',
+    updatedAt TEXT NOT NULL DEFAULT (datetime('now')),
+    PRIMARY KEY (base, quote),
+    CHECK (base != quote)
+);
+
+-- Todo amounts converted into their card's currency, rounded to minor units.
+-- `amount` is NULL and `unconverted` is 1 when no rate is known.
+CREATE VIEW IF NOT EXISTS TodoCardAmount AS
+SELECT todoId, cardId, done, amount,
+       rawAmount IS NOT NULL AND amount IS NULL AS unconverted
+FROM (
+    SELECT t.id AS todoId, t.cardId, t.done, t.amount AS rawAmount,
+           CASE
+               WHEN t.currency IS NULL OR t.currency = c.currency THEN t.amount
+               ELSE CAST(ROUND(t.amount * COALESCE(
+                   (SELECT rate FROM ExchangeRate WHERE base = t.currency AND quote = c.currency),
+                   (SELECT 1.0 / rate FROM ExchangeRate WHERE base = c.currency AND quote = t.currency)
+               )) AS INTEGER)
+           END AS amount
+    FROM Todo t
+    JOIN Card c ON c.id = t.cardId
+);
@@ -0,0 +1,9 @@
+-- Pinned cards can be kept out of automatic archiving.
+ALTER TABLE Card ADD COLUMN pinned INTEGER NOT NULL DEFAULT 0;
+
+-- Application settings, one JSON document per key.
+CREATE TABLE IF NOT EXISTS Settings (
+    key TEXT PRIMARY KEY NOT NULL,
+    value TEXT NOT NULL,
+    updatedAt TEXT NOT NULL DEFAULT (datetime('now'))
+);
@@ -0,0 +1,20 @@
+-- Spending limits. A budget covers one card, one tag, or every card when
+-- both are NULL; monthly budgets only count todos dated in the calendar month
+-- being checked. `amount` is in `currency`, in millionths.
+CREATE TABLE IF NOT EXISTS Budget (
+    id TEXT PRIMARY KEY NOT NULL,
+    name TEXT,
+    cardId TEXT,
+    tagId TEXT,
+    monthly INTEGER NOT NULL DEFAULT 0,
+    amount INTEGER NOT NULL CHECK (amount > 0),
+    currency TEXT NOT NULL,
+    createdAt TEXT NOT NULL DEFAULT (datetime('now')),
+    updatedAt TEXT NOT NULL DEFAULT (datetime('now')),
+    FOREIGN KEY (cardId) REFERENCES Card(id) ON DELETE CASCADE,
+    FOREIGN KEY (tagId) REFERENCES Tag(id) ON DELETE CASCADE,
+    CHECK (cardId IS NULL OR tagId IS NULL)
+);
+
+CREATE INDEX IF NOT EXISTS idx_budget_card ON Budget(cardId);
+CREATE INDEX IF NOT EXISTS idx_budget_tag ON Budget(tagId);
@@ -0,0 +1,497 @@
+//! Optional HTTP/JSON API, so scripts and phone shortcuts on the LAN can log
+//! expenses while the app runs. Configured by `Settings::api_server` and off
+//! by default; every request needs `Authorization: Bearer <token>`.
+//!
+//! Bodies and responses use the same JSON as the Tauri commands:
+//!
+//! - `GET /api/cards` (`?archived=true&limit=&cursor=&sort=&order=`), `POST /api/cards`
+//! - `GET`, `PATCH`, `DELETE /api/cards/{id}`
+//! - `POST /api/cards/{id}/todos`, `POST /api/cards/{id}/archive`, `POST /api/cards/{id}/unarchive`
+//! - `PATCH`, `DELETE /api/todos/{id}`
+//! - `GET /api/search?q=`, `GET /api/changes`, `POST /api/archive`
+
+use crate::commands::notify_budget_exceeded;
+use crate::db::{with_db, with_store_mut};
+use crate::errors::AppError;
+use crate::models::{BudgetWarningDto, OkResponse};
+use crate::pagination::{CardSort, SortOrder};
+use crate::settings::{self, ApiServer};
+use crate::store::{Store, TodoChanges};
+use once_cell::sync::Lazy;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::Read;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use tauri::AppHandle;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+/// Largest request body accepted.
+pub const MAX_BODY_BYTES: u64 = 64 * 1024;
+
+struct Running {
+    config: ApiServer,
+    server: Arc<Server>,
+    thread: JoinHandle<()>,
+}
+
+static RUNNING: Lazy<Mutex<Option<Running>>> = Lazy::new(|| Mutex::new(None));
+
+/// Starts, stops or restarts the server to match the saved settings. Called
+/// at startup and whenever settings change.
+pub fn reload(app: &AppHandle) {
+    match with_db(settings::load) {
+        Ok(settings) => apply(app, &settings.api_server),
+        Err(e) => log::warn!("Failed to load API server settings: {}", e),
+    }
+}
+
+fn apply(app: &AppHandle, config: &ApiServer) {
+    let mut running = RUNNING.lock().unwrap_or_else(|e| e.into_inner());
+    let wanted = config.enabled.then_some(config);
+    if running.as_ref().map(|r| &r.config) == wanted {
+        return;
+    }
+
+    if let Some(old) = running.take() {
+        old.server.unblock();
+        if old.thread.join().is_err() {
+            log::warn!("API server thread panicked");
+        }
+        log::info!("API server stopped");
+    }
+    let Some(config) = wanted else {
+        return;
+    };
+
+    let address = match config.bind_address.parse::<IpAddr>() {
+        Ok(ip) => (ip, config.port),
+        Err(_) => {
+            log::warn!("Invalid API bind address: {}", config.bind_address);
+            return;
+        }
+    };
+    let server = match Server::http(address) {
+        Ok(server) => Arc::new(server),
+        Err(e) => {
+            log::warn!("Failed to start API server on {:?}: {}", address, e);
+            return;
+        }
+    };
+    log::info!("API server listening on {}:{}", address.0, address.1);
+
+    let thread = std::thread::spawn({
+        let server = server.clone();
+        let app = app.clone();
+        let token = config.token.clone();
+        move || {
+            // Ends once `unblock` is called
+            for request in server.incoming_requests() {
+                handle(&app, &token, request);
+            }
+        }
+    });
+    *running = Some(Running {
+        config: config.clone(),
+        server,
+        thread,
+    });
+}
+
+#[derive(Debug)]
+pub enum ApiError {
+    Unauthorized,
+    NoRoute,
+    /// The path exists but not for this method; holds the `Allow` header value
+    MethodNotAllowed(&'static str),
+    BadRequest(String),
+    TooLarge,
+    App(AppError),
+}
+
+impl From<AppError> for ApiError {
+    fn from(e: AppError) -> Self {
+        ApiError::App(e)
+    }
+}
+
+impl ApiError {
+    pub fn status(&self) -> u16 {
+        match self {
+            ApiError::Unauthorized => 401,
+            ApiError::NoRoute => 404,
+            ApiError::MethodNotAllowed(_) => 405,
+            ApiError::BadRequest(_) => 400,
+            ApiError::TooLarge => 413,
+            ApiError::App(e) => e.http_status(),
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            ApiError::Unauthorized => "Missing or wrong API token".into(),
+            ApiError::NoRoute => "No such endpoint".into(),
+            ApiError::MethodNotAllowed(allow) => format!("Method not allowed; use {}", allow),
+            ApiError::BadRequest(message) => message.clone(),
+            ApiError::TooLarge => format!("Request body is over {} bytes", MAX_BODY_BYTES),
+            ApiError::App(e) => e.to_string(),
+        }
+    }
+
+    /// The same `{ code, message, details }` shape the app's commands use.
+    pub fn body(&self) -> Value {
+        let code = match self {
+            ApiError::Unauthorized => "UNAUTHORIZED",
+            ApiError::NoRoute => "NOT_FOUND",
+            ApiError::MethodNotAllowed(_) => "METHOD_NOT_ALLOWED",
+            ApiError::BadRequest(_) => "BAD_REQUEST",
+            ApiError::TooLarge => "PAYLOAD_TOO_LARGE",
+            ApiError::App(e) => return serde_json::to_value(e).unwrap_or(Value::Null),
+        };
+        json!({ "code": code, "message": self.message(), "details": null })
+    }
+}
+
+fn handle(app: &AppHandle, token: &str, mut request: Request) {
+    // Unauthenticated requests are turned away before their body is read or
+    // the store is locked
+    let result = authorize(authorization(&request).as_deref(), token)
+        .and_then(|()| ApiRequest::read(&mut request))
+        .and_then(|api_request| with_store_mut(|store| Ok(route(store, token, &api_request)))?);
+    let (status, body) = match &result {
+        Ok(reply) => {
+            notify_budget_exceeded(app, &reply.warning);
+            (reply.status, reply.body.clone())
+        }
+        Err(e) => {
+            if e.status() == 500 {
+                log::warn!(
+                    "API {} {} failed: {}",
+                    request.method(),
+                    request.url(),
+                    e.message()
+                );
+            }
+            (e.status(), e.body().to_string())
+        }
+    };
+
+    let mut response = Response::from_string(body).with_status_code(status);
+    let mut headers = vec![("Content-Type", "application/json")];
+    match &result {
+        Err(ApiError::Unauthorized) => headers.push(("WWW-Authenticate", "Bearer")),
+        Err(ApiError::MethodNotAllowed(allow)) => headers.push(("Allow", allow)),
+        _ => {}
+    }
+    for (name, value) in headers {
+        if let Ok(header) = Header::from_bytes(name.as_bytes(), value.as_bytes()) {
+            response.add_header(header);
+        }
+    }
+    if let Err(e) = request.respond(response) {
+        log::warn!("Failed to send API response: {}", e);
+    }
+}
+
+/// The parts of an HTTP request the routes use, read up front so routing
+/// works on any store without a socket.
+#[derive(Debug, Clone)]
+pub struct ApiRequest {
+    pub method: Method,
+    /// Path and query string, e.g. `/api/cards?limit=20`
+    pub url: String,
+    pub authorization: Option<String>,
+    /// `None` when the body is over `MAX_BODY_BYTES`
+    pub body: Option<Vec<u8>>,
+}
+
+impl ApiRequest {
+    fn read(request: &mut Request) -> Result<ApiRequest, ApiError> {
+        let authorization = authorization(request);
+        let mut body = Vec::new();
+        request
+            .as_reader()
+            .take(MAX_BODY_BYTES + 1)
+            .read_to_end(&mut body)
+            .map_err(AppError::from)?;
+
+        Ok(ApiRequest {
+            method: request.method().clone(),
+            url: request.url().to_string(),
+            authorization,
+            body: (body.len() as u64 <= MAX_BODY_BYTES).then_some(body),
+        })
+    }
+}
+
+/// A successful response.
+#[derive(Debug)]
+pub struct Reply {
+    pub status: u16,
+    /// JSON body
+    pub body: String,
+    /// Budget warning to show in the app window
+    pub warning: Option<BudgetWarningDto>,
+}
+
+#[derive(Deserialize)]
+struct NewCard {
+    title: Option<String>,
+    amount: String,
+    currency: Option<String>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct CardChanges {
+    title: Option<String>,
+    amount: Option<String>,
+    currency: Option<String>,
+}
+
+/// A todo is scheduled now unless `scheduled_at` is given.
+#[derive(Deserialize)]
+struct NewTodo {
+    title: String,
+    amount: Option<String>,
+    scheduled_at: Option<String>,
+    currency: Option<String>,
+}
+
+pub fn route(store: &mut Store, token: &str, request: &ApiRequest) -> Result<Reply, ApiError> {
+    authorize(request.authorization.as_deref(), token)?;
+
+    let (path, query) = request.url.split_once('?').unwrap_or((&request.url, ""));
+    let query = parse_query(query);
+    let segments: Vec<String> = path
+        .trim_matches('/')
+        .split('/')
+        .map(percent_decode)
+        .collect();
+    let segments: Vec<&str> = segments.iter().map(String::as_str).collect();
+
+    match (&request.method, segments.as_slice()) {
+        (Method::Get, ["api", "cards"]) => {
+            let limit = query_value(&query, "limit")?;
+            let sort = query_enum::<CardSort>(&query, "sort")?;
+            let order = query_enum::<SortOrder>(&query, "order")?;
+            let cursor = query.get("cursor").map(String::as_str);
+            let archived = query
+                .get("archived")
+                .is_some_and(|v| v == "true" || v == "1");
+            if archived {
+                ok(store.list_archived_cards(limit, cursor, sort, order)?)
+            } else {
+                ok(store.list_cards(limit, cursor, sort, order)?)
+            }
+        }
+        (Method::Post, ["api", "cards"]) => {
+            let card: NewCard = read_body(request)?;
+            created(store.create_card(
+                card.title.as_deref(),
+                &card.amount,
+                card.currency.as_deref(),
+            )?)
+        }
+        (Method::Get, ["api", "cards", id]) => ok(store.get_card(id)?),
+        (Method::Patch, ["api", "cards", id]) => {
+            let changes: CardChanges = read_body(request)?;
+            ok(store.update_card(
+                id,
+                changes.title.as_deref(),
+                changes.amount.as_deref(),
+                changes.currency.as_deref(),
+            )?)
+        }
+        (Method::Delete, ["api", "cards", id]) => {
+            store.delete_card(id)?;
+            ok(OkResponse { ok: true })
+        }
+        (Method::Post, ["api", "cards", id, "todos"]) => {
+            let todo: NewTodo = read_body(request)?;
+            let result = store.add_todo(
+                id,
+                &todo.title,
+                todo.amount.as_deref(),
+                todo.scheduled_at.is_none(),
+                todo.scheduled_at.as_deref(),
+                todo.currency.as_deref(),
+            )?;
+            Ok(Reply {
+                warning: result.warning.clone(),
+                ..created(result)?
+            })
+        }
+        (Method::Post, ["api", "cards", id, "archive"]) => ok(store.archive_card(id)?),
+        (Method::Post, ["api", "cards", id, "unarchive"]) => ok(store.unarchive_card(id)?),
+        (Method::Patch, ["api", "todos", id]) => {
+            let changes: TodoChanges = read_body(request)?;
+            let result = store.update_todo(id, changes)?;
+            Ok(Reply {
+                warning: result.warning.clone(),
+                ..ok(result)?
+            })
+        }
+        (Method::Delete, ["api", "todos", id]) => {
+            store.delete_todo(id)?;
+            ok(OkResponse { ok: true })
+        }
+        (Method::Get, ["api", "search"]) => {
+            let q = query
+                .get("q")
+                .ok_or_else(|| ApiError::BadRequest("Missing q".into()))?;
+            let limit = query_value(&query, "limit")?;
+            let cursor = query.get("cursor").map(String::as_str);
+            ok(store.search(q, limit, cursor)?)
+        }
+        (Method::Get, ["api", "changes"]) => {
+            let limit = query_value(&query, "limit")?;
+            let cursor = query.get("cursor").map(String::as_str);
+            ok(store.recent_changes(limit, cursor)?)
+        }
+        (Method::Post, ["api", "archive"]) => ok(store.archive_old_cards()?),
+        (_, path) => Err(match allowed_methods(path) {
+            Some(allow) => ApiError::MethodNotAllowed(allow),
+            None => ApiError::NoRoute,
+        }),
+    }
+}
+
+/// The methods `route` accepts for `path`, or `None` for an unknown path.
+fn allowed_methods(path: &[&str]) -> Option<&'static str> {
+    match path {
+        ["api", "cards"] => Some("GET, POST"),
+        ["api", "cards", _] => Some("GET, PATCH, DELETE"),
+        ["api", "cards", _, "todos" | "archive" | "unarchive"] => Some("POST"),
+        ["api", "todos", _] => Some("PATCH, DELETE"),
+        ["api", "search" | "changes"] => Some("GET"),
+        ["api", "archive"] => Some("POST"),
+        _ => None,
+    }
+}
+
+fn ok<T: Serialize>(value: T) -> Result<Reply, ApiError> {
+    reply(200, &value)
+}
+
+fn created<T: Serialize>(value: T) -> Result<Reply, ApiError> {
+    reply(201, &value)
+}
+
+fn reply<T: Serialize>(status: u16, value: &T) -> Result<Reply, ApiError> {
+    Ok(Reply {
+        status,
+        body: to_json(value)?,
+        warning: None,
+    })
+}
+
+fn to_json<T: Serialize>(value: &T) -> Result<String, ApiError> {
+    serde_json::to_string(value)
+        .map_err(|e| AppError::Internal(format!("Failed to write JSON: {}", e)).into())
+}
+
+fn read_body<T: DeserializeOwned>(request: &ApiRequest) -> Result<T, ApiError> {
+    let body = request.body.as_deref().ok_or(ApiError::TooLarge)?;
+    serde_json::from_slice(body)
+        .map_err(|e| ApiError::BadRequest(format!("Invalid JSON body: {}", e)))
+}
+
+fn query_value(query: &HashMap<String, String>, name: &str) -> Result<Option<u32>, ApiError> {
+    query
+        .get(name)
+        .map(|value| {
+            value
+                .parse()
+                .map_err(|_| ApiError::BadRequest(format!("Invalid {}: {}", name, value)))
+        })
+        .transpose()
+}
+
+/// A snake_case enum value such as `sort=remaining`.
+fn query_enum<T: DeserializeOwned>(
+    query: &HashMap<String, String>,
+    name: &str,
+) -> Result<Option<T>, ApiError> {
+    query
+        .get(name)
+        .map(|value| {
+            serde_json::from_value(serde_json::Value::String(value.clone()))
+                .map_err(|_| ApiError::BadRequest(format!("Invalid {}: {}", name, value)))
+        })
+        .transpose()
+}
+
+fn parse_query(query: &str) -> HashMap<String, String> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (
+                percent_decode(&name.replace('+', " ")),
+                percent_decode(&value.replace('+', " ")),
+            )
+        })
+        .collect()
+}
+
+/// Decodes `%XX` escapes; malformed escapes are kept as is.
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            // `from_str_radix` alone would also take a sign, as in `%+1`
+            b'%' => match value
+                .get(i + 1..i + 3)
+                .filter(|hex| hex.bytes().all(|b| b.is_ascii_hexdigit()))
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            {
+                Some(byte) => {
+                    decoded.push(byte);
+                    i += 2;
+                }
+                None => decoded.push(b'%'),
+            },
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn authorization(request: &Request) -> Option<String> {
+    request
+        .headers()
+        .iter()
+        .find(|h| h.field.equiv("Authorization"))
+        .map(|h| h.value.as_str().to_string())
+}
+
+/// Accepts an `Authorization: Bearer <token>` header carrying `token`.
+fn authorize(authorization: Option<&str>, token: &str) -> Result<(), ApiError> {
+    let authorized = authorization
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|given| token_matches(given.trim(), token));
+    if authorized {
+        Ok(())
+    } else {
+        Err(ApiError::Unauthorized)
+    }
+}
+
+/// Compares in time independent of where the strings differ.
+fn token_matches(given: &str, token: &str) -> bool {
+    !token.is_empty()
+        && given.len() == token.len()
+        && given
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
@@ -1,28 +1,95 @@
-use crate::commands::archive_old_cards;
+use crate::commands::{archive_old_cards, purge_trash, run_recurrences};
+use crate::db::{self, with_db};
+use crate::settings;
+use crate::snapshots;
+use once_cell::sync::Lazy;
 use std::time::Duration;
-use tokio::time::interval;
+use tokio::sync::Notify;
+use tokio::time::timeout;
 
-pub async fn start_archiver() {
-    let mut ticker = interval(Duration::from_secs(24 * 60 * 60));
+const RUN_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+static WAKE: Lazy<Notify> = Lazy::new(Notify::new);
 
+pub async fn start_archiver() {
     if let Err(e) = run_archive() {
         log::warn!("Initial archive run failed: {}", e);
     }
 
     loop {
-        ticker.tick().await;
+        let woken = timeout(RUN_INTERVAL, WAKE.notified()).await.is_ok();
         if let Err(e) = run_archive() {
-            log::warn!("Scheduled archive run failed: {}", e);
+            if woken {
+                log::warn!("Archive run after settings change failed: {}", e);
+            } else {
+                log::warn!("Scheduled archive run failed: {}", e);
+            }
         }
     }
 }
 
+/// Run the archiver now instead of waiting for the next scheduled run, so a
+/// changed archive policy or backup retention applies without a restart.
+pub fn wake() {
+    WAKE.notify_one();
+}
+
 fn run_archive() -> Result<(), String> {
+    // Snapshot before the archiver's own writes
+    if let Err(e) = run_snapshots() {
+        log::warn!("Snapshot failed: {}", e);
+    }
+
+    // Each step is independent, so one failing must not hold back the rest
+    match run_recurrences() {
+        Ok(result) => {
+            if result.created_count > 0 {
+                log::info!("Created {} recurring todos", result.created_count);
+            }
+        }
+        Err(e) => log::warn!("Recurring todos failed: {}", e),
+    }
+
     match archive_old_cards() {
         Ok(result) => {
             if result.archived_count > 0 {
                 log::info!("Archived {} old cards", result.archived_count);
             }
+        }
+        Err(e) => log::warn!("Archiving old cards failed: {}", e),
+    }
+
+    let retention_days = with_db(settings::load)
+        .map_err(|e| e.to_string())?
+        .trash_retention_days;
+    match purge_trash(Some(retention_days.into())) {
+        Ok(result) => {
+            if result.purged_count > 0 {
+                log::info!("Purged {} cards from the trash", result.purged_count);
+            }
+            Ok(())
+        }
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn run_snapshots() -> Result<(), String> {
+    let dir = db::backups_dir().map_err(|e| e.to_string())?;
+    let retention = with_db(settings::load)
+        .map_err(|e| e.to_string())?
+        .backup_retention;
+
+    match with_db(|conn| snapshots::take_if_due(conn, &dir, chrono::Utc::now())) {
+        Ok(Some(snapshot)) => log::info!("Saved snapshot {}", snapshot.id),
+        Ok(None) => {}
+        Err(e) => return Err(e.to_string()),
+    }
+
+    match snapshots::rotate(&dir, retention.keep_daily, retention.keep_weekly) {
+        Ok(removed) => {
+            if removed > 0 {
+                log::info!("Removed {} old snapshots", removed);
+            }
             Ok(())
         }
         Err(e) => Err(e.to_string()),
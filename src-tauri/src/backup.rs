@@ -0,0 +1,718 @@
+use crate::currency;
+use crate::errors::AppError;
+use crate::migrations;
+use crate::models::{BackupResult, RestoreResult};
+use crate::money::Money;
+use crate::recurrence::Frequency;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
+
+/// Marker in every backup so unrelated JSON files are rejected.
+const BACKUP_FORMAT: &str = "tin-backup";
+
+/// Version of the document layout below, independent of the schema version.
+const BACKUP_FORMAT_VERSION: u32 = 1;
+
+/// Every table except `Settings`. Settings describe this installation (and
+/// hold the API token), so a restore keeps the current ones.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BackupDocument {
+    pub format: String,
+    pub format_version: u32,
+    pub schema_version: u32,
+    pub app_version: String,
+    pub created_at: String,
+    pub cards: Vec<BackupCard>,
+    pub todos: Vec<BackupTodo>,
+    pub changes: Vec<BackupChange>,
+    #[serde(default)]
+    pub tags: Vec<BackupTag>,
+    #[serde(default)]
+    pub todo_tags: Vec<BackupTodoTag>,
+    #[serde(default)]
+    pub recurrences: Vec<BackupRecurrence>,
+    #[serde(default)]
+    pub exchange_rates: Vec<BackupExchangeRate>,
+    #[serde(default)]
+    pub budgets: Vec<BackupBudget>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BackupCard {
+    pub id: String,
+    pub title: Option<String>,
+    pub amount: Money,
+    #[serde(default = "default_currency")]
+    pub currency: String,
+    pub locked_amount: Option<Money>,
+    pub archived: bool,
+    #[serde(default)]
+    pub pinned: bool,
+    pub archived_at: Option<String>,
+    #[serde(default)]
+    pub deleted_at: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BackupTodo {
+    pub id: String,
+    pub card_id: String,
+    pub title: String,
+    pub amount: Option<Money>,
+    #[serde(default)]
+    pub currency: Option<String>,
+    pub done: bool,
+    pub scheduled_at: Option<String>,
+    pub order_index: i32,
+    #[serde(default)]
+    pub recurrence_of: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BackupChange {
+    pub id: String,
+    pub card_id: String,
+    pub kind: String,
+    pub payload: serde_json::Value,
+    pub created_at: String,
+    #[serde(default)]
+    pub undone_at: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BackupTag {
+    pub id: String,
+    pub name: String,
+    pub color: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BackupTodoTag {
+    pub todo_id: String,
+    pub tag_id: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BackupRecurrence {
+    pub todo_id: String,
+    pub card_id: String,
+    pub frequency: String,
+    pub interval: u32,
+    pub start_at: String,
+    pub end_at: Option<String>,
+    pub occurrences: u32,
+    pub next_at: String,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BackupExchangeRate {
+    pub base: String,
+    pub quote: String,
+    pub rate: f64,
+    pub source: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BackupBudget {
+    pub id: String,
+    pub name: Option<String>,
+    pub card_id: Option<String>,
+    pub tag_id: Option<String>,
+    pub monthly: bool,
+    pub amount: Money,
+    pub currency: String,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+fn default_currency() -> String {
+    currency::DEFAULT_CURRENCY.to_string()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RestoreMode {
+    /// Wipe the database and load the backup
+    Replace,
+    /// Add missing rows and overwrite rows the backup has a newer version of
+    Merge,
+}
+
+impl RestoreMode {
+    pub fn parse(value: &str) -> Result<RestoreMode, AppError> {
+        match value {
+            "replace" => Ok(RestoreMode::Replace),
+            "merge" => Ok(RestoreMode::Merge),
+            _ => Err(AppError::invalid_field(
+                "mode",
+                format!("Unknown restore mode: {}", value),
+            )),
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RestoreMode::Replace => "replace",
+            RestoreMode::Merge => "merge",
+        }
+    }
+}
+
+/// Read every table into a backup document.
+pub fn snapshot(conn: &Connection, now: &str) -> Result<BackupDocument, AppError> {
+    let cards = conn
+        .prepare(
+            "SELECT id, title, amount, currency, lockedAmount, archived, archivedAt, deletedAt, createdAt, updatedAt, pinned
+             FROM Card ORDER BY createdAt, id",
+        )?
+        .query_map([], |row| {
+            Ok(BackupCard {
+                id: row.get(0)?,
+                title: row.get(1)?,
+                amount: row.get(2)?,
+                currency: row.get(3)?,
+                locked_amount: row.get(4)?,
+                archived: row.get::<_, i32>(5)? != 0,
+                pinned: row.get::<_, i32>(10)? != 0,
+                archived_at: row.get(6)?,
+                deleted_at: row.get(7)?,
+                created_at: row.get(8)?,
+                updated_at: row.get(9)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let todos = conn
+        .prepare(
+            "SELECT id, cardId, title, amount, currency, done, scheduledAt, orderIndex, recurrenceOf, createdAt, updatedAt
+             FROM Todo ORDER BY cardId, orderIndex, id",
+        )?
+        .query_map([], |row| {
+            Ok(BackupTodo {
+                id: row.get(0)?,
+                card_id: row.get(1)?,
+                title: row.get(2)?,
+                amount: row.get(3)?,
+                currency: row.get(4)?,
+                done: row.get::<_, i32>(5)? != 0,
+                scheduled_at: row.get(6)?,
+                order_index: row.get(7)?,
+                recurrence_of: row.get(8)?,
+                created_at: row.get(9)?,
+                updated_at: row.get(10)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // rowid order is the undo order, so keep it
+    let changes = conn
+        .prepare(
+            "SELECT id, cardId, kind, payload, createdAt, undoneAt FROM ChangeLog ORDER BY rowid",
+        )?
+        .query_map([], |row| {
+            let payload: String = row.get(3)?;
+            Ok(BackupChange {
+                id: row.get(0)?,
+                card_id: row.get(1)?,
+                kind: row.get(2)?,
+                payload: serde_json::from_str(&payload).unwrap_or(serde_json::json!({})),
+                created_at: row.get(4)?,
+                undone_at: row.get(5)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let tags = conn
+        .prepare("SELECT id, name, color, createdAt, updatedAt FROM Tag ORDER BY name")?
+        .query_map([], |row| {
+            Ok(BackupTag {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                color: row.get(2)?,
+                created_at: row.get(3)?,
+                updated_at: row.get(4)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let todo_tags = conn
+        .prepare("SELECT todoId, tagId FROM TodoTag ORDER BY todoId, tagId")?
+        .query_map([], |row| {
+            Ok(BackupTodoTag {
+                todo_id: row.get(0)?,
+                tag_id: row.get(1)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let recurrences = conn
+        .prepare(
+            "SELECT todoId, cardId, frequency, interval, startAt, endAt, occurrences, nextAt, createdAt, updatedAt
+             FROM Recurrence ORDER BY todoId",
+        )?
+        .query_map([], |row| {
+            Ok(BackupRecurrence {
+                todo_id: row.get(0)?,
+                card_id: row.get(1)?,
+                frequency: row.get(2)?,
+                interval: row.get(3)?,
+                start_at: row.get(4)?,
+                end_at: row.get(5)?,
+                occurrences: row.get(6)?,
+                next_at: row.get(7)?,
+                created_at: row.get(8)?,
+                updated_at: row.get(9)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let exchange_rates = conn
+        .prepare(
+            "SELECT base, quote, rate, source, updatedAt FROM ExchangeRate ORDER BY base, quote",
+        )?
+        .query_map([], |row| {
+            Ok(BackupExchangeRate {
+                base: row.get(0)?,
+                quote: row.get(1)?,
+                rate: row.get(2)?,
+                source: row.get(3)?,
+                updated_at: row.get(4)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let budgets = conn
+        .prepare(
+            "SELECT id, name, cardId, tagId, monthly, amount, currency, createdAt, updatedAt
+             FROM Budget ORDER BY createdAt, id",
+        )?
+        .query_map([], |row| {
+            Ok(BackupBudget {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                card_id: row.get(2)?,
+                tag_id: row.get(3)?,
+                monthly: row.get::<_, i32>(4)? != 0,
+                amount: row.get(5)?,
+                currency: row.get(6)?,
+                created_at: row.get(7)?,
+                updated_at: row.get(8)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(BackupDocument {
+        format: BACKUP_FORMAT.to_string(),
+        format_version: BACKUP_FORMAT_VERSION,
+        schema_version: migrations::current_version(conn)?,
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        created_at: now.to_string(),
+        cards,
+        todos,
+        changes,
+        tags,
+        todo_tags,
+        recurrences,
+        exchange_rates,
+        budgets,
+    })
+}
+
+pub fn export(conn: &Connection, path: &Path, now: &str) -> Result<BackupResult, AppError> {
+    let document = snapshot(conn, now)?;
+
+    let mut writer = BufWriter::new(File::create(path)?);
+    serde_json::to_writer_pretty(&mut writer, &document)
+        .map_err(|e| AppError::Internal(format!("Failed to write backup: {}", e)))?;
+    writer.flush()?;
+
+    Ok(BackupResult {
+        path: path.to_string_lossy().into_owned(),
+        schema_version: document.schema_version,
+        card_count: document.cards.len() as i32,
+        todo_count: document.todos.len() as i32,
+        change_count: document.changes.len() as i32,
+    })
+}
+
+/// Read and validate a backup file without touching the database.
+pub fn read(path: &Path) -> Result<BackupDocument, AppError> {
+    let reader = BufReader::new(File::open(path)?);
+    let document: BackupDocument =
+        serde_json::from_reader(reader).map_err(|e| AppError::InvalidBackup(e.to_string()))?;
+    validate(&document)?;
+    Ok(document)
+}
+
+/// Check the document is a backup this version can load and that every
+/// reference points at a row inside the document.
+pub fn validate(document: &BackupDocument) -> Result<(), AppError> {
+    let invalid = |msg: String| Err(AppError::InvalidBackup(msg));
+
+    if document.format != BACKUP_FORMAT {
+        return invalid(format!("not a Tin backup (format {:?})", document.format));
+    }
+    if document.format_version != BACKUP_FORMAT_VERSION {
+        return invalid(format!(
+            "unsupported backup format version {}",
+            document.format_version
+        ));
+    }
+    let latest = migrations::latest_version();
+    if document.schema_version > latest {
+        return Err(AppError::SchemaTooNew(document.schema_version, latest));
+    }
+
+    let mut card_ids = HashSet::new();
+    for card in &document.cards {
+        if !card_ids.insert(card.id.as_str()) {
+            return invalid(format!("duplicate card {}", card.id));
+        }
+        currency::validate_code(&card.currency)?;
+    }
+
+    let mut todo_ids = HashSet::new();
+    for todo in &document.todos {
+        if !todo_ids.insert(todo.id.as_str()) {
+            return invalid(format!("duplicate todo {}", todo.id));
+        }
+        if !card_ids.contains(todo.card_id.as_str()) {
+            return invalid(format!(
+                "todo {} references missing card {}",
+                todo.id, todo.card_id
+            ));
+        }
+        if let Some(code) = &todo.currency {
+            currency::validate_code(code)?;
+        }
+    }
+    for todo in &document.todos {
+        if let Some(template) = &todo.recurrence_of {
+            if !todo_ids.contains(template.as_str()) {
+                return invalid(format!(
+                    "todo {} references missing todo {}",
+                    todo.id, template
+                ));
+            }
+        }
+    }
+
+    let mut change_ids = HashSet::new();
+    for change in &document.changes {
+        if !change_ids.insert(change.id.as_str()) {
+            return invalid(format!("duplicate change {}", change.id));
+        }
+        if !card_ids.contains(change.card_id.as_str()) {
+            return invalid(format!(
+                "change {} references missing card {}",
+                change.id, change.card_id
+            ));
+        }
+    }
+
+    let mut tag_ids = HashSet::new();
+    let mut tag_names = HashSet::new();
+    for tag in &document.tags {
+        if !tag_ids.insert(tag.id.as_str()) || !tag_names.insert(tag.name.to_lowercase()) {
+            return invalid(format!("duplicate tag {}", tag.name));
+        }
+    }
+    for link in &document.todo_tags {
+        if !todo_ids.contains(link.todo_id.as_str()) || !tag_ids.contains(link.tag_id.as_str()) {
+            return invalid(format!(
+                "tag link {}/{} references a missing row",
+                link.todo_id, link.tag_id
+            ));
+        }
+    }
+
+    for rule in &document.recurrences {
+        if !todo_ids.contains(rule.todo_id.as_str()) || !card_ids.contains(rule.card_id.as_str()) {
+            return invalid(format!(
+                "recurrence {} references a missing row",
+                rule.todo_id
+            ));
+        }
+        Frequency::parse(&rule.frequency)?;
+        if rule.interval == 0 {
+            return invalid(format!("recurrence {} has interval 0", rule.todo_id));
+        }
+    }
+
+    for rate in &document.exchange_rates {
+        currency::validate_rate(&rate.base, &rate.quote, &rate.rate.to_string())?;
+    }
+
+    let mut budget_ids = HashSet::new();
+    for budget in &document.budgets {
+        if !budget_ids.insert(budget.id.as_str()) {
+            return invalid(format!("duplicate budget {}", budget.id));
+        }
+        let card_ok = budget
+            .card_id
+            .as_deref()
+            .map_or(true, |id| card_ids.contains(id));
+        let tag_ok = budget
+            .tag_id
+            .as_deref()
+            .map_or(true, |id| tag_ids.contains(id));
+        if !card_ok || !tag_ok || (budget.card_id.is_some() && budget.tag_id.is_some()) {
+            return invalid(format!("budget {} references a missing row", budget.id));
+        }
+        if budget.amount <= Money::ZERO {
+            return invalid(format!("budget {} has no amount", budget.id));
+        }
+        currency::validate_code(&budget.currency)?;
+    }
+
+    Ok(())
+}
+
+/// Load a document in one transaction and rebuild the search index. The
+/// document is validated first, so a bad one leaves the database untouched.
+/// Settings are not part of a backup and are never changed.
+pub fn restore(
+    conn: &mut Connection,
+    document: &BackupDocument,
+    mode: RestoreMode,
+) -> Result<RestoreResult, AppError> {
+    validate(document)?;
+
+    let tx = conn.transaction()?;
+    // Rows may reference rows later in the document (recurrence templates)
+    tx.pragma_update(None, "defer_foreign_keys", true)?;
+
+    if mode == RestoreMode::Replace {
+        tx.execute_batch(
+            "DELETE FROM ChangeLog;
+             DELETE FROM Recurrence;
+             DELETE FROM Budget;
+             DELETE FROM TodoTag;
+             DELETE FROM Todo;
+             DELETE FROM Tag;
+             DELETE FROM Card;
+             DELETE FROM ExchangeRate;",
+        )?;
+    }
+
+    // Upserts only overwrite rows the backup has a newer version of, which in
+    // replace mode is every row since the tables are empty.
+    let mut cards_restored = 0;
+    for card in &document.cards {
+        cards_restored += tx.execute(
+            "INSERT INTO Card (id, title, amount, currency, lockedAmount, archived, archivedAt, deletedAt, createdAt, updatedAt, pinned)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)
+             ON CONFLICT (id) DO UPDATE SET
+                 title = excluded.title, amount = excluded.amount, currency = excluded.currency,
+                 lockedAmount = excluded.lockedAmount, archived = excluded.archived, pinned = excluded.pinned,
+                 archivedAt = excluded.archivedAt, deletedAt = excluded.deletedAt,
+                 updatedAt = excluded.updatedAt
+             WHERE excluded.updatedAt > Card.updatedAt",
+            params![
+                card.id,
+                card.title,
+                card.amount,
+                card.currency,
+                card.locked_amount,
+                card.archived as i32,
+                card.archived_at,
+                card.deleted_at,
+                card.created_at,
+                card.updated_at,
+                card.pinned as i32
+            ],
+        )?;
+    }
+
+    // Backup tag id -> id in the database, for tags merged by name
+    let mut tag_map: HashMap<&str, String> = HashMap::new();
+    for tag in &document.tags {
+        let existing: Option<String> = tx
+            .query_row(
+                "SELECT id FROM Tag WHERE name = ?1 AND id != ?2",
+                params![tag.name, tag.id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        if let Some(id) = existing {
+            tag_map.insert(&tag.id, id);
+            continue;
+        }
+        tx.execute(
+            "INSERT INTO Tag (id, name, color, createdAt, updatedAt) VALUES (?1, ?2, ?3, ?4, ?5)
+             ON CONFLICT (id) DO UPDATE SET
+                 name = excluded.name, color = excluded.color, updatedAt = excluded.updatedAt
+             WHERE excluded.updatedAt > Tag.updatedAt",
+            params![tag.id, tag.name, tag.color, tag.created_at, tag.updated_at],
+        )?;
+        tag_map.insert(&tag.id, tag.id.clone());
+    }
+
+    let mut todos_restored = 0;
+    let mut skipped_todos = HashSet::new();
+    for todo in &document.todos {
+        // The same occurrence materialized on both sides under different ids
+        let occurrence_exists: bool = tx.query_row(
+            "SELECT COUNT(*) > 0 FROM Todo WHERE recurrenceOf = ?1 AND scheduledAt IS ?2 AND id != ?3",
+            params![todo.recurrence_of, todo.scheduled_at, todo.id],
+            |row| row.get(0),
+        )?;
+        if occurrence_exists {
+            skipped_todos.insert(todo.id.as_str());
+            continue;
+        }
+
+        todos_restored += tx.execute(
+            "INSERT INTO Todo (id, cardId, title, amount, currency, done, scheduledAt, orderIndex, recurrenceOf, createdAt, updatedAt)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)
+             ON CONFLICT (id) DO UPDATE SET
+                 cardId = excluded.cardId, title = excluded.title, amount = excluded.amount,
+                 currency = excluded.currency, done = excluded.done, scheduledAt = excluded.scheduledAt,
+                 orderIndex = excluded.orderIndex, recurrenceOf = excluded.recurrenceOf,
+                 updatedAt = excluded.updatedAt
+             WHERE excluded.updatedAt > Todo.updatedAt",
+            params![
+                todo.id,
+                todo.card_id,
+                todo.title,
+                todo.amount,
+                todo.currency,
+                todo.done as i32,
+                todo.scheduled_at,
+                todo.order_index,
+                todo.recurrence_of,
+                todo.created_at,
+                todo.updated_at
+            ],
+        )?;
+    }
+
+    for link in &document.todo_tags {
+        if skipped_todos.contains(link.todo_id.as_str()) {
+            continue;
+        }
+        tx.execute(
+            "INSERT OR IGNORE INTO TodoTag (todoId, tagId) VALUES (?1, ?2)",
+            params![link.todo_id, tag_map[link.tag_id.as_str()]],
+        )?;
+    }
+
+    for rule in &document.recurrences {
+        tx.execute(
+            "INSERT INTO Recurrence (todoId, cardId, frequency, interval, startAt, endAt, occurrences, nextAt, createdAt, updatedAt)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+             ON CONFLICT (todoId) DO UPDATE SET
+                 cardId = excluded.cardId, frequency = excluded.frequency, interval = excluded.interval,
+                 startAt = excluded.startAt, endAt = excluded.endAt, occurrences = excluded.occurrences,
+                 nextAt = excluded.nextAt, updatedAt = excluded.updatedAt
+             WHERE excluded.updatedAt > Recurrence.updatedAt",
+            params![
+                rule.todo_id,
+                rule.card_id,
+                rule.frequency,
+                rule.interval,
+                rule.start_at,
+                rule.end_at,
+                rule.occurrences,
+                rule.next_at,
+                rule.created_at,
+                rule.updated_at
+            ],
+        )?;
+    }
+
+    for rate in &document.exchange_rates {
+        tx.execute(
+            "INSERT INTO ExchangeRate (base, quote, rate, source, updatedAt) VALUES (?1, ?2, ?3, ?4, ?5)
+             ON CONFLICT (base, quote) DO UPDATE SET
+                 rate = excluded.rate, source = excluded.source, updatedAt = excluded.updatedAt
+             WHERE excluded.updatedAt > ExchangeRate.updatedAt",
+            params![rate.base, rate.quote, rate.rate, rate.source, rate.updated_at],
+        )?;
+    }
+
+    for budget in &document.budgets {
+        tx.execute(
+            "INSERT INTO Budget (id, name, cardId, tagId, monthly, amount, currency, createdAt, updatedAt)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+             ON CONFLICT (id) DO UPDATE SET
+                 name = excluded.name, cardId = excluded.cardId, tagId = excluded.tagId,
+                 monthly = excluded.monthly, amount = excluded.amount, currency = excluded.currency,
+                 updatedAt = excluded.updatedAt
+             WHERE excluded.updatedAt > Budget.updatedAt",
+            params![
+                budget.id,
+                budget.name,
+                budget.card_id,
+                budget.tag_id.as_deref().map(|id| tag_map[id].as_str()),
+                budget.monthly as i32,
+                budget.amount,
+                budget.currency,
+                budget.created_at,
+                budget.updated_at
+            ],
+        )?;
+    }
+
+    let mut changes_restored = 0;
+    for change in &document.changes {
+        // Merged entries describe another database's history; marking them
+        // keeps them out of this one's undo stack
+        let mut payload = change.payload.clone();
+        if mode == RestoreMode::Merge && payload.is_object() {
+            payload["merged_from_backup"] = serde_json::Value::Bool(true);
+        }
+        changes_restored += tx.execute(
+            "INSERT OR IGNORE INTO ChangeLog (id, cardId, kind, payload, createdAt, undoneAt)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                change.id,
+                change.card_id,
+                change.kind,
+                payload.to_string(),
+                change.created_at,
+                change.undone_at
+            ],
+        )?;
+    }
+
+    rebuild_search_index(&tx)?;
+    tx.commit()?;
+
+    Ok(RestoreResult {
+        mode: mode.as_str().to_string(),
+        schema_version: document.schema_version,
+        cards_restored: cards_restored as i32,
+        todos_restored: todos_restored as i32,
+        changes_restored: changes_restored as i32,
+    })
+}
+
+/// Repopulate `search_index` from Card, Todo and tag names.
+pub fn rebuild_search_index(conn: &Connection) -> Result<(), AppError> {
+    conn.execute_batch(
+        "DELETE FROM search_index;
+         INSERT INTO search_index(card_id, todo_id, card_title, todo_title, content)
+         SELECT id, NULL, COALESCE(title, ''), '', '' FROM Card;
+         INSERT INTO search_index(card_id, todo_id, card_title, todo_title, content)
+         SELECT td.cardId, td.id, '', td.title, (
+             SELECT COALESCE(group_concat(t.name, ' '), '')
+             FROM TodoTag tt JOIN Tag t ON t.id = tt.tagId
+             WHERE tt.todoId = td.id
+         )
+         FROM Todo td;",
+    )?;
+    Ok(())
+}
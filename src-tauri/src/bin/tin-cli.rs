@@ -0,0 +1,3 @@
+fn main() -> std::process::ExitCode {
+    tin_lib::cli::run(std::env::args().skip(1).collect())
+}
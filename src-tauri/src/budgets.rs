@@ -0,0 +1,352 @@
+//! Spending limits per card, per tag or across all cards, optionally reset
+//! every calendar month, and the over-spend check run after todo changes.
+//!
+//! A budget's usage is the sum of the todo amounts it covers, done or not,
+//! converted into the budget currency with the same exchange rates as card
+//! balances. Trashed cards never count; archived ones do.
+
+use crate::currency;
+use crate::dates::parse_timestamp;
+use crate::errors::AppError;
+use crate::models::{BudgetDto, BudgetStatusDto, BudgetWarningDto};
+use crate::money::Money;
+use crate::settings;
+use crate::store::{generate_id, now_iso, Store};
+use chrono::{DateTime, Datelike, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Deserialize;
+
+/// Emitted with a `BudgetWarningDto` when a todo change leaves a budget over.
+pub const BUDGET_EXCEEDED_EVENT: &str = "budget-exceeded";
+
+const BUDGET_SELECT: &str =
+    "SELECT b.id, b.name, b.cardId, c.title, b.tagId, t.name, b.monthly, b.amount, b.currency,
+            b.createdAt, b.updatedAt
+     FROM Budget b
+     LEFT JOIN Card c ON c.id = b.cardId
+     LEFT JOIN Tag t ON t.id = b.tagId";
+
+fn map_budget_row(row: &rusqlite::Row) -> rusqlite::Result<BudgetDto> {
+    Ok(BudgetDto {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        card_id: row.get(2)?,
+        card_title: row.get(3)?,
+        tag_id: row.get(4)?,
+        tag_name: row.get(5)?,
+        monthly: row.get::<_, i32>(6)? != 0,
+        amount: row.get::<_, Money>(7)?.to_string(),
+        currency: row.get(8)?,
+        created_at: row.get(9)?,
+        updated_at: row.get(10)?,
+    })
+}
+
+pub fn get(conn: &Connection, budget_id: &str) -> Result<BudgetDto, AppError> {
+    conn.query_row(
+        &format!("{} WHERE b.id = ?1", BUDGET_SELECT),
+        params![budget_id],
+        map_budget_row,
+    )
+    .optional()?
+    .ok_or_else(|| AppError::BudgetNotFound(budget_id.to_string()))
+}
+
+pub fn list(conn: &Connection) -> Result<Vec<BudgetDto>, AppError> {
+    let budgets = conn
+        .prepare(&format!("{} ORDER BY b.createdAt, b.id", BUDGET_SELECT))?
+        .query_map([], map_budget_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(budgets)
+}
+
+/// What a budget covers and allows, for `Store::set_budget`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct BudgetDefinition {
+    pub name: Option<String>,
+    /// Limit to one card; at most one of `card_id` and `tag_id` is set
+    pub card_id: Option<String>,
+    pub tag_id: Option<String>,
+    /// Reset every calendar month
+    pub monthly: bool,
+    pub amount: String,
+    /// Defaults to the card's currency, then to the default currency setting
+    pub currency: Option<String>,
+}
+
+impl Store {
+    /// Creates a budget, or replaces `budget_id`'s definition. A budget covers
+    /// one card, one tag or, with neither, every card.
+    pub fn set_budget(
+        &mut self,
+        budget_id: Option<&str>,
+        definition: BudgetDefinition,
+    ) -> Result<BudgetDto, AppError> {
+        let BudgetDefinition {
+            name,
+            card_id,
+            tag_id,
+            monthly,
+            amount,
+            currency,
+        } = definition;
+        if card_id.is_some() && tag_id.is_some() {
+            return Err(AppError::invalid_field(
+                "tag_id",
+                "A budget covers either a card or a tag, not both",
+            ));
+        }
+        let currency = currency
+            .as_deref()
+            .map(currency::validate_code)
+            .transpose()?;
+        let name = name.map(|n| n.trim().to_string()).filter(|n| !n.is_empty());
+        let now = now_iso();
+        let conn = self.conn();
+
+        let card_currency = match &card_id {
+            Some(id) => Some(
+                conn.query_row(
+                    "SELECT currency FROM Card WHERE id = ?1 AND deletedAt IS NULL",
+                    params![id],
+                    |row| row.get::<_, String>(0),
+                )
+                .map_err(|e| match e {
+                    rusqlite::Error::QueryReturnedNoRows => AppError::CardNotFound(id.clone()),
+                    _ => e.into(),
+                })?,
+            ),
+            None => None,
+        };
+        if let Some(id) = &tag_id {
+            conn.query_row("SELECT id FROM Tag WHERE id = ?1", params![id], |_| Ok(()))
+                .map_err(|e| match e {
+                    rusqlite::Error::QueryReturnedNoRows => AppError::TagNotFound(id.clone()),
+                    _ => e.into(),
+                })?;
+        }
+        let currency = match currency.or(card_currency) {
+            Some(code) => code,
+            None => settings::load(conn)?.default_currency,
+        };
+        let amount = currency::parse_amount(&amount, &currency)?;
+        if amount <= Money::ZERO {
+            return Err(AppError::InvalidAmount(
+                "Budget amount must be greater than zero".into(),
+            ));
+        }
+
+        let id = match budget_id {
+            Some(id) => {
+                let updated = conn.execute(
+                    "UPDATE Budget SET name = ?1, cardId = ?2, tagId = ?3, monthly = ?4, amount = ?5, currency = ?6, updatedAt = ?7
+                     WHERE id = ?8",
+                    params![name, card_id, tag_id, monthly as i32, amount, currency, now, id],
+                )?;
+                if updated == 0 {
+                    return Err(AppError::BudgetNotFound(id.to_string()));
+                }
+                id.to_string()
+            }
+            None => {
+                let id = generate_id();
+                conn.execute(
+                    "INSERT INTO Budget (id, name, cardId, tagId, monthly, amount, currency, createdAt, updatedAt)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                    params![id, name, card_id, tag_id, monthly as i32, amount, currency, now, now],
+                )?;
+                id
+            }
+        };
+
+        get(conn, &id)
+    }
+
+    pub fn delete_budget(&mut self, budget_id: &str) -> Result<(), AppError> {
+        let deleted = self
+            .conn()
+            .execute("DELETE FROM Budget WHERE id = ?1", params![budget_id])?;
+        if deleted == 0 {
+            return Err(AppError::BudgetNotFound(budget_id.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Usage of one budget or all of them, measured at `at`.
+    pub fn budget_status(
+        &self,
+        budget_id: Option<&str>,
+        at: DateTime<Utc>,
+    ) -> Result<Vec<BudgetStatusDto>, AppError> {
+        let conn = self.conn();
+        let list = match budget_id {
+            Some(id) => vec![get(conn, id)?],
+            None => list(conn)?,
+        };
+        list.into_iter()
+            .map(|budget| status(conn, budget, at))
+            .collect()
+    }
+}
+
+/// Usage of `budget`; monthly budgets are measured in the month containing `at`.
+pub fn status(
+    conn: &Connection,
+    budget: BudgetDto,
+    at: DateTime<Utc>,
+) -> Result<BudgetStatusDto, AppError> {
+    let amount = Money::parse(&budget.amount)?;
+    let month = budget.monthly.then(|| {
+        at.date_naive()
+            .with_day(1)
+            .unwrap_or(at.date_naive())
+            .format("%Y-%m-%d")
+            .to_string()
+    });
+
+    let (spent, pending, unconverted_count, period_end, percent_used): (
+        Money,
+        Money,
+        i64,
+        Option<String>,
+        f64,
+    ) = conn.query_row(
+        "WITH spending AS (
+             SELECT a.done,
+                    CASE
+                        WHEN c.currency = ?4 THEN a.amount
+                        ELSE CAST(ROUND(a.amount * COALESCE(
+                            (SELECT rate FROM ExchangeRate WHERE base = c.currency AND quote = ?4),
+                            (SELECT 1.0 / rate FROM ExchangeRate WHERE base = ?4 AND quote = c.currency)
+                        )) AS INTEGER)
+                    END AS amount
+             FROM Todo t
+             JOIN TodoCardAmount a ON a.todoId = t.id
+             JOIN Card c ON c.id = t.cardId
+             WHERE t.amount IS NOT NULL
+               AND c.deletedAt IS NULL
+               AND (?1 IS NULL OR t.cardId = ?1)
+               AND (?2 IS NULL OR EXISTS (
+                   SELECT 1 FROM TodoTag tt WHERE tt.todoId = t.id AND tt.tagId = ?2
+               ))
+               AND (?3 IS NULL OR date(COALESCE(t.scheduledAt, t.createdAt), 'start of month') = ?3)
+         )
+         SELECT COALESCE(SUM(CASE WHEN done = 1 THEN amount END), 0),
+                COALESCE(SUM(CASE WHEN done = 0 THEN amount END), 0),
+                COUNT(*) - COUNT(amount),
+                date(?3, '+1 month'),
+                ROUND(TOTAL(amount) * 100.0 / ?5, 1)
+         FROM spending",
+        params![budget.card_id, budget.tag_id, month, budget.currency, amount],
+        |row| {
+            Ok((
+                row.get(0)?,
+                row.get(1)?,
+                row.get(2)?,
+                row.get(3)?,
+                row.get(4)?,
+            ))
+        },
+    )?;
+
+    let used = spent.checked_add(pending)?;
+    Ok(BudgetStatusDto {
+        budget,
+        period_start: month,
+        period_end,
+        spent: spent.to_string(),
+        pending: pending.to_string(),
+        used: used.to_string(),
+        remaining: amount.checked_sub(used)?.to_string(),
+        percent_used,
+        exceeded: used > amount,
+        unconverted_count,
+    })
+}
+
+/// Budgets `todo_id` counts toward that are over their amount after a change
+/// to it, or `None` when all are within limits. Monthly budgets are checked
+/// for the todo's own month.
+pub fn check_todo(conn: &Connection, todo_id: &str) -> Result<Option<BudgetWarningDto>, AppError> {
+    let todo: Option<(String, Option<Money>, String)> = conn
+        .query_row(
+            "SELECT cardId, amount, COALESCE(scheduledAt, createdAt) FROM Todo WHERE id = ?1",
+            params![todo_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .optional()?;
+    // Todos without an amount cannot push a budget over
+    let Some((card_id, Some(_), date)) = todo else {
+        return Ok(None);
+    };
+    let at = parse_timestamp(&date).unwrap_or_else(Utc::now);
+
+    let budgets = conn
+        .prepare(&format!(
+            "{} WHERE (b.cardId IS NULL OR b.cardId = ?1)
+               AND (b.tagId IS NULL OR b.tagId IN (SELECT tagId FROM TodoTag WHERE todoId = ?2))
+             ORDER BY b.createdAt, b.id",
+            BUDGET_SELECT
+        ))?
+        .query_map(params![card_id, todo_id], map_budget_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut exceeded = Vec::new();
+    for budget in budgets {
+        let status = status(conn, budget, at)?;
+        if status.exceeded {
+            exceeded.push(status);
+        }
+    }
+
+    let message = match exceeded.as_slice() {
+        [] => return Ok(None),
+        [only] => format!(
+            "Over budget {}: {} of {} {} used",
+            label(&only.budget),
+            format_money(&only.used, &only.budget.currency),
+            format_money(&only.budget.amount, &only.budget.currency),
+            only.budget.currency
+        ),
+        several => format!(
+            "Over {} budgets: {}",
+            several.len(),
+            several
+                .iter()
+                .map(|s| label(&s.budget))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    };
+
+    Ok(Some(BudgetWarningDto {
+        todo_id: todo_id.to_string(),
+        card_id,
+        message,
+        budgets: exceeded,
+    }))
+}
+
+/// Quoted name for messages, e.g. `"Groceries"` or `tag "work" (monthly)`.
+fn label(budget: &BudgetDto) -> String {
+    let scope = match (&budget.name, &budget.card_title, &budget.tag_name) {
+        (Some(name), _, _) => format!("\"{}\"", name),
+        (None, Some(title), _) => format!("for card \"{}\"", title),
+        (None, None, Some(tag)) => format!("for tag \"{}\"", tag),
+        (None, None, None) if budget.card_id.is_some() => "for card".to_string(),
+        (None, None, None) => "for all cards".to_string(),
+    };
+    if budget.monthly {
+        format!("{} (monthly)", scope)
+    } else {
+        scope
+    }
+}
+
+fn format_money(amount: &str, code: &str) -> String {
+    match Money::parse(amount) {
+        Ok(money) => currency::format_amount(money, code),
+        Err(_) => amount.to_string(),
+    }
+}
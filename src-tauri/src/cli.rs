@@ -0,0 +1,483 @@
+//! `tin-cli`: the card, todo, search, export and archive commands against the
+//! app's tin.db, without starting Tauri.
+
+use crate::backup;
+use crate::csv_io;
+use crate::currency;
+use crate::db::DB_FILE_NAME;
+use crate::errors::AppError;
+use crate::models::{CardDto, ExportFilter};
+use crate::money::Money;
+use crate::pagination::CardSort;
+use crate::store::{now_iso, Store};
+use rusqlite::params;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+/// `identifier` in tauri.conf.json, which names the app data folder.
+const APP_IDENTIFIER: &str = "com.ariaj.tin";
+
+const USAGE: &str = "Usage: tin-cli [--data-dir DIR] [--json] <command> [arguments]
+
+Commands:
+  list [--archived] [--sort created|updated|archived|title|amount|remaining] [--limit N]
+  add-card <title> <amount> [--currency CODE]
+  add-todo <card> <title> [amount] [--scheduled WHEN] [--currency CODE]
+  search <query>... [--limit N]
+  export <path> [--archived] [--from WHEN] [--to WHEN] [--backup]
+  archive [<card>]
+
+<card> is a card id or its exact title. Without a card, `archive` applies the
+automatic archive policy. The database is tin.db in --data-dir, $TIN_DATA_DIR,
+or the app's data folder.";
+
+/// Options that take a value; every other option is a switch.
+const VALUE_OPTIONS: &[&str] = &[
+    "data-dir",
+    "sort",
+    "limit",
+    "currency",
+    "scheduled",
+    "from",
+    "to",
+];
+const SWITCHES: &[&str] = &["json", "archived", "backup", "help"];
+
+pub fn run(args: Vec<String>) -> ExitCode {
+    let invocation = match parse(args) {
+        Ok(invocation) => invocation,
+        Err(message) => {
+            eprintln!("error: {}\nRun `tin-cli --help` for usage.", message);
+            return ExitCode::from(2);
+        }
+    };
+    if invocation.command == Command::Help {
+        println!("{}", USAGE);
+        return ExitCode::SUCCESS;
+    }
+
+    match execute(invocation) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(CliError::Usage(message)) => {
+            eprintln!("error: {}\nRun `tin-cli --help` for usage.", message);
+            ExitCode::from(2)
+        }
+        Err(CliError::App(e)) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// A parsed command line: the global options and the command to run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Invocation {
+    pub data_dir: Option<PathBuf>,
+    pub json: bool,
+    pub command: Command,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// `--help`, or no command at all
+    Help,
+    List {
+        archived: bool,
+        sort: Option<CardSort>,
+        limit: Option<u32>,
+    },
+    AddCard {
+        title: String,
+        amount: String,
+        currency: Option<String>,
+    },
+    AddTodo {
+        card: String,
+        title: String,
+        amount: Option<String>,
+        scheduled: Option<String>,
+        currency: Option<String>,
+    },
+    Search {
+        query: String,
+        limit: Option<u32>,
+    },
+    Export {
+        path: PathBuf,
+        archived: bool,
+        from: Option<String>,
+        to: Option<String>,
+        backup: bool,
+    },
+    /// Archive one card, or apply the archive policy when `card` is `None`
+    Archive {
+        card: Option<String>,
+    },
+}
+
+/// Parse the arguments after the program name. Errors are usage messages.
+/// Options a command does not use are accepted and ignored.
+pub fn parse(args: Vec<String>) -> Result<Invocation, String> {
+    let args = Args::parse(args)?;
+    let data_dir = args.value("data-dir").map(PathBuf::from);
+    let json = args.switch("json");
+    let invocation = |command| Invocation {
+        data_dir: data_dir.clone(),
+        json,
+        command,
+    };
+
+    let Some((name, rest)) = args.positional.split_first() else {
+        return Ok(invocation(Command::Help));
+    };
+    if args.switch("help") {
+        return Ok(invocation(Command::Help));
+    }
+    let (min, max) = match name.as_str() {
+        "list" => (0, 0),
+        "add-card" => (2, 2),
+        "add-todo" => (2, 3),
+        "search" => (1, usize::MAX),
+        "export" => (1, 1),
+        "archive" => (0, 1),
+        _ => return Err(format!("Unknown command {}", name)),
+    };
+    if rest.len() < min || rest.len() > max {
+        return Err(format!("Wrong number of arguments for {}", name));
+    }
+
+    let command = match name.as_str() {
+        "list" => Command::List {
+            archived: args.switch("archived"),
+            sort: args
+                .value("sort")
+                .map(|sort| {
+                    serde_json::from_value::<CardSort>(serde_json::Value::String(sort.clone()))
+                        .map_err(|_| format!("Unknown sort {}", sort))
+                })
+                .transpose()?,
+            limit: args.limit()?,
+        },
+        "add-card" => Command::AddCard {
+            title: rest[0].clone(),
+            amount: rest[1].clone(),
+            currency: args.value("currency"),
+        },
+        "add-todo" => Command::AddTodo {
+            card: rest[0].clone(),
+            title: rest[1].clone(),
+            amount: rest.get(2).cloned(),
+            scheduled: args.value("scheduled"),
+            currency: args.value("currency"),
+        },
+        "search" => Command::Search {
+            query: rest.join(" "),
+            limit: args.limit()?,
+        },
+        "export" => Command::Export {
+            path: PathBuf::from(&rest[0]),
+            archived: args.switch("archived"),
+            from: args.value("from"),
+            to: args.value("to"),
+            backup: args.switch("backup"),
+        },
+        "archive" => Command::Archive {
+            card: rest.first().cloned(),
+        },
+        _ => unreachable!("commands are matched above"),
+    };
+    Ok(invocation(command))
+}
+
+enum CliError {
+    Usage(String),
+    App(AppError),
+}
+
+impl From<AppError> for CliError {
+    fn from(e: AppError) -> Self {
+        CliError::App(e)
+    }
+}
+
+struct Args {
+    positional: Vec<String>,
+    options: HashMap<String, String>,
+}
+
+impl Args {
+    fn parse(raw: Vec<String>) -> Result<Args, String> {
+        let mut positional = Vec::new();
+        let mut options = HashMap::new();
+        let mut raw = raw.into_iter();
+
+        while let Some(arg) = raw.next() {
+            let Some(name) = arg.strip_prefix("--").filter(|n| !n.is_empty()) else {
+                positional.push(arg);
+                continue;
+            };
+            let (name, inline) = match name.split_once('=') {
+                Some((name, value)) => (name.to_string(), Some(value.to_string())),
+                None => (name.to_string(), None),
+            };
+            if VALUE_OPTIONS.contains(&name.as_str()) {
+                let value = inline
+                    .or_else(|| raw.next())
+                    .ok_or_else(|| format!("--{} needs a value", name))?;
+                options.insert(name, value);
+            } else if SWITCHES.contains(&name.as_str()) && inline.is_none() {
+                options.insert(name, String::new());
+            } else {
+                return Err(format!("Unknown option --{}", name));
+            }
+        }
+
+        Ok(Args {
+            positional,
+            options,
+        })
+    }
+
+    fn switch(&self, name: &str) -> bool {
+        self.options.contains_key(name)
+    }
+
+    fn value(&self, name: &str) -> Option<String> {
+        self.options.get(name).cloned()
+    }
+
+    fn limit(&self) -> Result<Option<u32>, String> {
+        self.value("limit")
+            .map(|limit| {
+                limit
+                    .parse()
+                    .map_err(|_| format!("Invalid --limit: {}", limit))
+            })
+            .transpose()
+    }
+}
+
+fn execute(invocation: Invocation) -> Result<(), CliError> {
+    let data_dir = match invocation
+        .data_dir
+        .or_else(|| std::env::var_os("TIN_DATA_DIR").map(PathBuf::from))
+        .or_else(default_data_dir)
+    {
+        Some(dir) => dir,
+        None => {
+            return Err(CliError::Usage(
+                "Cannot find the app data folder; pass --data-dir".into(),
+            ))
+        }
+    };
+    std::fs::create_dir_all(&data_dir).map_err(AppError::from)?;
+    let mut store = Store::open(&data_dir.join(DB_FILE_NAME))?;
+
+    let json = invocation.json;
+    match invocation.command {
+        Command::Help => Ok(()),
+        Command::List {
+            archived,
+            sort,
+            limit,
+        } => {
+            // Without --limit, follow the cursor through every page
+            let mut cards = Vec::new();
+            let mut cursor = None;
+            loop {
+                let page = if archived {
+                    store.list_archived_cards(limit, cursor.as_deref(), sort, None)?
+                } else {
+                    store.list_cards(limit, cursor.as_deref(), sort, None)?
+                };
+                cards.extend(page.items);
+                match page.next_cursor {
+                    Some(next) if limit.is_none() => cursor = Some(next),
+                    _ => break,
+                }
+            }
+            output(json, &cards, |cards| {
+                cards.iter().map(card_line).collect::<Vec<_>>().join("\n")
+            })
+        }
+        Command::AddCard {
+            title,
+            amount,
+            currency,
+        } => {
+            let card = store.create_card(Some(&title), &amount, currency.as_deref())?;
+            output(json, &card, card_line)
+        }
+        Command::AddTodo {
+            card,
+            title,
+            amount,
+            scheduled,
+            currency,
+        } => {
+            let card_id = find_card(&store, &card)?;
+            let result = store.add_todo(
+                &card_id,
+                &title,
+                amount.as_deref(),
+                scheduled.is_none(),
+                scheduled.as_deref(),
+                currency.as_deref(),
+            )?;
+            if !json {
+                if let Some(warning) = &result.warning {
+                    eprintln!("warning: {}", warning.message);
+                }
+            }
+            output(json, &result, |result| {
+                format!("{}\n{}", result.todo.id, card_line(&result.updated_card))
+            })
+        }
+        Command::Search { query, limit } => {
+            let page = store.search(&query, limit, None)?;
+            output(json, &page.items, |items| {
+                items
+                    .iter()
+                    .map(|r| {
+                        format!(
+                            "{}  {}  {}",
+                            r.card_id,
+                            r.todo_id.as_deref().unwrap_or("-"),
+                            match (&r.card_title, &r.todo_title) {
+                                (_, Some(todo)) => todo.clone(),
+                                (Some(card), None) => card.clone(),
+                                (None, None) => String::new(),
+                            }
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            })
+        }
+        Command::Export {
+            path,
+            archived,
+            from,
+            to,
+            backup,
+        } => {
+            if backup {
+                let result = backup::export(store.conn(), &path, &now_iso())?;
+                output(json, &result, |r| {
+                    format!(
+                        "Wrote {} cards and {} todos to {}",
+                        r.card_count, r.todo_count, r.path
+                    )
+                })
+            } else {
+                let filter = ExportFilter {
+                    card_ids: None,
+                    include_archived: archived,
+                    from,
+                    to,
+                };
+                let result = csv_io::export(store.conn(), &path, &filter)?;
+                output(json, &result, |r| {
+                    format!("Wrote {} rows to {}", r.row_count, r.path)
+                })
+            }
+        }
+        Command::Archive { card } => match card {
+            Some(card) => {
+                let card_id = find_card(&store, &card)?;
+                let card = store.archive_card(&card_id)?;
+                output(json, &card, card_line)
+            }
+            None => {
+                let result = store.archive_old_cards()?;
+                output(json, &result, |r| {
+                    format!("Archived {} cards", r.archived_count)
+                })
+            }
+        },
+    }
+}
+
+fn output<T: Serialize>(
+    json: bool,
+    value: &T,
+    text: impl Fn(&T) -> String,
+) -> Result<(), CliError> {
+    let out = if json {
+        serde_json::to_string_pretty(value)
+            .map_err(|e| AppError::Internal(format!("Failed to write JSON: {}", e)))?
+    } else {
+        text(value)
+    };
+    if out.is_empty() {
+        return Ok(());
+    }
+    match writeln!(io::stdout().lock(), "{}", out) {
+        // Piped into `head` and the like
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        result => Ok(result.map_err(AppError::from)?),
+    }
+}
+
+/// `<id>  <title>  <remaining> of <amount> <currency>`
+fn card_line(card: &CardDto) -> String {
+    let amount = |value: &str| match Money::parse(value) {
+        Ok(money) => currency::format_amount(money, &card.currency),
+        Err(_) => value.to_string(),
+    };
+    format!(
+        "{}  {}  {} of {} {}",
+        card.id,
+        card.title.as_deref().unwrap_or("(untitled)"),
+        amount(&card.remaining),
+        amount(&card.amount),
+        card.currency
+    )
+}
+
+/// A card id, or the exact (case-insensitive) title of one card not in the trash.
+fn find_card(store: &Store, value: &str) -> Result<String, AppError> {
+    let conn = store.conn();
+    let by_id: bool = conn.query_row(
+        "SELECT COUNT(*) > 0 FROM Card WHERE id = ?1",
+        params![value],
+        |row| row.get(0),
+    )?;
+    if by_id {
+        return Ok(value.to_string());
+    }
+
+    let ids = conn
+        .prepare("SELECT id FROM Card WHERE deletedAt IS NULL AND title = ?1 COLLATE NOCASE")?
+        .query_map(params![value], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+    match ids.as_slice() {
+        [id] => Ok(id.clone()),
+        [] => Err(AppError::CardNotFound(value.to_string())),
+        _ => Err(AppError::invalid_field(
+            "card",
+            format!("Several cards are titled {:?}; use the card id", value),
+        )),
+    }
+}
+
+/// The folder Tauri's `app_data_dir()` resolves to on this platform.
+fn default_data_dir() -> Option<PathBuf> {
+    let home = || std::env::var_os("HOME").map(PathBuf::from);
+    let absolute = |value: OsString| Some(PathBuf::from(value)).filter(|p| p.is_absolute());
+
+    let base = if cfg!(target_os = "windows") {
+        std::env::var_os("APPDATA").and_then(absolute)
+    } else if cfg!(target_os = "macos") {
+        home().map(|h| h.join("Library").join("Application Support"))
+    } else {
+        std::env::var_os("XDG_DATA_HOME")
+            .and_then(absolute)
+            .or_else(|| home().map(|h| h.join(".local").join("share")))
+    };
+    base.map(|b| b.join(APP_IDENTIFIER))
+}
@@ -1,168 +1,55 @@
-use crate::db::{with_db, with_db_mut};
+use crate::backup::{self, RestoreMode};
+use crate::budgets::{BudgetDefinition, BUDGET_EXCEEDED_EVENT};
+use crate::csv_io;
+use crate::dates::{format_timestamp, parse_timestamp};
+use crate::db::{self, with_db, with_db_mut, with_store, with_store_mut};
 use crate::errors::AppError;
+use crate::migrations;
 use crate::models::*;
+use crate::pagination::{self, CardSort, SortOrder};
+use crate::recurrence::Frequency;
+use crate::reports::{self, Granularity};
+use crate::search_query;
+use crate::settings::{ArchivePolicy, Settings, SETTINGS_CHANGED_EVENT};
+use crate::snapshots::{self, Snapshot};
+use crate::store::{now_iso, Store, TodoChanges};
+use crate::timeline;
 use chrono::Utc;
-use rusqlite::params;
-
-fn generate_id() -> String {
-    uuid::Uuid::new_v4().to_string()
-}
-
-fn now_iso() -> String {
-    Utc::now().format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string()
-}
+use std::path::Path;
+use tauri::{AppHandle, Emitter};
 
 #[tauri::command]
-pub fn list_cards() -> Result<Vec<CardDto>, AppError> {
-    with_db(|conn| {
-        let mut stmt = conn.prepare(
-            "SELECT id, title, amount, lockedAmount, archived, createdAt, updatedAt, archivedAt 
-             FROM Card WHERE archived = 0 ORDER BY createdAt DESC",
-        )?;
-
-        let cards = stmt
-            .query_map([], |row| {
-                Ok(CardDto {
-                    id: row.get(0)?,
-                    title: row.get(1)?,
-                    amount: format!("{:.6}", row.get::<_, f64>(2)?),
-                    locked_amount: row.get::<_, Option<f64>>(3)?.map(|a| format!("{:.6}", a)),
-                    archived: row.get::<_, i32>(4)? != 0,
-                    created_at: row.get(5)?,
-                    updated_at: row.get(6)?,
-                    archived_at: row.get(7)?,
-                })
-            })?
-            .collect::<Result<Vec<_>, _>>()?;
-
-        Ok(cards)
-    })
+pub fn list_cards(
+    limit: Option<u32>,
+    cursor: Option<String>,
+    sort: Option<CardSort>,
+    order: Option<SortOrder>,
+) -> Result<Page<CardDto>, AppError> {
+    with_store(|store| store.list_cards(limit, cursor.as_deref(), sort, order))
 }
 
 #[tauri::command]
-pub fn list_archived_cards() -> Result<Vec<CardDto>, AppError> {
-    with_db(|conn| {
-        let mut stmt = conn.prepare(
-            "SELECT id, title, amount, lockedAmount, archived, createdAt, updatedAt, archivedAt 
-             FROM Card WHERE archived = 1 ORDER BY archivedAt DESC",
-        )?;
-
-        let cards = stmt
-            .query_map([], |row| {
-                Ok(CardDto {
-                    id: row.get(0)?,
-                    title: row.get(1)?,
-                    amount: format!("{:.6}", row.get::<_, f64>(2)?),
-                    locked_amount: row.get::<_, Option<f64>>(3)?.map(|a| format!("{:.6}", a)),
-                    archived: row.get::<_, i32>(4)? != 0,
-                    created_at: row.get(5)?,
-                    updated_at: row.get(6)?,
-                    archived_at: row.get(7)?,
-                })
-            })?
-            .collect::<Result<Vec<_>, _>>()?;
-
-        Ok(cards)
-    })
+pub fn list_archived_cards(
+    limit: Option<u32>,
+    cursor: Option<String>,
+    sort: Option<CardSort>,
+    order: Option<SortOrder>,
+) -> Result<Page<CardDto>, AppError> {
+    with_store(|store| store.list_archived_cards(limit, cursor.as_deref(), sort, order))
 }
 
 #[tauri::command]
 pub fn get_card(card_id: String) -> Result<CardWithTodosDto, AppError> {
-    with_db(|conn| {
-        let card = conn
-            .query_row(
-                "SELECT id, title, amount, lockedAmount, archived, createdAt, updatedAt, archivedAt 
-             FROM Card WHERE id = ?1",
-                params![card_id],
-                |row| {
-                    Ok(CardDto {
-                        id: row.get(0)?,
-                        title: row.get(1)?,
-                        amount: format!("{:.6}", row.get::<_, f64>(2)?),
-                        locked_amount: row.get::<_, Option<f64>>(3)?.map(|a| format!("{:.6}", a)),
-                        archived: row.get::<_, i32>(4)? != 0,
-                        created_at: row.get(5)?,
-                        updated_at: row.get(6)?,
-                        archived_at: row.get(7)?,
-                    })
-                },
-            )
-            .map_err(|e| match e {
-                rusqlite::Error::QueryReturnedNoRows => AppError::CardNotFound(card_id.clone()),
-                _ => AppError::Database(e),
-            })?;
-
-        let mut stmt = conn.prepare(
-            "SELECT id, cardId, title, amount, done, scheduledAt, orderIndex, createdAt, updatedAt 
-             FROM Todo WHERE cardId = ?1 ORDER BY orderIndex ASC, createdAt ASC",
-        )?;
-
-        let todos = stmt
-            .query_map(params![card_id], |row| {
-                Ok(TodoDto {
-                    id: row.get(0)?,
-                    card_id: row.get(1)?,
-                    title: row.get(2)?,
-                    amount: row.get::<_, Option<f64>>(3)?.map(|a| format!("{:.6}", a)),
-                    done: row.get::<_, i32>(4)? != 0,
-                    scheduled_at: row.get(5)?,
-                    order_index: row.get(6)?,
-                    created_at: row.get(7)?,
-                    updated_at: row.get(8)?,
-                })
-            })?
-            .collect::<Result<Vec<_>, _>>()?;
-
-        Ok(CardWithTodosDto {
-            id: card.id,
-            title: card.title,
-            amount: card.amount,
-            locked_amount: card.locked_amount,
-            archived: card.archived,
-            created_at: card.created_at,
-            updated_at: card.updated_at,
-            archived_at: card.archived_at,
-            todos,
-        })
-    })
+    with_store(|store| store.get_card(&card_id))
 }
 
 #[tauri::command]
-pub fn create_card(title: Option<String>, amount: String) -> Result<CardDto, AppError> {
-    let amount_f: f64 = amount
-        .parse()
-        .map_err(|_| AppError::InvalidAmount(amount.clone()))?;
-    let id = generate_id();
-    let now = now_iso();
-
-    with_db_mut(|conn| {
-        let tx = conn.transaction()?;
-
-        tx.execute(
-            "INSERT INTO Card (id, title, amount, createdAt, updatedAt) VALUES (?1, ?2, ?3, ?4, ?5)",
-            params![id, title, amount_f, now, now],
-        )?;
-
-        let changelog_id = generate_id();
-        let payload = serde_json::json!({ "title": title, "amount": amount });
-        tx.execute(
-            "INSERT INTO ChangeLog (id, cardId, kind, payload, createdAt) VALUES (?1, ?2, ?3, ?4, ?5)",
-            params![changelog_id, id, "created", payload.to_string(), now],
-        )?;
-
-        tx.commit()?;
-
-        Ok(CardDto {
-            id: id.clone(),
-            title,
-            amount: format!("{:.6}", amount_f),
-            locked_amount: None,
-            archived: false,
-            created_at: now.clone(),
-            updated_at: now,
-            archived_at: None,
-        })
-    })
+pub fn create_card(
+    title: Option<String>,
+    amount: String,
+    currency: Option<String>,
+) -> Result<CardDto, AppError> {
+    with_store_mut(|store| store.create_card(title.as_deref(), &amount, currency.as_deref()))
 }
 
 #[tauri::command]
@@ -170,511 +57,518 @@ pub fn update_card(
     card_id: String,
     title: Option<String>,
     amount: Option<String>,
+    currency: Option<String>,
 ) -> Result<CardDto, AppError> {
-    let now = now_iso();
+    with_store_mut(|store| {
+        store.update_card(
+            &card_id,
+            title.as_deref(),
+            amount.as_deref(),
+            currency.as_deref(),
+        )
+    })
+}
 
-    with_db_mut(|conn| {
-        let tx = conn.transaction()?;
-
-        let existing: (Option<String>, f64) = tx
-            .query_row(
-                "SELECT title, amount FROM Card WHERE id = ?1",
-                params![card_id],
-                |row| Ok((row.get(0)?, row.get(1)?)),
-            )
-            .map_err(|e| match e {
-                rusqlite::Error::QueryReturnedNoRows => AppError::CardNotFound(card_id.clone()),
-                _ => AppError::Database(e),
-            })?;
-
-        let new_title = title.clone().or(existing.0);
-        let new_amount = match &amount {
-            Some(a) => a.parse().map_err(|_| AppError::InvalidAmount(a.clone()))?,
-            None => existing.1,
-        };
-
-        tx.execute(
-            "UPDATE Card SET title = ?1, amount = ?2, updatedAt = ?3 WHERE id = ?4",
-            params![new_title, new_amount, now, card_id],
-        )?;
+#[tauri::command]
+pub fn set_locked_amount(card_id: String, amount: Option<String>) -> Result<CardDto, AppError> {
+    with_store_mut(|store| store.set_locked_amount(&card_id, amount.as_deref()))
+}
 
-        let changelog_id = generate_id();
-        let payload =
-            serde_json::json!({ "title": new_title, "amount": format!("{:.6}", new_amount) });
-        tx.execute(
-            "INSERT INTO ChangeLog (id, cardId, kind, payload, createdAt) VALUES (?1, ?2, ?3, ?4, ?5)",
-            params![changelog_id, card_id, "updated", payload.to_string(), now],
-        )?;
+/// Moves the card (and with it, its todos) to the trash.
+#[tauri::command]
+pub fn delete_card(card_id: String) -> Result<OkResponse, AppError> {
+    with_store_mut(|store| store.delete_card(&card_id))?;
+    Ok(OkResponse { ok: true })
+}
 
-        tx.commit()?;
-
-        let card = conn.query_row(
-            "SELECT id, title, amount, lockedAmount, archived, createdAt, updatedAt, archivedAt FROM Card WHERE id = ?1",
-            params![card_id],
-            |row| {
-                Ok(CardDto {
-                    id: row.get(0)?,
-                    title: row.get(1)?,
-                    amount: format!("{:.6}", row.get::<_, f64>(2)?),
-                    locked_amount: row.get::<_, Option<f64>>(3)?.map(|a| format!("{:.6}", a)),
-                    archived: row.get::<_, i32>(4)? != 0,
-                    created_at: row.get(5)?,
-                    updated_at: row.get(6)?,
-                    archived_at: row.get(7)?,
-                })
-            },
-        )?;
+#[tauri::command]
+pub fn list_trashed_cards() -> Result<Vec<CardDto>, AppError> {
+    with_store(Store::list_trashed_cards)
+}
 
-        Ok(card)
-    })
+#[tauri::command]
+pub fn restore_card(card_id: String) -> Result<CardDto, AppError> {
+    with_store_mut(|store| store.restore_card(&card_id))
 }
 
+/// Permanently removes cards that have been in the trash longer than
+/// `older_than_days` (default `Settings::trash_retention_days`), along with
+/// their todos and history.
 #[tauri::command]
-pub fn delete_card(card_id: String) -> Result<OkResponse, AppError> {
-    with_db_mut(|conn| {
-        conn.execute("DELETE FROM Card WHERE id = ?1", params![card_id])?;
-        Ok(OkResponse { ok: true })
-    })
+pub fn purge_trash(older_than_days: Option<i64>) -> Result<PurgeResult, AppError> {
+    with_store_mut(|store| store.purge_trash(older_than_days))
 }
 
 #[tauri::command]
 pub fn add_todo(
+    app: AppHandle,
     card_id: String,
     title: String,
     amount: Option<String>,
     use_current_time: bool,
     scheduled_at: Option<String>,
+    currency: Option<String>,
 ) -> Result<AddTodoResult, AppError> {
-    let todo_amount: Option<f64> = match &amount {
-        Some(a) => Some(a.parse().map_err(|_| AppError::InvalidAmount(a.clone()))?),
-        None => None,
-    };
-
-    let todo_id = generate_id();
-    let now = now_iso();
-    let actual_scheduled_at = if use_current_time {
-        Some(now.clone())
-    } else {
-        scheduled_at.clone()
-    };
-
-    with_db_mut(|conn| {
-        let tx = conn.transaction()?;
-
-        // Verify card exists (no deduction - lockedAmount is user-only editable)
-        tx.query_row(
-            "SELECT id FROM Card WHERE id = ?1",
-            params![card_id],
-            |_| Ok(()),
+    let result = with_store_mut(|store| {
+        store.add_todo(
+            &card_id,
+            &title,
+            amount.as_deref(),
+            use_current_time,
+            scheduled_at.as_deref(),
+            currency.as_deref(),
         )
-        .map_err(|e| match e {
-            rusqlite::Error::QueryReturnedNoRows => AppError::CardNotFound(card_id.clone()),
-            _ => AppError::Database(e),
-        })?;
-
-        // Update only updatedAt (NO amount deduction)
-        tx.execute(
-            "UPDATE Card SET updatedAt = ?1 WHERE id = ?2",
-            params![now, card_id],
-        )?;
-
-        let max_order: i32 = tx
-            .query_row(
-                "SELECT COALESCE(MAX(orderIndex), 0) FROM Todo WHERE cardId = ?1",
-                params![card_id],
-                |row| row.get(0),
-            )
-            .unwrap_or(0);
-
-        tx.execute(
-            "INSERT INTO Todo (id, cardId, title, amount, done, createdAt, scheduledAt, orderIndex, updatedAt) 
-             VALUES (?1, ?2, ?3, ?4, 0, ?5, ?6, ?7, ?8)",
-            params![todo_id, card_id, title, todo_amount, now, actual_scheduled_at, max_order + 1, now],
-        )?;
-
-        let changelog_id = generate_id();
-        let payload = serde_json::json!({
-            "todo_id": todo_id,
-            "title": title,
-            "amount": amount
-        });
-        tx.execute(
-            "INSERT INTO ChangeLog (id, cardId, kind, payload, createdAt) VALUES (?1, ?2, ?3, ?4, ?5)",
-            params![changelog_id, card_id, "todo_added", payload.to_string(), now],
-        )?;
-
-        tx.commit()?;
-
-        let todo = TodoDto {
-            id: todo_id,
-            card_id: card_id.clone(),
-            title,
-            amount: todo_amount.map(|a| format!("{:.6}", a)),
-            done: false,
-            scheduled_at: actual_scheduled_at,
-            order_index: max_order + 1,
-            created_at: now.clone(),
-            updated_at: now.clone(),
-        };
-
-        let updated_card = conn.query_row(
-            "SELECT id, title, amount, lockedAmount, archived, createdAt, updatedAt, archivedAt FROM Card WHERE id = ?1",
-            params![card_id],
-            |row| {
-                Ok(CardDto {
-                    id: row.get(0)?,
-                    title: row.get(1)?,
-                    amount: format!("{:.6}", row.get::<_, f64>(2)?),
-                    locked_amount: row.get::<_, Option<f64>>(3)?.map(|a| format!("{:.6}", a)),
-                    archived: row.get::<_, i32>(4)? != 0,
-                    created_at: row.get(5)?,
-                    updated_at: row.get(6)?,
-                    archived_at: row.get(7)?,
-                })
-            },
-        )?;
-
-        Ok(AddTodoResult { todo, updated_card })
-    })
+    })?;
+    notify_budget_exceeded(&app, &result.warning);
+    Ok(result)
 }
 
+/// Returns the todo with its card's recomputed balances, like `add_todo`.
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub fn update_todo(
+    app: AppHandle,
     todo_id: String,
     title: Option<String>,
     amount: Option<String>,
     done: Option<bool>,
     scheduled_at: Option<String>,
     order_index: Option<i32>,
-) -> Result<TodoDto, AppError> {
-    let now = now_iso();
+    currency: Option<String>,
+) -> Result<AddTodoResult, AppError> {
+    let changes = TodoChanges {
+        title,
+        amount,
+        done,
+        scheduled_at,
+        order_index,
+        currency,
+    };
+    let result = with_store_mut(|store| store.update_todo(&todo_id, changes))?;
+    notify_budget_exceeded(&app, &result.warning);
+    Ok(result)
+}
 
-    with_db_mut(|conn| {
-        let tx = conn.transaction()?;
-
-        let existing: (String, String, Option<f64>, bool, Option<String>, i32) = tx.query_row(
-            "SELECT cardId, title, amount, done, scheduledAt, orderIndex FROM Todo WHERE id = ?1",
-            params![todo_id],
-            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get::<_, i32>(3)? != 0, row.get(4)?, row.get(5)?)),
-        ).map_err(|e| match e {
-            rusqlite::Error::QueryReturnedNoRows => AppError::TodoNotFound(todo_id.clone()),
-            _ => AppError::Database(e),
-        })?;
-
-        let new_title = title.unwrap_or(existing.1);
-        let new_amount = match amount {
-            Some(a) => Some(a.parse::<f64>().map_err(|_| AppError::InvalidAmount(a))?),
-            None => existing.2,
-        };
-        let new_done = done.unwrap_or(existing.3);
-        let new_scheduled_at = scheduled_at.or(existing.4);
-        let new_order_index = order_index.unwrap_or(existing.5);
-
-        tx.execute(
-            "UPDATE Todo SET title = ?1, amount = ?2, done = ?3, scheduledAt = ?4, orderIndex = ?5, updatedAt = ?6 WHERE id = ?7",
-            params![new_title, new_amount, new_done as i32, new_scheduled_at, new_order_index, now, todo_id],
-        )?;
+pub(crate) fn notify_budget_exceeded(app: &AppHandle, warning: &Option<BudgetWarningDto>) {
+    if let Some(warning) = warning {
+        if let Err(e) = app.emit(BUDGET_EXCEEDED_EVENT, warning) {
+            log::warn!("Failed to emit {}: {}", BUDGET_EXCEEDED_EVENT, e);
+        }
+    }
+}
 
-        let changelog_id = generate_id();
-        let payload =
-            serde_json::json!({ "todo_id": todo_id, "title": new_title, "done": new_done });
-        tx.execute(
-            "INSERT INTO ChangeLog (id, cardId, kind, payload, createdAt) VALUES (?1, ?2, ?3, ?4, ?5)",
-            params![changelog_id, existing.0, "todo_updated", payload.to_string(), now],
-        )?;
+#[tauri::command]
+pub fn delete_todo(todo_id: String) -> Result<OkResponse, AppError> {
+    with_store_mut(|store| store.delete_todo(&todo_id))?;
+    Ok(OkResponse { ok: true })
+}
 
-        tx.commit()?;
-
-        Ok(TodoDto {
-            id: todo_id,
-            card_id: existing.0,
-            title: new_title,
-            amount: new_amount.map(|a| format!("{:.6}", a)),
-            done: new_done,
-            scheduled_at: new_scheduled_at,
-            order_index: new_order_index,
-            created_at: now.clone(),
-            updated_at: now,
-        })
-    })
+#[tauri::command]
+pub fn list_tags() -> Result<Vec<TagDto>, AppError> {
+    with_store(Store::list_tags)
 }
 
 #[tauri::command]
-pub fn delete_todo(todo_id: String) -> Result<OkResponse, AppError> {
-    let now = now_iso();
+pub fn create_tag(name: String, color: Option<String>) -> Result<TagDto, AppError> {
+    with_store_mut(|store| store.create_tag(&name, color.as_deref()))
+}
 
-    with_db_mut(|conn| {
-        let tx = conn.transaction()?;
-
-        let (card_id, title): (String, String) = tx
-            .query_row(
-                "SELECT cardId, title FROM Todo WHERE id = ?1",
-                params![todo_id],
-                |row| Ok((row.get(0)?, row.get(1)?)),
-            )
-            .map_err(|e| match e {
-                rusqlite::Error::QueryReturnedNoRows => AppError::TodoNotFound(todo_id.clone()),
-                _ => AppError::Database(e),
-            })?;
-
-        tx.execute("DELETE FROM Todo WHERE id = ?1", params![todo_id])?;
-
-        let changelog_id = generate_id();
-        let payload = serde_json::json!({ "todo_id": todo_id, "title": title });
-        tx.execute(
-            "INSERT INTO ChangeLog (id, cardId, kind, payload, createdAt) VALUES (?1, ?2, ?3, ?4, ?5)",
-            params![changelog_id, card_id, "todo_deleted", payload.to_string(), now],
-        )?;
+#[tauri::command]
+pub fn update_tag(
+    tag_id: String,
+    name: Option<String>,
+    color: Option<String>,
+) -> Result<TagDto, AppError> {
+    with_store_mut(|store| store.update_tag(&tag_id, name.as_deref(), color.as_deref()))
+}
 
-        tx.commit()?;
-        Ok(OkResponse { ok: true })
-    })
+#[tauri::command]
+pub fn delete_tag(tag_id: String) -> Result<OkResponse, AppError> {
+    with_store_mut(|store| store.delete_tag(&tag_id))?;
+    Ok(OkResponse { ok: true })
+}
+
+/// Replaces the todo's tags with `tag_ids`.
+#[tauri::command]
+pub fn set_todo_tags(todo_id: String, tag_ids: Vec<String>) -> Result<TodoDto, AppError> {
+    with_store_mut(|store| store.set_todo_tags(&todo_id, &tag_ids))
+}
+
+/// Sums todo amounts per tag; see `Store::totals_by_tag`.
+#[tauri::command]
+pub fn totals_by_tag(
+    card_id: Option<String>,
+    from: Option<String>,
+    to: Option<String>,
+) -> Result<Vec<TagTotalDto>, AppError> {
+    with_store(|store| store.totals_by_tag(card_id.as_deref(), from.as_deref(), to.as_deref()))
 }
 
+/// Spending per day, week or month with per-card breakdowns and the change
+/// from each previous period. See `reports`.
 #[tauri::command]
-pub fn search(query: String) -> Result<Vec<SearchResultDto>, AppError> {
+pub fn spending_by_period(
+    granularity: Granularity,
+    from: Option<String>,
+    to: Option<String>,
+    include_archived: Option<bool>,
+) -> Result<SpendingReportDto, AppError> {
     with_db(|conn| {
-        let mut date_after: Option<String> = None;
-        let mut date_before: Option<String> = None;
-        let mut text_parts: Vec<String> = Vec::new();
-
-        for part in query.split_whitespace() {
-            if part.starts_with("after:") {
-                date_after = Some(part.trim_start_matches("after:").to_string());
-            } else if part.starts_with("before:") {
-                date_before = Some(part.trim_start_matches("before:").to_string());
-            } else {
-                text_parts.push(part.to_string());
-            }
-        }
+        reports::spending_by_period(
+            conn,
+            granularity,
+            from.as_deref(),
+            to.as_deref(),
+            include_archived.unwrap_or(false),
+            Utc::now(),
+        )
+    })
+}
 
-        let fts_query = text_parts.join(" ");
+/// Creates a budget, or replaces `budget_id`'s definition. A budget covers
+/// `card_id`, `tag_id` or, with neither, every card; `currency` defaults to the
+/// card's currency, then to the default currency setting.
+#[tauri::command]
+pub fn set_budget(
+    budget_id: Option<String>,
+    name: Option<String>,
+    card_id: Option<String>,
+    tag_id: Option<String>,
+    monthly: Option<bool>,
+    amount: String,
+    currency: Option<String>,
+) -> Result<BudgetDto, AppError> {
+    let definition = BudgetDefinition {
+        name,
+        card_id,
+        tag_id,
+        monthly: monthly.unwrap_or(false),
+        amount,
+        currency,
+    };
+    with_store_mut(|store| store.set_budget(budget_id.as_deref(), definition))
+}
 
-        if fts_query.is_empty() && date_after.is_none() && date_before.is_none() {
-            return Ok(Vec::new());
-        }
+#[tauri::command]
+pub fn delete_budget(budget_id: String) -> Result<OkResponse, AppError> {
+    with_store_mut(|store| store.delete_budget(&budget_id))?;
+    Ok(OkResponse { ok: true })
+}
 
-        let mut results = Vec::new();
-
-        if !fts_query.is_empty() {
-            let mut stmt = conn.prepare(
-                "SELECT card_id, todo_id, card_title, todo_title, snippet(search_index, 4, '<b>', '</b>', '...', 32) as snippet
-                 FROM search_index WHERE search_index MATCH ?1 ORDER BY rank LIMIT 50"
-            )?;
-
-            let rows = stmt.query_map(params![format!("{}*", fts_query)], |row| {
-                Ok(SearchResultDto {
-                    card_id: row.get(0)?,
-                    todo_id: row.get(1)?,
-                    card_title: row.get(2)?,
-                    todo_title: row.get(3)?,
-                    snippet: row.get(4)?,
-                })
-            })?;
+/// Usage of one budget or all of them. Monthly budgets are measured in the
+/// month containing `at` (default now), which takes the same forms as the
+/// search `after:` filter.
+#[tauri::command]
+pub fn budget_status(
+    budget_id: Option<String>,
+    at: Option<String>,
+) -> Result<Vec<BudgetStatusDto>, AppError> {
+    let now = Utc::now();
+    let at = match &at {
+        Some(value) => search_query::resolve_date(value, now)
+            .ok_or_else(|| AppError::invalid_field("at", format!("Invalid date: {}", value)))?,
+        None => now,
+    };
 
-            for row in rows {
-                results.push(row?);
-            }
-        }
+    with_store(|store| store.budget_status(budget_id.as_deref(), at))
+}
 
-        if let (Some(after), Some(before)) = (&date_after, &date_before) {
-            let mut stmt = conn.prepare(
-                "SELECT id, NULL, title, NULL, title FROM Card WHERE createdAt >= ?1 AND createdAt <= ?2 LIMIT 50"
-            )?;
-            let rows = stmt.query_map(params![after, before], |row| {
-                Ok(SearchResultDto {
-                    card_id: row.get(0)?,
-                    todo_id: row.get(1)?,
-                    card_title: row.get(2)?,
-                    todo_title: row.get(3)?,
-                    snippet: row.get::<_, Option<String>>(4)?.unwrap_or_default(),
-                })
-            })?;
-            for row in rows {
-                results.push(row?);
-            }
-        }
+/// Makes `todo_id` the template of a recurring series. Occurrences go into
+/// `card_id` (default: the template's card) starting one interval after the
+/// template's `scheduledAt` (or `createdAt`).
+#[tauri::command]
+pub fn set_recurrence(
+    todo_id: String,
+    frequency: String,
+    interval: Option<u32>,
+    end_at: Option<String>,
+    card_id: Option<String>,
+) -> Result<RecurrenceDto, AppError> {
+    let frequency = Frequency::parse(&frequency)?;
+    with_store_mut(|store| {
+        store.set_recurrence(
+            &todo_id,
+            frequency,
+            interval,
+            end_at.as_deref(),
+            card_id.as_deref(),
+        )
+    })
+}
 
-        Ok(results)
+#[tauri::command]
+pub fn clear_recurrence(todo_id: String) -> Result<OkResponse, AppError> {
+    with_store_mut(|store| store.clear_recurrence(&todo_id))?;
+    Ok(OkResponse { ok: true })
+}
+
+#[tauri::command]
+pub fn list_recurrences() -> Result<Vec<RecurrenceDto>, AppError> {
+    with_store(Store::list_recurrences)
+}
+
+/// Creates todos for all recurrence occurrences that are due now.
+#[tauri::command]
+pub fn run_recurrences() -> Result<RecurrenceRunResult, AppError> {
+    Ok(RecurrenceRunResult {
+        created_count: with_store_mut(|store| store.run_recurrences(Utc::now()))?,
     })
 }
 
 #[tauri::command]
-pub fn recent_changes(limit: Option<i32>) -> Result<Vec<ChangeLogDto>, AppError> {
-    let limit = limit.unwrap_or(50);
+pub fn search(
+    query: String,
+    limit: Option<u32>,
+    cursor: Option<String>,
+) -> Result<Page<SearchResultDto>, AppError> {
+    with_store(|store| store.search(&query, limit, cursor.as_deref()))
+}
 
-    with_db(|conn| {
-        let mut stmt = conn.prepare(
-            "SELECT id, cardId, kind, payload, createdAt FROM ChangeLog ORDER BY createdAt DESC LIMIT ?1"
-        )?;
+#[tauri::command]
+pub fn recent_changes(
+    limit: Option<u32>,
+    cursor: Option<String>,
+) -> Result<Page<ChangeLogDto>, AppError> {
+    with_store(|store| store.recent_changes(limit, cursor.as_deref()))
+}
 
-        let changes = stmt
-            .query_map(params![limit], |row| {
-                let payload_str: String = row.get(3)?;
-                let payload: serde_json::Value =
-                    serde_json::from_str(&payload_str).unwrap_or(serde_json::json!({}));
-                Ok(ChangeLogDto {
-                    id: row.get(0)?,
-                    card_id: row.get(1)?,
-                    kind: row.get(2)?,
-                    payload,
-                    created_at: row.get(4)?,
-                })
-            })?
-            .collect::<Result<Vec<_>, _>>()?;
+/// The card's own ChangeLog, newest first, with field-level diffs.
+#[tauri::command]
+pub fn card_history(
+    card_id: String,
+    limit: Option<u32>,
+    cursor: Option<String>,
+) -> Result<Page<HistoryEntryDto>, AppError> {
+    let limit = pagination::page_limit(Some(limit.unwrap_or(50)))?;
 
-        Ok(changes)
-    })
+    with_db(|conn| timeline::card_history(conn, &card_id, limit, cursor))
+}
+
+/// The card and its todos as they were at `timestamp`.
+#[tauri::command]
+pub fn card_history_at(card_id: String, timestamp: String) -> Result<CardAtDto, AppError> {
+    let at = parse_timestamp(&timestamp).ok_or_else(|| {
+        AppError::invalid_field("timestamp", format!("Invalid timestamp: {}", timestamp))
+    })?;
+
+    with_db(|conn| timeline::card_at(conn, &card_id, at))
 }
 
 #[tauri::command]
 pub fn archive_card(card_id: String) -> Result<CardDto, AppError> {
-    let now = now_iso();
+    with_store_mut(|store| store.archive_card(&card_id))
+}
 
-    with_db_mut(|conn| {
-        let tx = conn.transaction()?;
+#[tauri::command]
+pub fn unarchive_card(card_id: String) -> Result<CardDto, AppError> {
+    with_store_mut(|store| store.unarchive_card(&card_id))
+}
 
-        // Check card exists
-        tx.query_row(
-            "SELECT id FROM Card WHERE id = ?1",
-            params![card_id],
-            |_| Ok(()),
-        )
-        .map_err(|e| match e {
-            rusqlite::Error::QueryReturnedNoRows => AppError::CardNotFound(card_id.clone()),
-            _ => AppError::Database(e),
-        })?;
-
-        // Archive the card
-        tx.execute(
-            "UPDATE Card SET archived = 1, archivedAt = ?1, updatedAt = ?2 WHERE id = ?3",
-            params![now, now, card_id],
-        )?;
+/// Archives cards matching the saved `ArchivePolicy`.
+#[tauri::command]
+pub fn archive_old_cards() -> Result<ArchiveResult, AppError> {
+    with_store_mut(Store::archive_old_cards)
+}
 
-        // Log the change
-        let changelog_id = generate_id();
-        let payload = serde_json::json!({ "reason": "user_archive" });
-        tx.execute(
-            "INSERT INTO ChangeLog (id, cardId, kind, payload, createdAt) VALUES (?1, ?2, ?3, ?4, ?5)",
-            params![changelog_id, card_id, "archived", payload.to_string(), now],
-        )?;
+/// Pins or unpins a card; pinned cards can be kept out of automatic archiving.
+#[tauri::command]
+pub fn pin_card(card_id: String, pinned: bool) -> Result<CardDto, AppError> {
+    with_store_mut(|store| store.pin_card(&card_id, pinned))
+}
 
-        tx.commit()?;
-
-        // Return the updated card
-        let card = conn.query_row(
-            "SELECT id, title, amount, lockedAmount, archived, createdAt, updatedAt, archivedAt FROM Card WHERE id = ?1",
-            params![card_id],
-            |row| {
-                Ok(CardDto {
-                    id: row.get(0)?,
-                    title: row.get(1)?,
-                    amount: format!("{:.6}", row.get::<_, f64>(2)?),
-                    locked_amount: row.get::<_, Option<f64>>(3)?.map(|a| format!("{:.6}", a)),
-                    archived: row.get::<_, i32>(4)? != 0,
-                    created_at: row.get(5)?,
-                    updated_at: row.get(6)?,
-                    archived_at: row.get(7)?,
-                })
-            },
-        )?;
+#[tauri::command]
+pub fn get_settings() -> Result<Settings, AppError> {
+    with_store(Store::settings)
+}
 
-        Ok(card)
-    })
+/// Saves all settings and notifies subsystems through `SETTINGS_CHANGED_EVENT`.
+#[tauri::command]
+pub fn update_settings(app: AppHandle, settings: Settings) -> Result<Settings, AppError> {
+    let settings = with_store_mut(|store| store.save_settings(settings))?;
+    notify_settings_changed(&app, &settings);
+    Ok(settings)
 }
 
 #[tauri::command]
-pub fn unarchive_card(card_id: String) -> Result<CardDto, AppError> {
-    let now = now_iso();
+pub fn get_archive_policy() -> Result<ArchivePolicy, AppError> {
+    Ok(with_store(Store::settings)?.archive_policy)
+}
 
-    with_db_mut(|conn| {
-        let tx = conn.transaction()?;
+#[tauri::command]
+pub fn update_archive_policy(
+    app: AppHandle,
+    policy: ArchivePolicy,
+) -> Result<ArchivePolicy, AppError> {
+    let settings = with_store_mut(|store| store.save_archive_policy(&policy))?;
+    notify_settings_changed(&app, &settings);
+    Ok(policy)
+}
 
-        // Check card exists
-        tx.query_row(
-            "SELECT id FROM Card WHERE id = ?1",
-            params![card_id],
-            |_| Ok(()),
-        )
-        .map_err(|e| match e {
-            rusqlite::Error::QueryReturnedNoRows => AppError::CardNotFound(card_id.clone()),
-            _ => AppError::Database(e),
-        })?;
-
-        // Unarchive the card
-        tx.execute(
-            "UPDATE Card SET archived = 0, archivedAt = NULL, updatedAt = ?1 WHERE id = ?2",
-            params![now, card_id],
-        )?;
+fn notify_settings_changed(app: &AppHandle, settings: &Settings) {
+    // The change is already saved; listeners re-read settings on their next run
+    if let Err(e) = app.emit(SETTINGS_CHANGED_EVENT, settings) {
+        log::warn!("Failed to emit {}: {}", SETTINGS_CHANGED_EVENT, e);
+    }
+}
 
-        // Log the change
-        let changelog_id = generate_id();
-        let payload = serde_json::json!({ "reason": "user_unarchive" });
-        tx.execute(
-            "INSERT INTO ChangeLog (id, cardId, kind, payload, createdAt) VALUES (?1, ?2, ?3, ?4, ?5)",
-            params![changelog_id, card_id, "unarchived", payload.to_string(), now],
-        )?;
+#[tauri::command]
+pub fn schema_version() -> Result<SchemaVersionDto, AppError> {
+    with_db(|conn| {
+        Ok(SchemaVersionDto {
+            current: migrations::current_version(conn)?,
+            latest: migrations::latest_version(),
+        })
+    })
+}
 
-        tx.commit()?;
-
-        // Return the updated card
-        let card = conn.query_row(
-            "SELECT id, title, amount, lockedAmount, archived, createdAt, updatedAt, archivedAt FROM Card WHERE id = ?1",
-            params![card_id],
-            |row| {
-                Ok(CardDto {
-                    id: row.get(0)?,
-                    title: row.get(1)?,
-                    amount: format!("{:.6}", row.get::<_, f64>(2)?),
-                    locked_amount: row.get::<_, Option<f64>>(3)?.map(|a| format!("{:.6}", a)),
-                    archived: row.get::<_, i32>(4)? != 0,
-                    created_at: row.get(5)?,
-                    updated_at: row.get(6)?,
-                    archived_at: row.get(7)?,
+#[tauri::command]
+pub fn undo_last_change() -> Result<ChangeLogDto, AppError> {
+    with_store_mut(|store| store.undo_last_change())
+}
+
+#[tauri::command]
+pub fn redo_change(change_id: Option<String>) -> Result<ChangeLogDto, AppError> {
+    with_store_mut(|store| store.redo_change(change_id.as_deref()))
+}
+
+#[tauri::command]
+pub fn check_integrity() -> Result<IntegrityReportDto, AppError> {
+    with_db(|conn| {
+        let integrity_errors: Vec<String> = conn
+            .prepare("PRAGMA integrity_check")?
+            .query_map([], |row| row.get(0))?
+            .collect::<Result<Vec<String>, _>>()?
+            .into_iter()
+            .filter(|msg| msg != "ok")
+            .collect();
+
+        let foreign_key_violations = conn
+            .prepare("PRAGMA foreign_key_check")?
+            .query_map([], |row| {
+                Ok(ForeignKeyViolationDto {
+                    table: row.get(0)?,
+                    rowid: row.get(1)?,
+                    parent: row.get(2)?,
                 })
-            },
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // search_index is a virtual table, so foreign_key_check cannot see it
+        let orphaned_search_rows: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM search_index
+             WHERE card_id NOT IN (SELECT id FROM Card)
+                OR (todo_id IS NOT NULL AND todo_id NOT IN (SELECT id FROM Todo))",
+            [],
+            |row| row.get(0),
         )?;
 
-        Ok(card)
+        Ok(IntegrityReportDto {
+            ok: integrity_errors.is_empty()
+                && foreign_key_violations.is_empty()
+                && orphaned_search_rows == 0,
+            integrity_errors,
+            foreign_key_violations,
+            orphaned_search_rows,
+        })
     })
 }
 
 #[tauri::command]
-pub fn archive_old_cards() -> Result<ArchiveResult, AppError> {
-    let now = now_iso();
-    let thirty_days_ago = (Utc::now() - chrono::Duration::days(30))
-        .format("%Y-%m-%dT%H:%M:%S%.3fZ")
-        .to_string();
+pub fn export_csv(path: String, filter: Option<ExportFilter>) -> Result<ExportResult, AppError> {
+    let filter = filter.unwrap_or_default();
+    with_db(|conn| csv_io::export(conn, Path::new(&path), &filter))
+}
 
+/// Imports cards and todos from `path`. With `dry_run` nothing is written and
+/// the report previews what would be created.
+#[tauri::command]
+pub fn import_csv(
+    path: String,
+    mapping: Option<CsvMapping>,
+    dry_run: Option<bool>,
+) -> Result<ImportReport, AppError> {
+    let mapping = mapping.unwrap_or_default();
+    let now = now_iso();
     with_db_mut(|conn| {
-        let tx = conn.transaction()?;
+        csv_io::import(
+            conn,
+            Path::new(&path),
+            &mapping,
+            dry_run.unwrap_or(false),
+            &now,
+        )
+    })
+}
 
-        let mut stmt = tx.prepare("SELECT id FROM Card WHERE archived = 0 AND createdAt <= ?1")?;
+#[tauri::command]
+pub fn list_exchange_rates() -> Result<Vec<ExchangeRateDto>, AppError> {
+    with_store(Store::list_exchange_rates)
+}
 
-        let card_ids: Vec<String> = stmt
-            .query_map(params![thirty_days_ago], |row| row.get(0))?
-            .collect::<Result<Vec<_>, _>>()?;
+/// Records that one unit of `base` is worth `rate` units of `quote`.
+#[tauri::command]
+pub fn set_exchange_rate(
+    base: String,
+    quote: String,
+    rate: String,
+) -> Result<ExchangeRateDto, AppError> {
+    with_store_mut(|store| store.set_exchange_rate(&base, &quote, &rate))
+}
 
-        drop(stmt);
+#[tauri::command]
+pub fn delete_exchange_rate(base: String, quote: String) -> Result<OkResponse, AppError> {
+    with_store_mut(|store| store.delete_exchange_rate(&base, &quote))?;
+    Ok(OkResponse { ok: true })
+}
 
-        for card_id in &card_ids {
-            tx.execute(
-                "UPDATE Card SET archived = 1, archivedAt = ?1, updatedAt = ?2 WHERE id = ?3",
-                params![now, now, card_id],
-            )?;
+/// Loads rates from a CSV file with `base`, `quote` and `rate` columns,
+/// replacing existing rates for the same pairs.
+#[tauri::command]
+pub fn import_exchange_rates(path: String) -> Result<RateImportResult, AppError> {
+    let now = now_iso();
+    with_db_mut(|conn| csv_io::import_rates(conn, Path::new(&path), &now))
+}
 
-            let changelog_id = generate_id();
-            let payload = serde_json::json!({ "reason": "auto_archive_30_days" });
-            tx.execute(
-                "INSERT INTO ChangeLog (id, cardId, kind, payload, createdAt) VALUES (?1, ?2, ?3, ?4, ?5)",
-                params![changelog_id, card_id, "archived", payload.to_string(), now],
-            )?;
-        }
+/// Writes the whole database to `path` as a versioned JSON document.
+#[tauri::command]
+pub fn export_backup(path: String) -> Result<BackupResult, AppError> {
+    let now = now_iso();
+    with_db(|conn| backup::export(conn, Path::new(&path), &now))
+}
 
-        tx.commit()?;
+/// Loads a backup written by `export_backup`. `mode` is `replace` (wipe the
+/// database first) or `merge` (keep local rows unless the backup's are newer).
+/// The file is fully validated before the database is touched.
+#[tauri::command]
+pub fn restore_backup(path: String, mode: String) -> Result<RestoreResult, AppError> {
+    let mode = RestoreMode::parse(&mode)?;
+    let document = backup::read(Path::new(&path))?;
+    with_db_mut(|conn| backup::restore(conn, &document, mode))
+}
 
-        Ok(ArchiveResult {
-            archived_count: card_ids.len() as i32,
-        })
-    })
+fn snapshot_dto(snapshot: Snapshot) -> SnapshotDto {
+    SnapshotDto {
+        id: snapshot.id,
+        path: snapshot.path.to_string_lossy().into_owned(),
+        created_at: format_timestamp(snapshot.created_at),
+        size_bytes: snapshot.size_bytes,
+    }
+}
+
+/// Rotating snapshots taken by the archiver, newest first.
+#[tauri::command]
+pub fn list_backups() -> Result<Vec<SnapshotDto>, AppError> {
+    let dir = db::backups_dir()?;
+    Ok(snapshots::list(&dir)?
+        .into_iter()
+        .map(snapshot_dto)
+        .collect())
+}
+
+/// Replaces the live database with snapshot `backup_id`. The current state is
+/// snapshotted first.
+#[tauri::command]
+pub fn restore_from_backup(backup_id: String) -> Result<SnapshotDto, AppError> {
+    let dir = db::backups_dir()?;
+    with_db_mut(|conn| snapshots::restore(conn, &dir, &backup_id, Utc::now())).map(snapshot_dto)
 }
@@ -0,0 +1,594 @@
+use crate::currency;
+use crate::dates::parse_timestamp;
+use crate::errors::AppError;
+use crate::history;
+use crate::models::{
+    CsvMapping, ExportFilter, ExportResult, ImportReport, ImportRowError, RateImportResult,
+};
+use crate::money::Money;
+use crate::settings;
+use rusqlite::{params, Connection, OptionalExtension};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+/// Header written by `export` and expected by `import` unless remapped.
+pub const HEADER: [&str; 16] = [
+    "card_id",
+    "card_title",
+    "card_amount",
+    "card_currency",
+    "card_locked_amount",
+    "card_archived",
+    "card_archived_at",
+    "card_created_at",
+    "todo_id",
+    "todo_title",
+    "todo_amount",
+    "todo_currency",
+    "todo_done",
+    "todo_scheduled_at",
+    "todo_created_at",
+    "tags",
+];
+
+/// Tag names are joined with this in the `tags` column.
+const TAG_SEPARATOR: char = ';';
+
+/// Write one row per todo, with the card columns repeated. Cards without
+/// todos get a single row with empty todo columns so they survive a round trip.
+pub fn export(
+    conn: &Connection,
+    path: &Path,
+    filter: &ExportFilter,
+) -> Result<ExportResult, AppError> {
+    let card_ids = filter
+        .card_ids
+        .as_ref()
+        .map(|ids| serde_json::json!(ids).to_string());
+
+    let mut stmt = conn.prepare(
+        "SELECT c.id, c.title, c.amount, c.currency, c.lockedAmount, c.archived, c.archivedAt, c.createdAt,
+                t.id, t.title, t.amount, t.currency, t.done, t.scheduledAt, t.createdAt,
+                (SELECT group_concat(tg.name, ';') FROM TodoTag tt JOIN Tag tg ON tg.id = tt.tagId
+                 WHERE tt.todoId = t.id)
+         FROM Card c
+         LEFT JOIN Todo t ON t.cardId = c.id
+         WHERE c.deletedAt IS NULL
+           AND (?1 OR c.archived = 0)
+           AND (?2 IS NULL OR c.id IN (SELECT value FROM json_each(?2)))
+           AND (?3 IS NULL OR COALESCE(t.scheduledAt, t.createdAt, c.createdAt) >= ?3)
+           AND (?4 IS NULL OR COALESCE(t.scheduledAt, t.createdAt, c.createdAt) <= ?4)
+         ORDER BY c.createdAt, c.id, t.orderIndex",
+    )?;
+
+    let mut rows = stmt.query(params![
+        filter.include_archived,
+        card_ids,
+        filter.from,
+        filter.to
+    ])?;
+
+    let mut writer = csv::Writer::from_path(path)?;
+    writer.write_record(HEADER)?;
+
+    let mut row_count = 0;
+    while let Some(row) = rows.next()? {
+        let money = |idx: usize| -> rusqlite::Result<String> {
+            Ok(row
+                .get::<_, Option<Money>>(idx)?
+                .map(|a| a.to_string())
+                .unwrap_or_default())
+        };
+        let text = |idx: usize| -> rusqlite::Result<String> {
+            Ok(row.get::<_, Option<String>>(idx)?.unwrap_or_default())
+        };
+        let flag = |idx: usize| -> rusqlite::Result<String> {
+            Ok(row
+                .get::<_, Option<i32>>(idx)?
+                .map(|v| (v != 0).to_string())
+                .unwrap_or_default())
+        };
+
+        writer.write_record([
+            text(0)?,
+            text(1)?,
+            money(2)?,
+            text(3)?,
+            money(4)?,
+            flag(5)?,
+            text(6)?,
+            text(7)?,
+            text(8)?,
+            text(9)?,
+            money(10)?,
+            text(11)?,
+            flag(12)?,
+            text(13)?,
+            text(14)?,
+            text(15)?,
+        ])?;
+        row_count += 1;
+    }
+    writer.flush().map_err(csv::Error::from)?;
+
+    Ok(ExportResult {
+        path: path.to_string_lossy().into_owned(),
+        row_count,
+    })
+}
+
+/// Column positions resolved from the CSV header and the caller's mapping.
+struct Columns {
+    card_id: Option<usize>,
+    card_title: Option<usize>,
+    card_amount: usize,
+    card_currency: Option<usize>,
+    card_locked_amount: Option<usize>,
+    card_archived: Option<usize>,
+    card_archived_at: Option<usize>,
+    card_created_at: Option<usize>,
+    todo_title: Option<usize>,
+    todo_amount: Option<usize>,
+    todo_currency: Option<usize>,
+    todo_done: Option<usize>,
+    todo_scheduled_at: Option<usize>,
+    todo_created_at: Option<usize>,
+    tags: Option<usize>,
+}
+
+impl Columns {
+    fn resolve(headers: &csv::StringRecord, mapping: &CsvMapping) -> Result<Columns, AppError> {
+        let find = |mapped: &Option<String>, default: &str| -> Result<Option<usize>, AppError> {
+            let name = mapped.as_deref().unwrap_or(default);
+            let idx = headers
+                .iter()
+                .position(|h| h.trim().eq_ignore_ascii_case(name.trim()));
+            // A column the caller asked for by name must exist
+            if idx.is_none() && mapped.is_some() {
+                return Err(AppError::invalid_field(
+                    default,
+                    format!("CSV has no column named {}", name),
+                ));
+            }
+            Ok(idx)
+        };
+
+        Ok(Columns {
+            card_id: find(&mapping.card_id, "card_id")?,
+            card_title: find(&mapping.card_title, "card_title")?,
+            card_amount: find(&mapping.card_amount, "card_amount")?.ok_or_else(|| {
+                AppError::invalid_field("card_amount", "CSV has no card amount column")
+            })?,
+            card_currency: find(&mapping.card_currency, "card_currency")?,
+            card_locked_amount: find(&mapping.card_locked_amount, "card_locked_amount")?,
+            card_archived: find(&mapping.card_archived, "card_archived")?,
+            card_archived_at: find(&mapping.card_archived_at, "card_archived_at")?,
+            card_created_at: find(&mapping.card_created_at, "card_created_at")?,
+            todo_title: find(&mapping.todo_title, "todo_title")?,
+            todo_amount: find(&mapping.todo_amount, "todo_amount")?,
+            todo_currency: find(&mapping.todo_currency, "todo_currency")?,
+            todo_done: find(&mapping.todo_done, "todo_done")?,
+            todo_scheduled_at: find(&mapping.todo_scheduled_at, "todo_scheduled_at")?,
+            todo_created_at: find(&mapping.todo_created_at, "todo_created_at")?,
+            tags: find(&mapping.tags, "tags")?,
+        })
+    }
+}
+
+/// One parsed and validated CSV row.
+struct ImportRow {
+    card_id: Option<String>,
+    card_title: Option<String>,
+    card_amount: Money,
+    card_currency: String,
+    card_locked_amount: Option<Money>,
+    card_archived: bool,
+    card_archived_at: Option<String>,
+    card_created_at: Option<String>,
+    todo: Option<ImportTodo>,
+}
+
+struct ImportTodo {
+    title: String,
+    amount: Option<Money>,
+    currency: Option<String>,
+    done: bool,
+    scheduled_at: Option<String>,
+    created_at: Option<String>,
+    tags: Vec<String>,
+}
+
+/// Cards without a currency column get `default_currency`, the setting.
+fn parse_row(
+    record: &csv::StringRecord,
+    columns: &Columns,
+    default_currency: &str,
+) -> Result<ImportRow, String> {
+    let field = |idx: Option<usize>| {
+        idx.and_then(|i| record.get(i))
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+    };
+    let money = |idx: Option<usize>, code: &str| {
+        field(idx)
+            .map(|v| currency::parse_amount(v, code))
+            .transpose()
+            .map_err(|e| e.to_string())
+    };
+    let code = |idx: Option<usize>| {
+        field(idx)
+            .map(currency::validate_code)
+            .transpose()
+            .map_err(|e| e.to_string())
+    };
+    let flag = |idx: Option<usize>, name: &str| match field(idx) {
+        None => Ok(false),
+        Some(v) => parse_bool(v).ok_or_else(|| format!("Invalid {} value: {}", name, v)),
+    };
+    // Stored as given, like `add_todo` does
+    let timestamp = |idx: Option<usize>| {
+        field(idx)
+            .map(|v| match parse_timestamp(v) {
+                Some(_) => Ok(v.to_string()),
+                None => Err(format!("Invalid date: {}", v)),
+            })
+            .transpose()
+    };
+
+    let card_currency = code(columns.card_currency)?.unwrap_or_else(|| default_currency.into());
+    let card_amount = money(Some(columns.card_amount), &card_currency)?
+        .ok_or_else(|| "Card amount is required".to_string())?;
+    let card_locked_amount = money(columns.card_locked_amount, &card_currency)?;
+    if card_locked_amount.is_some_and(|a| a < Money::ZERO) {
+        return Err("Locked amount cannot be negative".into());
+    }
+
+    let todo = match field(columns.todo_title) {
+        Some(title) => {
+            let todo_currency = code(columns.todo_currency)?;
+            Some(ImportTodo {
+                title: title.to_string(),
+                amount: money(
+                    columns.todo_amount,
+                    todo_currency.as_deref().unwrap_or(&card_currency),
+                )?,
+                currency: todo_currency,
+                done: flag(columns.todo_done, "done")?,
+                scheduled_at: timestamp(columns.todo_scheduled_at)?,
+                created_at: timestamp(columns.todo_created_at)?,
+                tags: field(columns.tags)
+                    .map(|v| {
+                        v.split(TAG_SEPARATOR)
+                            .map(str::trim)
+                            .filter(|t| !t.is_empty())
+                            .map(String::from)
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+            })
+        }
+        None => None,
+    };
+
+    Ok(ImportRow {
+        card_id: field(columns.card_id).map(String::from),
+        card_title: field(columns.card_title).map(String::from),
+        card_amount,
+        card_currency,
+        card_locked_amount,
+        card_archived: flag(columns.card_archived, "archived")?,
+        card_archived_at: timestamp(columns.card_archived_at)?,
+        card_created_at: timestamp(columns.card_created_at)?,
+        todo,
+    })
+}
+
+/// A line the CSV reader could not read (e.g. invalid UTF-8) becomes a row
+/// error; failing to read the file itself still aborts.
+fn row_error(e: csv::Error) -> Result<ImportRowError, AppError> {
+    if let csv::ErrorKind::Io(_) = e.kind() {
+        return Err(e.into());
+    }
+    Ok(ImportRowError {
+        row: e.position().map_or(0, |p| p.line()),
+        message: e.to_string(),
+    })
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+    match value.to_ascii_lowercase().as_str() {
+        "1" | "true" | "yes" | "y" | "x" => Some(true),
+        "0" | "false" | "no" | "n" => Some(false),
+        _ => None,
+    }
+}
+
+/// Create cards and todos from a CSV file in one transaction. Rows that fail
+/// to read or validate are reported and skipped. Rows matching an existing card
+/// (same id, or same title, amount and currency) reuse it, and todos already on that
+/// card with the same title, amount, currency and date are counted as
+/// duplicates. Only todos from before the import count, each matching one
+/// row, so identical rows within the file are all kept.
+/// With `dry_run` the transaction is rolled back, so the report is a preview.
+pub fn import(
+    conn: &mut Connection,
+    path: &Path,
+    mapping: &CsvMapping,
+    dry_run: bool,
+    now: &str,
+) -> Result<ImportReport, AppError> {
+    let mut reader = csv::ReaderBuilder::new().flexible(true).from_path(path)?;
+    let columns = Columns::resolve(reader.headers()?, mapping).map_err(|e| e.within("mapping"))?;
+
+    let tx = conn.transaction()?;
+    let default_currency = settings::load(&tx)?.default_currency;
+    let mut report = ImportReport {
+        dry_run,
+        cards_created: 0,
+        todos_created: 0,
+        duplicates_skipped: 0,
+        errors: Vec::new(),
+    };
+    // CSV card key -> card id in the database
+    let mut cards: HashMap<String, String> = HashMap::new();
+    let mut todos = TodoMatches::default();
+
+    for record in reader.records() {
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                report.errors.push(row_error(e)?);
+                continue;
+            }
+        };
+        let line = record.position().map_or(0, |p| p.line());
+
+        let row = match parse_row(&record, &columns, &default_currency) {
+            Ok(row) => row,
+            Err(message) => {
+                report.errors.push(ImportRowError { row: line, message });
+                continue;
+            }
+        };
+
+        let key = row.card_id.clone().unwrap_or_else(|| {
+            format!(
+                "{}\u{1f}{}\u{1f}{}",
+                row.card_title.as_deref().unwrap_or_default(),
+                row.card_amount,
+                row.card_currency
+            )
+        });
+        let card_id = match cards.get(&key) {
+            Some(id) => id.clone(),
+            None => {
+                let id = match find_card(&tx, &row)? {
+                    Some(id) => id,
+                    None => {
+                        report.cards_created += 1;
+                        insert_card(&tx, &row, now)?
+                    }
+                };
+                cards.insert(key, id.clone());
+                id
+            }
+        };
+
+        if let Some(todo) = &row.todo {
+            if insert_todo(&tx, &card_id, todo, &mut todos, now)? {
+                report.todos_created += 1;
+            } else {
+                report.duplicates_skipped += 1;
+            }
+        }
+    }
+
+    if !dry_run {
+        tx.commit()?;
+    }
+    Ok(report)
+}
+
+fn find_card(conn: &Connection, row: &ImportRow) -> Result<Option<String>, AppError> {
+    if let Some(id) = &row.card_id {
+        let existing = conn
+            .query_row(
+                "SELECT id FROM Card WHERE id = ?1 AND deletedAt IS NULL",
+                params![id],
+                |r| r.get(0),
+            )
+            .optional()?;
+        if existing.is_some() {
+            return Ok(existing);
+        }
+    }
+
+    Ok(conn
+        .query_row(
+            "SELECT id FROM Card WHERE title IS ?1 AND amount = ?2 AND currency = ?3 AND deletedAt IS NULL
+             ORDER BY createdAt LIMIT 1",
+            params![row.card_title, row.card_amount, row.card_currency],
+            |r| r.get(0),
+        )
+        .optional()?)
+}
+
+fn insert_card(conn: &Connection, row: &ImportRow, now: &str) -> Result<String, AppError> {
+    let id = uuid::Uuid::new_v4().to_string();
+    conn.execute(
+        "INSERT INTO Card (id, title, amount, currency, lockedAmount, archived, archivedAt, createdAt, updatedAt)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, CASE WHEN ?6 THEN COALESCE(?9, ?8) END, COALESCE(?7, ?8), ?8)",
+        params![
+            id,
+            row.card_title,
+            row.card_amount,
+            row.card_currency,
+            row.card_locked_amount,
+            row.card_archived,
+            row.card_created_at,
+            now,
+            row.card_archived_at
+        ],
+    )?;
+
+    let payload = serde_json::json!({
+        "title": row.card_title,
+        "amount": row.card_amount.to_string(),
+        "reason": "import",
+        "after": history::card_snapshot(conn, &id)?
+    });
+    history::log_change(conn, &id, "created", &payload, now)?;
+    Ok(id)
+}
+
+/// Todo ids seen by one import: those it created, and the pre-existing ones
+/// already matched by a duplicate row.
+#[derive(Default)]
+struct TodoMatches {
+    created: HashSet<String>,
+    matched: HashSet<String>,
+}
+
+/// Returns false when the card already had an identical todo before the
+/// import that no earlier row has matched.
+fn insert_todo(
+    conn: &Connection,
+    card_id: &str,
+    todo: &ImportTodo,
+    todos: &mut TodoMatches,
+    now: &str,
+) -> Result<bool, AppError> {
+    let mut stmt = conn.prepare(
+        "SELECT id FROM Todo
+         WHERE cardId = ?1 AND title = ?2 AND amount IS ?3 AND currency IS ?4 AND scheduledAt IS ?5",
+    )?;
+    let candidates = stmt
+        .query_map(
+            params![
+                card_id,
+                todo.title,
+                todo.amount,
+                todo.currency,
+                todo.scheduled_at
+            ],
+            |r| r.get::<_, String>(0),
+        )?
+        .collect::<Result<Vec<_>, _>>()?;
+    if let Some(existing) = candidates
+        .into_iter()
+        .find(|id| !todos.created.contains(id) && !todos.matched.contains(id))
+    {
+        todos.matched.insert(existing);
+        return Ok(false);
+    }
+
+    let todo_id = uuid::Uuid::new_v4().to_string();
+    conn.execute(
+        "INSERT INTO Todo (id, cardId, title, amount, currency, done, createdAt, scheduledAt, orderIndex, updatedAt)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, COALESCE(?7, ?9), ?8,
+                 (SELECT COALESCE(MAX(orderIndex), 0) + 1 FROM Todo WHERE cardId = ?2), ?9)",
+        params![
+            todo_id,
+            card_id,
+            todo.title,
+            todo.amount,
+            todo.currency,
+            todo.done as i32,
+            todo.created_at,
+            todo.scheduled_at,
+            now
+        ],
+    )?;
+    todos.created.insert(todo_id.clone());
+
+    for name in &todo.tags {
+        let tag_id = match conn
+            .query_row("SELECT id FROM Tag WHERE name = ?1", params![name], |r| {
+                r.get::<_, String>(0)
+            })
+            .optional()?
+        {
+            Some(id) => id,
+            None => {
+                let id = uuid::Uuid::new_v4().to_string();
+                conn.execute(
+                    "INSERT INTO Tag (id, name, createdAt, updatedAt) VALUES (?1, ?2, ?3, ?4)",
+                    params![id, name, now, now],
+                )?;
+                id
+            }
+        };
+        conn.execute(
+            "INSERT OR IGNORE INTO TodoTag (todoId, tagId) VALUES (?1, ?2)",
+            params![todo_id, tag_id],
+        )?;
+    }
+
+    conn.execute(
+        "UPDATE Card SET updatedAt = ?1 WHERE id = ?2",
+        params![now, card_id],
+    )?;
+
+    let after = history::todo_snapshot(conn, &todo_id)?;
+    let payload = serde_json::json!({
+        "todo_id": todo_id,
+        "title": todo.title,
+        "amount": todo.amount.map(|a| a.to_string()),
+        "reason": "import",
+        "after": after
+    });
+    history::log_change(conn, card_id, "todo_added", &payload, now)?;
+    Ok(true)
+}
+
+/// Load `base,quote,rate` rows into the exchange-rate table in one
+/// transaction. Invalid rows are reported and skipped.
+pub fn import_rates(
+    conn: &mut Connection,
+    path: &Path,
+    now: &str,
+) -> Result<RateImportResult, AppError> {
+    let mut reader = csv::ReaderBuilder::new().flexible(true).from_path(path)?;
+    let headers = reader.headers()?.clone();
+    let column = |name: &str| {
+        headers
+            .iter()
+            .position(|h| h.trim().eq_ignore_ascii_case(name))
+            .ok_or_else(|| {
+                AppError::invalid_field(name, format!("CSV has no column named {}", name))
+            })
+    };
+    let (base_idx, quote_idx, rate_idx) = (column("base")?, column("quote")?, column("rate")?);
+
+    let tx = conn.transaction()?;
+    let mut result = RateImportResult {
+        imported_count: 0,
+        errors: Vec::new(),
+    };
+
+    for record in reader.records() {
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                result.errors.push(row_error(e)?);
+                continue;
+            }
+        };
+        let line = record.position().map_or(0, |p| p.line());
+        let get = |idx: usize| record.get(idx).unwrap_or_default();
+
+        match currency::validate_rate(get(base_idx), get(quote_idx), get(rate_idx)) {
+            Ok((base, quote, rate)) => {
+                currency::upsert_rate(&tx, &base, &quote, rate, "import", now)?;
+                result.imported_count += 1;
+            }
+            Err(e) => result.errors.push(ImportRowError {
+                row: line,
+                message: e.to_string(),
+            }),
+        }
+    }
+
+    tx.commit()?;
+    Ok(result)
+}
@@ -0,0 +1,189 @@
+use crate::errors::AppError;
+use crate::models::ExchangeRateDto;
+use crate::money::Money;
+use crate::store::{now_iso, Store};
+use rusqlite::{params, Connection};
+
+/// Currency for cards created without one, and for cards that predate
+/// currencies.
+pub const DEFAULT_CURRENCY: &str = "USD";
+
+/// Active ISO 4217 codes and their number of minor-unit digits, sorted by code.
+#[rustfmt::skip]
+const CURRENCIES: &[(&str, u32)] = &[
+    ("AED", 2), ("AFN", 2), ("ALL", 2), ("AMD", 2), ("ANG", 2), ("AOA", 2),
+    ("ARS", 2), ("AUD", 2), ("AWG", 2), ("AZN", 2), ("BAM", 2), ("BBD", 2),
+    ("BDT", 2), ("BGN", 2), ("BHD", 3), ("BIF", 0), ("BMD", 2), ("BND", 2),
+    ("BOB", 2), ("BOV", 2), ("BRL", 2), ("BSD", 2), ("BTN", 2), ("BWP", 2),
+    ("BYN", 2), ("BZD", 2), ("CAD", 2), ("CDF", 2), ("CHE", 2), ("CHF", 2),
+    ("CHW", 2), ("CLF", 4), ("CLP", 0), ("CNY", 2), ("COP", 2), ("COU", 2),
+    ("CRC", 2), ("CUP", 2), ("CVE", 2), ("CZK", 2), ("DJF", 0), ("DKK", 2),
+    ("DOP", 2), ("DZD", 2), ("EGP", 2), ("ERN", 2), ("ETB", 2), ("EUR", 2),
+    ("FJD", 2), ("FKP", 2), ("GBP", 2), ("GEL", 2), ("GHS", 2), ("GIP", 2),
+    ("GMD", 2), ("GNF", 0), ("GTQ", 2), ("GYD", 2), ("HKD", 2), ("HNL", 2),
+    ("HTG", 2), ("HUF", 2), ("IDR", 2), ("ILS", 2), ("INR", 2), ("IQD", 3),
+    ("IRR", 2), ("ISK", 0), ("JMD", 2), ("JOD", 3), ("JPY", 0), ("KES", 2),
+    ("KGS", 2), ("KHR", 2), ("KMF", 0), ("KPW", 2), ("KRW", 0), ("KWD", 3),
+    ("KYD", 2), ("KZT", 2), ("LAK", 2), ("LBP", 2), ("LKR", 2), ("LRD", 2),
+    ("LSL", 2), ("LYD", 3), ("MAD", 2), ("MDL", 2), ("MGA", 2), ("MKD", 2),
+    ("MMK", 2), ("MNT", 2), ("MOP", 2), ("MRU", 2), ("MUR", 2), ("MVR", 2),
+    ("MWK", 2), ("MXN", 2), ("MXV", 2), ("MYR", 2), ("MZN", 2), ("NAD", 2),
+    ("NGN", 2), ("NIO", 2), ("NOK", 2), ("NPR", 2), ("NZD", 2), ("OMR", 3),
+    ("PAB", 2), ("PEN", 2), ("PGK", 2), ("PHP", 2), ("PKR", 2), ("PLN", 2),
+    ("PYG", 0), ("QAR", 2), ("RON", 2), ("RSD", 2), ("RUB", 2), ("RWF", 0),
+    ("SAR", 2), ("SBD", 2), ("SCR", 2), ("SDG", 2), ("SEK", 2), ("SGD", 2),
+    ("SHP", 2), ("SLE", 2), ("SOS", 2), ("SRD", 2), ("SSP", 2), ("STN", 2),
+    ("SVC", 2), ("SYP", 2), ("SZL", 2), ("THB", 2), ("TJS", 2), ("TMT", 2),
+    ("TND", 3), ("TOP", 2), ("TRY", 2), ("TTD", 2), ("TWD", 2), ("TZS", 2),
+    ("UAH", 2), ("UGX", 0), ("USD", 2), ("USN", 2), ("UYI", 0), ("UYU", 2),
+    ("UYW", 4), ("UZS", 2), ("VED", 2), ("VES", 2), ("VND", 0), ("VUV", 0),
+    ("WST", 2), ("XAF", 0), ("XCD", 2), ("XCG", 2), ("XOF", 0), ("XPF", 0),
+    ("YER", 2), ("ZAR", 2), ("ZMW", 2), ("ZWG", 2),
+];
+
+/// Decimal places the currency allows, or `None` for an unknown code.
+pub fn minor_units(code: &str) -> Option<u32> {
+    CURRENCIES
+        .binary_search_by(|(c, _)| (*c).cmp(code))
+        .ok()
+        .map(|i| CURRENCIES[i].1)
+}
+
+/// `5.000000` in EUR becomes `"5.00"`, in JPY `"5"`.
+pub fn format_amount(amount: Money, currency: &str) -> String {
+    let decimals = minor_units(currency).unwrap_or(2) as usize;
+    let text = amount.to_string();
+    let (whole, frac) = text.split_once('.').unwrap_or((&text, ""));
+    if decimals == 0 {
+        whole.to_string()
+    } else {
+        format!("{}.{}", whole, &frac[..decimals.min(frac.len())])
+    }
+}
+
+/// Normalize `code` to upper case and check it is an ISO 4217 currency.
+pub fn validate_code(code: &str) -> Result<String, AppError> {
+    let code = code.trim().to_ascii_uppercase();
+    match minor_units(&code) {
+        Some(_) => Ok(code),
+        None => Err(AppError::InvalidCurrency(code)),
+    }
+}
+
+/// Parse an amount entered in `currency`, rejecting more decimals than the
+/// currency has (`10.5` JPY, `1.234` EUR).
+pub fn parse_amount(input: &str, currency: &str) -> Result<Money, AppError> {
+    let amount = Money::parse(input)?;
+    let decimals =
+        minor_units(currency).ok_or_else(|| AppError::InvalidCurrency(currency.into()))?;
+    if !amount.fits_decimals(decimals) {
+        return Err(AppError::InvalidAmount(format!(
+            "{} has more than {} decimals for {}",
+            input.trim(),
+            decimals,
+            currency
+        )));
+    }
+    Ok(amount)
+}
+
+/// Validate an exchange-rate entry: two distinct currencies and a positive,
+/// finite rate.
+pub fn validate_rate(
+    base: &str,
+    quote: &str,
+    rate: &str,
+) -> Result<(String, String, f64), AppError> {
+    let base = validate_code(base)?;
+    let quote = validate_code(quote)?;
+    if base == quote {
+        return Err(AppError::invalid_field(
+            "quote",
+            format!(
+                "Exchange rate needs two different currencies, got {} twice",
+                base
+            ),
+        ));
+    }
+
+    match rate.trim().parse::<f64>() {
+        Ok(rate) if rate.is_finite() && rate > 0.0 => Ok((base, quote, rate)),
+        _ => Err(AppError::invalid_field(
+            "rate",
+            format!("Exchange rate must be a positive number: {}", rate),
+        )),
+    }
+}
+
+/// Insert or replace the rate for `base`/`quote`.
+pub fn upsert_rate(
+    conn: &Connection,
+    base: &str,
+    quote: &str,
+    rate: f64,
+    source: &str,
+    now: &str,
+) -> Result<(), AppError> {
+    conn.execute(
+        "INSERT INTO ExchangeRate (base, quote, rate, source, updatedAt) VALUES (?1, ?2, ?3, ?4, ?5)
+         ON CONFLICT (base, quote) DO UPDATE SET rate = excluded.rate, source = excluded.source,
+             updatedAt = excluded.updatedAt",
+        params![base, quote, rate, source, now],
+    )?;
+    Ok(())
+}
+
+fn map_rate_row(row: &rusqlite::Row) -> rusqlite::Result<ExchangeRateDto> {
+    Ok(ExchangeRateDto {
+        base: row.get(0)?,
+        quote: row.get(1)?,
+        rate: row.get(2)?,
+        source: row.get(3)?,
+        updated_at: row.get(4)?,
+    })
+}
+
+impl Store {
+    pub fn list_exchange_rates(&self) -> Result<Vec<ExchangeRateDto>, AppError> {
+        let mut stmt = self.conn().prepare(
+            "SELECT base, quote, rate, source, updatedAt FROM ExchangeRate ORDER BY base, quote",
+        )?;
+        let rates = stmt
+            .query_map([], map_rate_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rates)
+    }
+
+    /// Records that one unit of `base` is worth `rate` units of `quote`.
+    pub fn set_exchange_rate(
+        &mut self,
+        base: &str,
+        quote: &str,
+        rate: &str,
+    ) -> Result<ExchangeRateDto, AppError> {
+        let now = now_iso();
+        let (base, quote, rate) = validate_rate(base, quote, rate)?;
+        upsert_rate(self.conn(), &base, &quote, rate, "manual", &now)?;
+
+        Ok(ExchangeRateDto {
+            base,
+            quote,
+            rate,
+            source: "manual".into(),
+            updated_at: now,
+        })
+    }
+
+    pub fn delete_exchange_rate(&mut self, base: &str, quote: &str) -> Result<(), AppError> {
+        let base = base.trim().to_ascii_uppercase();
+        let quote = quote.trim().to_ascii_uppercase();
+        let deleted = self.conn().execute(
+            "DELETE FROM ExchangeRate WHERE base = ?1 AND quote = ?2",
+            params![base, quote],
+        )?;
+        if deleted == 0 {
+            return Err(AppError::RateNotFound { base, quote });
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,32 @@
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+
+/// Format used for every timestamp column (`createdAt`, `scheduledAt`, ...).
+pub const TIMESTAMP_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.3fZ";
+
+pub fn format_timestamp(dt: DateTime<Utc>) -> String {
+    dt.format(TIMESTAMP_FORMAT).to_string()
+}
+
+/// Parse the timestamp shapes that reach the backend: RFC 3339 from our own
+/// columns, `datetime-local` values (`2026-10-18T14:30`) and plain dates.
+/// Values without an offset are taken as UTC.
+pub fn parse_timestamp(value: &str) -> Option<DateTime<Utc>> {
+    let value = value.trim();
+
+    if let Ok(dt) = DateTime::parse_from_rfc3339(value) {
+        return Some(dt.with_timezone(&Utc));
+    }
+    for format in [
+        "%Y-%m-%dT%H:%M:%S%.f",
+        "%Y-%m-%dT%H:%M",
+        "%Y-%m-%d %H:%M:%S",
+    ] {
+        if let Ok(naive) = NaiveDateTime::parse_from_str(value, format) {
+            return Some(naive.and_utc());
+        }
+    }
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .map(|naive| naive.and_utc())
+}
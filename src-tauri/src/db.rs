@@ -1,49 +1,93 @@
 use crate::errors::AppError;
+use crate::migrations;
+use crate::store::Store;
 use once_cell::sync::OnceCell;
 use rusqlite::Connection;
-use std::path::PathBuf;
-use std::sync::Mutex;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
 
-static DB: OnceCell<Mutex<Connection>> = OnceCell::new();
+/// Name of the database file inside the app data folder.
+pub const DB_FILE_NAME: &str = "tin.db";
+
+static DB: OnceCell<Mutex<Store>> = OnceCell::new();
+static DB_PATH: OnceCell<PathBuf> = OnceCell::new();
 
 pub fn init_db(app_data_dir: PathBuf) -> Result<(), AppError> {
-    let db_path = app_data_dir.join("tin.db");
+    let db_path = app_data_dir.join(DB_FILE_NAME);
     std::fs::create_dir_all(&app_data_dir).ok();
 
-    let conn = Connection::open(&db_path)?;
-
-    conn.execute_batch(include_str!("../migrations/init.sql"))?;
+    let store = Store::open(&db_path)?;
+    let version = migrations::current_version(store.conn())?;
 
-    // Migration: Add lockedAmount column if it doesn't exist
-    let _ = conn.execute("ALTER TABLE Card ADD COLUMN lockedAmount REAL", []);
-
-    DB.set(Mutex::new(conn))
+    DB.set(Mutex::new(store))
         .map_err(|_| AppError::Internal("DB already initialized".into()))?;
+    DB_PATH.set(db_path.clone()).ok();
 
-    log::info!("Database initialized at {:?}", db_path);
+    log::info!(
+        "Database initialized at {:?} (schema version {})",
+        db_path,
+        version
+    );
     Ok(())
 }
 
+/// Open a connection with the pragmas every connection to tin.db needs.
+pub fn open_connection(path: &Path) -> Result<Connection, AppError> {
+    configure(Connection::open(path)?)
+}
+
+/// A private in-memory database with the same pragmas, for tests and tools.
+pub fn open_memory_connection() -> Result<Connection, AppError> {
+    configure(Connection::open_in_memory()?)
+}
+
+fn configure(conn: Connection) -> Result<Connection, AppError> {
+    conn.pragma_update(None, "foreign_keys", true)?;
+    // The app and tin-cli may have the file open at the same time
+    conn.busy_timeout(Duration::from_secs(5))?;
+    Ok(conn)
+}
+
+/// Folder next to tin.db holding the rotating snapshots.
+pub fn backups_dir() -> Result<PathBuf, AppError> {
+    let db_path = DB_PATH
+        .get()
+        .ok_or_else(|| AppError::Internal("DB not initialized".into()))?;
+    Ok(db_path.with_file_name("backups"))
+}
+
+fn lock() -> Result<MutexGuard<'static, Store>, AppError> {
+    DB.get()
+        .ok_or_else(|| AppError::Internal("DB not initialized".into()))?
+        .lock()
+        .map_err(|_| AppError::Internal("DB lock poisoned".into()))
+}
+
+pub fn with_store<F, T>(f: F) -> Result<T, AppError>
+where
+    F: FnOnce(&Store) -> Result<T, AppError>,
+{
+    f(&*lock()?)
+}
+
+pub fn with_store_mut<F, T>(f: F) -> Result<T, AppError>
+where
+    F: FnOnce(&mut Store) -> Result<T, AppError>,
+{
+    f(&mut *lock()?)
+}
+
 pub fn with_db<F, T>(f: F) -> Result<T, AppError>
 where
     F: FnOnce(&Connection) -> Result<T, AppError>,
 {
-    let conn = DB
-        .get()
-        .ok_or_else(|| AppError::Internal("DB not initialized".into()))?
-        .lock()
-        .map_err(|_| AppError::Internal("DB lock poisoned".into()))?;
-    f(&conn)
+    with_store(|store| f(store.conn()))
 }
 
 pub fn with_db_mut<F, T>(f: F) -> Result<T, AppError>
 where
     F: FnOnce(&mut Connection) -> Result<T, AppError>,
 {
-    let mut conn = DB
-        .get()
-        .ok_or_else(|| AppError::Internal("DB not initialized".into()))?
-        .lock()
-        .map_err(|_| AppError::Internal("DB lock poisoned".into()))?;
-    f(&mut conn)
+    with_store_mut(|store| f(store.conn_mut()))
 }
@@ -1,9 +1,22 @@
+use rusqlite::ffi::{self, ErrorCode};
+use serde::Serialize;
+use serde_json::{json, Value};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
 pub enum AppError {
     #[error("Database error: {0}")]
-    Database(#[from] rusqlite::Error),
+    Database(rusqlite::Error),
+
+    /// A write duplicated a unique or primary key.
+    #[error("Conflicts with existing data: {0}")]
+    Conflict(String),
+
+    #[error("The database is busy; try again")]
+    DbLocked,
+
+    #[error("The database file is damaged: {0}")]
+    DbCorrupt(String),
 
     #[error("Card not found: {0}")]
     CardNotFound(String),
@@ -11,18 +24,216 @@ pub enum AppError {
     #[error("Todo not found: {0}")]
     TodoNotFound(String),
 
+    #[error("Tag not found: {0}")]
+    TagNotFound(String),
+
+    #[error("Budget not found: {0}")]
+    BudgetNotFound(String),
+
+    /// No recurrence rule on the todo with this id.
+    #[error("Recurrence not found: {0}")]
+    RecurrenceNotFound(String),
+
+    #[error("No exchange rate for {base}/{quote}")]
+    RateNotFound { base: String, quote: String },
+
     #[error("Invalid amount: {0}")]
     InvalidAmount(String),
 
+    #[error("Invalid currency: {0}")]
+    InvalidCurrency(String),
+
+    #[error("Validation error: {0}")]
+    Validation(String),
+
+    /// Validation error tied to one input; `field` is a dotted path for
+    /// nested settings, e.g. `api_server.port`.
+    #[error("Validation error: {message}")]
+    InvalidField { field: String, message: String },
+
+    #[error("Database schema version {0} is newer than this app supports ({1})")]
+    SchemaTooNew(u32, u32),
+
+    #[error("Nothing to undo")]
+    NothingToUndo,
+
+    #[error("Nothing to redo")]
+    NothingToRedo,
+
+    #[error("Change cannot be reverted: {0}")]
+    IrreversibleChange(String),
+
+    #[error("CSV error: {0}")]
+    Csv(#[from] csv::Error),
+
+    #[error("Backup not found: {0}")]
+    BackupNotFound(String),
+
+    #[error("Invalid search query: {0}")]
+    InvalidQuery(String),
+
+    #[error("Invalid backup: {0}")]
+    InvalidBackup(String),
+
+    #[error("File error: {0}")]
+    Io(#[from] std::io::Error),
+
     #[error("Internal error: {0}")]
     Internal(String),
 }
 
+impl From<rusqlite::Error> for AppError {
+    fn from(e: rusqlite::Error) -> Self {
+        let message = || match &e {
+            rusqlite::Error::SqliteFailure(_, Some(message)) => message.clone(),
+            rusqlite::Error::SqliteFailure(failure, None) => failure.to_string(),
+            _ => e.to_string(),
+        };
+        match e.sqlite_error_code() {
+            // Only duplicates conflict with existing data; CHECK, NOT NULL and
+            // foreign key failures mean the input itself is invalid
+            Some(ErrorCode::ConstraintViolation) => match &e {
+                rusqlite::Error::SqliteFailure(failure, _)
+                    if matches!(
+                        failure.extended_code,
+                        ffi::SQLITE_CONSTRAINT_UNIQUE | ffi::SQLITE_CONSTRAINT_PRIMARYKEY
+                    ) =>
+                {
+                    AppError::Conflict(message())
+                }
+                _ => AppError::Validation(message()),
+            },
+            Some(ErrorCode::DatabaseBusy | ErrorCode::DatabaseLocked) => AppError::DbLocked,
+            Some(ErrorCode::DatabaseCorrupt | ErrorCode::NotADatabase) => {
+                AppError::DbCorrupt(message())
+            }
+            _ => AppError::Database(e),
+        }
+    }
+}
+
+impl AppError {
+    pub fn invalid_field(field: &str, message: impl Into<String>) -> AppError {
+        AppError::InvalidField {
+            field: field.to_string(),
+            message: message.into(),
+        }
+    }
+
+    /// Nests an `InvalidField` error under `parent`, for validation done by a
+    /// section of a larger input.
+    pub fn within(self, parent: &str) -> AppError {
+        match self {
+            AppError::InvalidField { field, message } => AppError::InvalidField {
+                field: format!("{}.{}", parent, field),
+                message,
+            },
+            other => other,
+        }
+    }
+
+    /// Stable identifier the frontend and API clients can branch on. Never
+    /// rename a shipped code.
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::Database(_) => "DATABASE",
+            AppError::Conflict(_) => "CONFLICT",
+            AppError::DbLocked => "DB_LOCKED",
+            AppError::DbCorrupt(_) => "DB_CORRUPT",
+            AppError::CardNotFound(_) => "CARD_NOT_FOUND",
+            AppError::TodoNotFound(_) => "TODO_NOT_FOUND",
+            AppError::TagNotFound(_) => "TAG_NOT_FOUND",
+            AppError::BudgetNotFound(_) => "BUDGET_NOT_FOUND",
+            AppError::RecurrenceNotFound(_) => "RECURRENCE_NOT_FOUND",
+            AppError::RateNotFound { .. } => "RATE_NOT_FOUND",
+            AppError::InvalidAmount(_) => "INVALID_AMOUNT",
+            AppError::InvalidCurrency(_) => "INVALID_CURRENCY",
+            AppError::Validation(_) | AppError::InvalidField { .. } => "VALIDATION",
+            AppError::SchemaTooNew(..) => "SCHEMA_TOO_NEW",
+            AppError::NothingToUndo => "NOTHING_TO_UNDO",
+            AppError::NothingToRedo => "NOTHING_TO_REDO",
+            AppError::IrreversibleChange(_) => "IRREVERSIBLE_CHANGE",
+            AppError::Csv(_) => "CSV",
+            AppError::BackupNotFound(_) => "BACKUP_NOT_FOUND",
+            AppError::InvalidQuery(_) => "INVALID_QUERY",
+            AppError::InvalidBackup(_) => "INVALID_BACKUP",
+            AppError::Io(_) => "IO",
+            AppError::Internal(_) => "INTERNAL",
+        }
+    }
+
+    /// HTTP status for the local API: missing rows are 404, bad input 400,
+    /// and state the request cannot change right now 409 or 503.
+    pub fn http_status(&self) -> u16 {
+        match self {
+            AppError::CardNotFound(_)
+            | AppError::TodoNotFound(_)
+            | AppError::TagNotFound(_)
+            | AppError::BudgetNotFound(_)
+            | AppError::RecurrenceNotFound(_)
+            | AppError::RateNotFound { .. }
+            | AppError::BackupNotFound(_) => 404,
+            AppError::InvalidAmount(_)
+            | AppError::InvalidCurrency(_)
+            | AppError::Validation(_)
+            | AppError::InvalidField { .. }
+            | AppError::InvalidQuery(_)
+            | AppError::InvalidBackup(_)
+            | AppError::Csv(_) => 400,
+            AppError::Conflict(_)
+            | AppError::NothingToUndo
+            | AppError::NothingToRedo
+            | AppError::IrreversibleChange(_) => 409,
+            AppError::DbLocked => 503,
+            AppError::Database(_)
+            | AppError::DbCorrupt(_)
+            | AppError::SchemaTooNew(..)
+            | AppError::Io(_)
+            | AppError::Internal(_) => 500,
+        }
+    }
+
+    /// Machine-readable context: the missing id, the rejected value or the
+    /// invalid field.
+    pub fn details(&self) -> Option<Value> {
+        match self {
+            AppError::CardNotFound(id)
+            | AppError::TodoNotFound(id)
+            | AppError::TagNotFound(id)
+            | AppError::BudgetNotFound(id)
+            | AppError::RecurrenceNotFound(id)
+            | AppError::BackupNotFound(id) => Some(json!({ "id": id })),
+            AppError::RateNotFound { base, quote } => Some(json!({ "base": base, "quote": quote })),
+            AppError::InvalidAmount(value) | AppError::InvalidCurrency(value) => {
+                Some(json!({ "value": value }))
+            }
+            AppError::InvalidField { field, .. } => Some(json!({ "field": field })),
+            AppError::SchemaTooNew(version, supported) => {
+                Some(json!({ "version": version, "supported": supported }))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Errors reach the frontend as `{ code, message, details }`.
+#[derive(Serialize)]
+struct ErrorBody<'a> {
+    code: &'a str,
+    message: String,
+    details: Option<Value>,
+}
+
 impl serde::Serialize for AppError {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: serde::ser::Serializer,
     {
-        serializer.serialize_str(self.to_string().as_ref())
+        ErrorBody {
+            code: self.code(),
+            message: self.to_string(),
+            details: self.details(),
+        }
+        .serialize(serializer)
     }
 }
@@ -0,0 +1,544 @@
+use crate::currency::DEFAULT_CURRENCY;
+use crate::errors::AppError;
+use crate::models::{ChangeLogDto, Page};
+use crate::money::Money;
+use crate::pagination::Cursor;
+use crate::store::{now_iso, Store};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde_json::{json, Value};
+
+/// ChangeLog kinds whose payloads carry `before`/`after` snapshots.
+const REVERSIBLE_KINDS: &[&str] = &[
+    "created",
+    "updated",
+    "locked_amount_updated",
+    "todo_added",
+    "todo_updated",
+    "todo_deleted",
+    "archived",
+    "unarchived",
+    "deleted",
+    "restored",
+];
+
+pub fn log_change(
+    conn: &Connection,
+    card_id: &str,
+    kind: &str,
+    payload: &Value,
+    now: &str,
+) -> Result<(), AppError> {
+    conn.execute(
+        "INSERT INTO ChangeLog (id, cardId, kind, payload, createdAt) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            uuid::Uuid::new_v4().to_string(),
+            card_id,
+            kind,
+            payload.to_string(),
+            now
+        ],
+    )?;
+    Ok(())
+}
+
+pub fn map_change_row(row: &rusqlite::Row) -> rusqlite::Result<ChangeLogDto> {
+    let payload_str: String = row.get(3)?;
+    let payload: Value = serde_json::from_str(&payload_str).unwrap_or(json!({}));
+    Ok(ChangeLogDto {
+        id: row.get(0)?,
+        card_id: row.get(1)?,
+        kind: row.get(2)?,
+        payload,
+        created_at: row.get(4)?,
+        undone: row.get::<_, Option<String>>(5)?.is_some(),
+    })
+}
+
+/// Newest-first page of ChangeLog entries, optionally for one card only.
+pub fn change_page(
+    conn: &Connection,
+    card_id: Option<&str>,
+    limit: u32,
+    cursor: Option<String>,
+) -> Result<Page<ChangeLogDto>, AppError> {
+    const SORT: &str = "created:desc";
+
+    let (after_key, after_id) = match cursor {
+        Some(cursor) => {
+            let cursor = Cursor::decode(&cursor, SORT)?;
+            (Some(cursor.key), Some(cursor.id))
+        }
+        None => (None, None),
+    };
+
+    let mut stmt = conn.prepare(
+        "SELECT id, cardId, kind, payload, createdAt, undoneAt FROM ChangeLog
+         WHERE (?1 IS NULL OR cardId = ?1)
+           AND (?2 IS NULL OR createdAt < ?2 OR (createdAt = ?2 AND id < ?3))
+         ORDER BY createdAt DESC, id DESC LIMIT ?4",
+    )?;
+    let mut items = stmt
+        .query_map(
+            params![card_id, after_key, after_id, limit + 1],
+            map_change_row,
+        )?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let next_cursor = if items.len() > limit as usize {
+        items.truncate(limit as usize);
+        items.last().map(|change| {
+            Cursor {
+                sort: SORT.to_string(),
+                key: change.created_at.clone(),
+                id: change.id.clone(),
+            }
+            .encode()
+        })
+    } else {
+        None
+    };
+
+    let total_count = conn.query_row(
+        "SELECT COUNT(*) FROM ChangeLog WHERE ?1 IS NULL OR cardId = ?1",
+        params![card_id],
+        |row| row.get(0),
+    )?;
+
+    Ok(Page {
+        items,
+        next_cursor,
+        total_count,
+    })
+}
+
+/// Snapshot of the user-editable card fields.
+pub fn card_snapshot(conn: &Connection, card_id: &str) -> Result<Value, AppError> {
+    conn.query_row(
+        "SELECT title, amount, lockedAmount, archived, archivedAt, createdAt, deletedAt, currency, pinned FROM Card WHERE id = ?1",
+        params![card_id],
+        |row| {
+            Ok(json!({
+                "title": row.get::<_, Option<String>>(0)?,
+                "amount": row.get::<_, Money>(1)?.to_string(),
+                "locked_amount": row.get::<_, Option<Money>>(2)?.map(|a| a.to_string()),
+                "archived": row.get::<_, i32>(3)? != 0,
+                "archived_at": row.get::<_, Option<String>>(4)?,
+                "created_at": row.get::<_, String>(5)?,
+                "deleted_at": row.get::<_, Option<String>>(6)?,
+                "currency": row.get::<_, String>(7)?,
+                "pinned": row.get::<_, i32>(8)? != 0,
+            }))
+        },
+    )
+    .map_err(|e| match e {
+        rusqlite::Error::QueryReturnedNoRows => AppError::CardNotFound(card_id.to_string()),
+        _ => e.into(),
+    })
+}
+
+/// Snapshot of a todo row, enough to recreate it after deletion.
+pub fn todo_snapshot(conn: &Connection, todo_id: &str) -> Result<Value, AppError> {
+    let mut snapshot = conn.query_row(
+        "SELECT id, cardId, title, amount, done, scheduledAt, orderIndex, createdAt, currency FROM Todo WHERE id = ?1",
+        params![todo_id],
+        |row| {
+            Ok(json!({
+                "id": row.get::<_, String>(0)?,
+                "card_id": row.get::<_, String>(1)?,
+                "title": row.get::<_, String>(2)?,
+                "amount": row.get::<_, Option<Money>>(3)?.map(|a| a.to_string()),
+                "done": row.get::<_, i32>(4)? != 0,
+                "scheduled_at": row.get::<_, Option<String>>(5)?,
+                "order_index": row.get::<_, i32>(6)?,
+                "created_at": row.get::<_, String>(7)?,
+                "currency": row.get::<_, Option<String>>(8)?,
+            }))
+        },
+    )
+    .map_err(|e| match e {
+        rusqlite::Error::QueryReturnedNoRows => AppError::TodoNotFound(todo_id.to_string()),
+        _ => e.into(),
+    })?;
+
+    let tag_ids = conn
+        .prepare("SELECT tagId FROM TodoTag WHERE todoId = ?1 ORDER BY tagId")?
+        .query_map(params![todo_id], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+    snapshot["tag_ids"] = json!(tag_ids);
+
+    Ok(snapshot)
+}
+
+/// Snapshot of the recurrence rule a template todo carries, with the ids of
+/// the occurrences it generated, or null when the todo does not recur.
+pub fn recurrence_snapshot(conn: &Connection, todo_id: &str) -> Result<Value, AppError> {
+    let rule = conn
+        .query_row(
+            "SELECT cardId, frequency, interval, startAt, endAt, occurrences, nextAt FROM Recurrence WHERE todoId = ?1",
+            params![todo_id],
+            |row| {
+                Ok(json!({
+                    "card_id": row.get::<_, String>(0)?,
+                    "frequency": row.get::<_, String>(1)?,
+                    "interval": row.get::<_, i64>(2)?,
+                    "start_at": row.get::<_, String>(3)?,
+                    "end_at": row.get::<_, Option<String>>(4)?,
+                    "occurrences": row.get::<_, i64>(5)?,
+                    "next_at": row.get::<_, String>(6)?,
+                }))
+            },
+        )
+        .optional()?;
+    let Some(mut rule) = rule else {
+        return Ok(Value::Null);
+    };
+
+    let occurrence_ids = conn
+        .prepare("SELECT id FROM Todo WHERE recurrenceOf = ?1 ORDER BY id")?
+        .query_map(params![todo_id], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+    rule["occurrence_ids"] = json!(occurrence_ids);
+
+    Ok(rule)
+}
+
+/// Revert the newest change the user made that has not been undone yet.
+/// Entries written by the archiver, recurrences, imports or a backup merge
+/// are not part of the undo stack.
+pub fn undo_last(conn: &mut Connection, now: &str) -> Result<ChangeLogDto, AppError> {
+    let tx = conn.transaction()?;
+
+    let change = tx
+        .query_row(
+            &format!(
+                "SELECT id, cardId, kind, payload, createdAt, undoneAt FROM ChangeLog
+                 WHERE undoneAt IS NULL AND {} AND {}
+                 ORDER BY rowid DESC LIMIT 1",
+                reversible_filter(),
+                user_filter()
+            ),
+            [],
+            map_change_row,
+        )
+        .optional()?
+        .ok_or(AppError::NothingToUndo)?;
+
+    match change.kind.as_str() {
+        "created" => {
+            // Trash rather than delete so the card's history survives for redo
+            let trashed = tx.execute(
+                "UPDATE Card SET deletedAt = ?1, updatedAt = ?2 WHERE id = ?3",
+                params![now, now, change.card_id],
+            )?;
+            expect_affected(&change, trashed)?;
+        }
+        "todo_added" => {
+            let after = snapshot_field(&change, "after")?;
+            let deleted = tx.execute(
+                "DELETE FROM Todo WHERE id = ?1",
+                params![str_field(after, "id")],
+            )?;
+            expect_affected(&change, deleted)?;
+        }
+        "todo_updated" => restore_todo(&tx, snapshot_field(&change, "before")?, now)?,
+        "todo_deleted" => {
+            let before = snapshot_field(&change, "before")?;
+            restore_todo(&tx, before, now)?;
+            // Deleting a template cascaded to its rule and unlinked its occurrences
+            if change.payload["recurrence"].is_object() {
+                restore_recurrence(&tx, before, &change.payload["recurrence"])?;
+            }
+        }
+        _ => restore_card(
+            &tx,
+            &change.card_id,
+            snapshot_field(&change, "before")?,
+            now,
+        )?,
+    }
+
+    tx.execute(
+        "UPDATE ChangeLog SET undoneAt = ?1 WHERE id = ?2",
+        params![now, change.id],
+    )?;
+    tx.commit()?;
+
+    Ok(ChangeLogDto {
+        undone: true,
+        ..change
+    })
+}
+
+impl Store {
+    pub fn undo_last_change(&mut self) -> Result<ChangeLogDto, AppError> {
+        undo_last(self.conn_mut(), &now_iso())
+    }
+
+    pub fn redo_change(&mut self, change_id: Option<&str>) -> Result<ChangeLogDto, AppError> {
+        redo(self.conn_mut(), change_id, &now_iso())
+    }
+}
+
+/// Re-apply the oldest undone change. Any change recorded after an undo
+/// discards the redo history, like a regular editor undo stack.
+pub fn redo(
+    conn: &mut Connection,
+    change_id: Option<&str>,
+    now: &str,
+) -> Result<ChangeLogDto, AppError> {
+    let tx = conn.transaction()?;
+    let reversible = reversible_filter();
+    let user = user_filter();
+
+    let change = tx
+        .query_row(
+            &format!(
+                "SELECT id, cardId, kind, payload, createdAt, undoneAt FROM ChangeLog
+                 WHERE undoneAt IS NOT NULL AND {reversible} AND {user}
+                   AND rowid > COALESCE(
+                       (SELECT MAX(rowid) FROM ChangeLog WHERE undoneAt IS NULL AND {reversible}),
+                       0)
+                 ORDER BY rowid ASC LIMIT 1"
+            ),
+            [],
+            map_change_row,
+        )
+        .optional()?
+        .ok_or(AppError::NothingToRedo)?;
+
+    if let Some(id) = change_id {
+        if id != change.id {
+            return Err(AppError::IrreversibleChange(format!(
+                "{} is not the next change to redo",
+                id
+            )));
+        }
+    }
+
+    match change.kind.as_str() {
+        "todo_deleted" => {
+            let before = snapshot_field(&change, "before")?;
+            let deleted = tx.execute(
+                "DELETE FROM Todo WHERE id = ?1",
+                params![str_field(before, "id")],
+            )?;
+            expect_affected(&change, deleted)?;
+        }
+        "todo_added" | "todo_updated" => restore_todo(&tx, snapshot_field(&change, "after")?, now)?,
+        _ => restore_card(&tx, &change.card_id, snapshot_field(&change, "after")?, now)?,
+    }
+
+    tx.execute(
+        "UPDATE ChangeLog SET undoneAt = NULL WHERE id = ?1",
+        params![change.id],
+    )?;
+    tx.commit()?;
+
+    Ok(ChangeLogDto {
+        undone: false,
+        ..change
+    })
+}
+
+/// Entries written before snapshots were recorded cannot be reverted and
+/// are skipped entirely.
+fn reversible_filter() -> String {
+    let kinds = REVERSIBLE_KINDS
+        .iter()
+        .map(|k| format!("'{}'", k))
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!(
+        "kind IN ({}) AND (json_type(payload, '$.before') = 'object' OR json_type(payload, '$.after') = 'object')",
+        kinds
+    )
+}
+
+/// Entries the archiver, a recurrence run or an import wrote on the user's
+/// behalf carry a `reason` naming them; entries merged in from a backup are
+/// marked with `merged_from_backup`.
+fn user_filter() -> &'static str {
+    "COALESCE(json_extract(payload, '$.reason'), '') NOT IN ('recurrence', 'import')
+     AND COALESCE(json_extract(payload, '$.reason'), '') NOT LIKE 'auto_archive%'
+     AND json_extract(payload, '$.merged_from_backup') IS NULL"
+}
+
+/// A reverse statement that touched nothing means the row changed under the
+/// entry; failing leaves the entry as it was instead of marking it applied.
+fn expect_affected(change: &ChangeLogDto, affected: usize) -> Result<(), AppError> {
+    if affected == 0 {
+        Err(AppError::IrreversibleChange(format!(
+            "{} no longer matches the data it changed",
+            change.id
+        )))
+    } else {
+        Ok(())
+    }
+}
+
+fn snapshot_field<'a>(change: &'a ChangeLogDto, field: &str) -> Result<&'a Value, AppError> {
+    let value = &change.payload[field];
+    if value.is_object() {
+        Ok(value)
+    } else {
+        Err(AppError::IrreversibleChange(format!(
+            "{} has no {} snapshot",
+            change.id, field
+        )))
+    }
+}
+
+fn str_field<'a>(snapshot: &'a Value, field: &str) -> Option<&'a str> {
+    snapshot[field].as_str()
+}
+
+fn money_field(snapshot: &Value, field: &str) -> Result<Option<Money>, AppError> {
+    str_field(snapshot, field).map(Money::parse).transpose()
+}
+
+fn restore_card(
+    conn: &Connection,
+    card_id: &str,
+    snapshot: &Value,
+    now: &str,
+) -> Result<(), AppError> {
+    let amount = money_field(snapshot, "amount")?.unwrap_or(Money::ZERO);
+    let locked_amount = money_field(snapshot, "locked_amount")?;
+    let archived = snapshot["archived"].as_bool().unwrap_or(false);
+    let pinned = snapshot["pinned"].as_bool().unwrap_or(false);
+
+    let updated = conn.execute(
+        "UPDATE Card SET title = ?1, amount = ?2, lockedAmount = ?3, archived = ?4, archivedAt = ?5, deletedAt = ?6,
+             currency = COALESCE(?7, currency), pinned = ?8, updatedAt = ?9
+         WHERE id = ?10",
+        params![
+            str_field(snapshot, "title"),
+            amount,
+            locked_amount,
+            archived as i32,
+            str_field(snapshot, "archived_at"),
+            str_field(snapshot, "deleted_at"),
+            str_field(snapshot, "currency"),
+            pinned as i32,
+            now,
+            card_id
+        ],
+    )?;
+
+    if updated == 0 {
+        conn.execute(
+            "INSERT INTO Card (id, title, amount, lockedAmount, archived, archivedAt, currency, pinned, createdAt, updatedAt)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![
+                card_id,
+                str_field(snapshot, "title"),
+                amount,
+                locked_amount,
+                archived as i32,
+                str_field(snapshot, "archived_at"),
+                str_field(snapshot, "currency").unwrap_or(DEFAULT_CURRENCY),
+                pinned as i32,
+                str_field(snapshot, "created_at").unwrap_or(now),
+                now
+            ],
+        )?;
+    }
+    Ok(())
+}
+
+fn restore_todo(conn: &Connection, snapshot: &Value, now: &str) -> Result<(), AppError> {
+    let todo_id = str_field(snapshot, "id")
+        .ok_or_else(|| AppError::IrreversibleChange("todo snapshot has no id".into()))?;
+    let card_id = str_field(snapshot, "card_id")
+        .ok_or_else(|| AppError::IrreversibleChange("todo snapshot has no card_id".into()))?;
+    let amount = money_field(snapshot, "amount")?;
+    let done = snapshot["done"].as_bool().unwrap_or(false);
+    let order_index = snapshot["order_index"].as_i64().unwrap_or(0);
+
+    let updated = conn.execute(
+        "UPDATE Todo SET title = ?1, amount = ?2, done = ?3, scheduledAt = ?4, orderIndex = ?5, currency = ?6,
+             updatedAt = ?7
+         WHERE id = ?8",
+        params![
+            str_field(snapshot, "title"),
+            amount,
+            done as i32,
+            str_field(snapshot, "scheduled_at"),
+            order_index,
+            str_field(snapshot, "currency"),
+            now,
+            todo_id
+        ],
+    )?;
+
+    if updated == 0 {
+        conn.execute(
+            "INSERT INTO Todo (id, cardId, title, amount, done, scheduledAt, orderIndex, currency, createdAt, updatedAt)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![
+                todo_id,
+                card_id,
+                str_field(snapshot, "title"),
+                amount,
+                done as i32,
+                str_field(snapshot, "scheduled_at"),
+                order_index,
+                str_field(snapshot, "currency"),
+                str_field(snapshot, "created_at").unwrap_or(now),
+                now
+            ],
+        )?;
+    }
+
+    // Snapshots taken before tags existed leave the links alone
+    if let Some(tag_ids) = snapshot["tag_ids"].as_array() {
+        conn.execute("DELETE FROM TodoTag WHERE todoId = ?1", params![todo_id])?;
+        for tag_id in tag_ids.iter().filter_map(Value::as_str) {
+            // Tags deleted since the snapshot are skipped
+            conn.execute(
+                "INSERT OR IGNORE INTO TodoTag (todoId, tagId) SELECT ?1, id FROM Tag WHERE id = ?2",
+                params![todo_id, tag_id],
+            )?;
+        }
+    }
+
+    conn.execute(
+        "UPDATE Card SET updatedAt = ?1 WHERE id = ?2",
+        params![now, card_id],
+    )?;
+    Ok(())
+}
+
+fn restore_recurrence(conn: &Connection, todo: &Value, rule: &Value) -> Result<(), AppError> {
+    let todo_id = str_field(todo, "id")
+        .ok_or_else(|| AppError::IrreversibleChange("todo snapshot has no id".into()))?;
+
+    conn.execute(
+        "INSERT OR REPLACE INTO Recurrence (todoId, cardId, frequency, interval, startAt, endAt, occurrences, nextAt)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        params![
+            todo_id,
+            str_field(rule, "card_id"),
+            str_field(rule, "frequency"),
+            rule["interval"].as_i64().unwrap_or(1),
+            str_field(rule, "start_at"),
+            str_field(rule, "end_at"),
+            rule["occurrences"].as_i64().unwrap_or(0),
+            str_field(rule, "next_at"),
+        ],
+    )?;
+
+    // Occurrences deleted since are skipped
+    for occurrence_id in rule["occurrence_ids"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+    {
+        conn.execute(
+            "UPDATE Todo SET recurrenceOf = ?1 WHERE id = ?2 AND recurrenceOf IS NULL",
+            params![todo_id, occurrence_id],
+        )?;
+    }
+    Ok(())
+}
@@ -1,11 +1,29 @@
+pub mod api;
 mod archiver;
+pub mod backup;
+pub mod budgets;
+pub mod cli;
 mod commands;
+pub mod csv_io;
+mod currency;
+mod dates;
 mod db;
-mod errors;
-mod models;
+pub mod errors;
+mod history;
+mod migrations;
+pub mod models;
+mod money;
+pub mod pagination;
+pub mod recurrence;
+mod reports;
+mod search_query;
+mod settings;
+pub mod snapshots;
+pub mod store;
+mod timeline;
 
 use commands::*;
-use tauri::Manager;
+use tauri::{Listener, Manager};
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
@@ -20,6 +38,12 @@ pub fn run() {
             db::init_db(app_data_dir).expect("Failed to initialize database");
 
             tauri::async_runtime::spawn(archiver::start_archiver());
+            api::reload(app.handle());
+            let handle = app.handle().clone();
+            app.listen(settings::SETTINGS_CHANGED_EVENT, move |_| {
+                archiver::wake();
+                api::reload(&handle);
+            });
 
             // Debug-only: Enable logging plugin
             if cfg!(debug_assertions) {
@@ -46,15 +70,54 @@ pub fn run() {
             get_card,
             create_card,
             update_card,
+            set_locked_amount,
             delete_card,
+            list_trashed_cards,
+            restore_card,
+            purge_trash,
             add_todo,
             update_todo,
             delete_todo,
+            list_tags,
+            create_tag,
+            update_tag,
+            delete_tag,
+            set_todo_tags,
+            totals_by_tag,
+            spending_by_period,
+            set_budget,
+            delete_budget,
+            budget_status,
+            set_recurrence,
+            clear_recurrence,
+            list_recurrences,
+            run_recurrences,
             search,
             recent_changes,
+            card_history,
+            card_history_at,
             archive_card,
             unarchive_card,
             archive_old_cards,
+            pin_card,
+            get_settings,
+            update_settings,
+            get_archive_policy,
+            update_archive_policy,
+            schema_version,
+            check_integrity,
+            export_csv,
+            import_csv,
+            list_exchange_rates,
+            set_exchange_rate,
+            delete_exchange_rate,
+            import_exchange_rates,
+            export_backup,
+            restore_backup,
+            list_backups,
+            restore_from_backup,
+            undo_last_change,
+            redo_change,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
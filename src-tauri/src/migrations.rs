@@ -0,0 +1,138 @@
+use crate::errors::AppError;
+use rusqlite::Connection;
+
+struct Migration {
+    version: u32,
+    name: &'static str,
+    sql: &'static str,
+}
+
+/// Every schema change, in order. Append only; never edit a shipped entry.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "init",
+        sql: include_str!("../migrations/0001_init.sql"),
+    },
+    Migration {
+        version: 2,
+        name: "money_minor_units",
+        sql: include_str!("../migrations/0002_money_minor_units.sql"),
+    },
+    Migration {
+        version: 3,
+        name: "changelog_undo",
+        sql: include_str!("../migrations/0003_changelog_undo.sql"),
+    },
+    Migration {
+        version: 4,
+        name: "card_trash",
+        sql: include_str!("../migrations/0004_card_trash.sql"),
+    },
+    Migration {
+        version: 5,
+        name: "orphan_cleanup",
+        sql: include_str!("../migrations/0005_orphan_cleanup.sql"),
+    },
+    Migration {
+        version: 6,
+        name: "tags",
+        sql: include_str!("../migrations/0006_tags.sql"),
+    },
+    Migration {
+        version: 7,
+        name: "recurrence",
+        sql: include_str!("../migrations/0007_recurrence.sql"),
+    },
+    Migration {
+        version: 8,
+        name: "currency",
+        sql: include_str!("../migrations/0008_currency.sql"),
+    },
+    Migration {
+        version: 9,
+        name: "archive_policy",
+        sql: include_str!("../migrations/0009_archive_policy.sql"),
+    },
+    Migration {
+        version: 10,
+        name: "budgets",
+        sql: include_str!("../migrations/0010_budgets.sql"),
+    },
+];
+
+pub fn latest_version() -> u32 {
+    MIGRATIONS.last().map(|m| m.version).unwrap_or(0)
+}
+
+pub fn current_version(conn: &Connection) -> Result<u32, AppError> {
+    Ok(conn.query_row("PRAGMA user_version", [], |row| row.get(0))?)
+}
+
+/// Bring the database up to `latest_version()`, one transaction per migration.
+pub fn run(conn: &mut Connection) -> Result<u32, AppError> {
+    adopt_legacy_schema(conn)?;
+
+    let current = current_version(conn)?;
+    let latest = latest_version();
+    if current > latest {
+        return Err(AppError::SchemaTooNew(current, latest));
+    }
+
+    for migration in MIGRATIONS.iter().filter(|m| m.version > current) {
+        let tx = conn.transaction()?;
+        tx.execute_batch(migration.sql)?;
+        tx.pragma_update(None, "user_version", migration.version)?;
+        tx.commit()?;
+
+        log::info!(
+            "Applied migration {:04}_{}",
+            migration.version,
+            migration.name
+        );
+    }
+
+    Ok(latest)
+}
+
+/// Databases created before versioning have `user_version = 0` but already
+/// contain tables. Stamp them with the version their schema matches so the
+/// runner only applies what is missing.
+fn adopt_legacy_schema(conn: &Connection) -> Result<(), AppError> {
+    if current_version(conn)? != 0 {
+        return Ok(());
+    }
+
+    let has_card: bool = conn.query_row(
+        "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = 'Card'",
+        [],
+        |row| row.get(0),
+    )?;
+    if !has_card {
+        return Ok(());
+    }
+
+    let has_locked_amount: bool = conn.query_row(
+        "SELECT COUNT(*) > 0 FROM pragma_table_info('Card') WHERE name = 'lockedAmount'",
+        [],
+        |row| row.get(0),
+    )?;
+    if !has_locked_amount {
+        conn.execute("ALTER TABLE Card ADD COLUMN lockedAmount REAL", [])?;
+    }
+
+    let amount_type: String = conn.query_row(
+        "SELECT type FROM pragma_table_info('Card') WHERE name = 'amount'",
+        [],
+        |row| row.get(0),
+    )?;
+    let version = if amount_type.eq_ignore_ascii_case("REAL") {
+        1
+    } else {
+        2
+    };
+
+    conn.pragma_update(None, "user_version", version)?;
+    log::info!("Adopted unversioned database as schema version {}", version);
+    Ok(())
+}
@@ -1,3 +1,4 @@
+use crate::reports::Granularity;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -5,11 +6,26 @@ pub struct CardDto {
     pub id: String,
     pub title: Option<String>,
     pub amount: String,
+    /// ISO 4217 code; balances are in this currency
+    pub currency: String,
     pub locked_amount: Option<String>,
+    /// Sum of done todo amounts
+    pub spent: String,
+    /// Sum of not-done todo amounts
+    pub pending: String,
+    /// `amount` minus `spent` and `pending`
+    pub remaining: String,
+    /// `locked_amount` minus the sum of the card's todo amounts
+    pub locked_remaining: Option<String>,
     pub archived: bool,
+    /// Pinned cards can be excluded from automatic archiving
+    pub pinned: bool,
     pub created_at: String,
     pub updated_at: String,
     pub archived_at: Option<String>,
+    pub deleted_at: Option<String>,
+    /// Todos in another currency with no exchange rate, left out of balances
+    pub unconverted_count: i64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -17,11 +33,19 @@ pub struct CardWithTodosDto {
     pub id: String,
     pub title: Option<String>,
     pub amount: String,
+    pub currency: String,
     pub locked_amount: Option<String>,
+    pub spent: String,
+    pub pending: String,
+    pub remaining: String,
+    pub locked_remaining: Option<String>,
     pub archived: bool,
+    pub pinned: bool,
     pub created_at: String,
     pub updated_at: String,
     pub archived_at: Option<String>,
+    pub deleted_at: Option<String>,
+    pub unconverted_count: i64,
     pub todos: Vec<TodoDto>,
 }
 
@@ -31,11 +55,21 @@ pub struct TodoDto {
     pub card_id: String,
     pub title: String,
     pub amount: Option<String>,
+    /// `None` when the todo is in its card's currency
+    pub currency: Option<String>,
     pub done: bool,
     pub scheduled_at: Option<String>,
     pub order_index: i32,
     pub created_at: String,
     pub updated_at: String,
+    pub tags: Vec<TagDto>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TagDto {
+    pub id: String,
+    pub name: String,
+    pub color: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -45,6 +79,69 @@ pub struct ChangeLogDto {
     pub kind: String,
     pub payload: serde_json::Value,
     pub created_at: String,
+    pub undone: bool,
+}
+
+/// One field changed by a ChangeLog entry, rendered for display.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FieldDiffDto {
+    pub field: String,
+    /// `None` when the field was first set (e.g. a todo was added)
+    pub old: Option<String>,
+    /// `None` when the field went away (e.g. a todo was deleted)
+    pub new: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntryDto {
+    pub id: String,
+    pub card_id: String,
+    pub kind: String,
+    pub created_at: String,
+    pub undone: bool,
+    /// Set for todo changes
+    pub todo_id: Option<String>,
+    /// e.g. `Updated todo "Coffee": amount 5.00 → 7.50, done no → yes`
+    pub summary: String,
+    pub diffs: Vec<FieldDiffDto>,
+}
+
+/// A todo as it was at `CardAtDto::as_of`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoricalTodoDto {
+    pub id: String,
+    pub title: String,
+    pub amount: Option<String>,
+    #[serde(default)]
+    pub currency: Option<String>,
+    pub done: bool,
+    pub scheduled_at: Option<String>,
+    #[serde(default)]
+    pub order_index: i64,
+    pub created_at: String,
+}
+
+/// A card and its todos reconstructed from the ChangeLog at `as_of`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CardAtDto {
+    pub id: String,
+    pub as_of: String,
+    pub title: Option<String>,
+    pub amount: String,
+    pub currency: String,
+    pub locked_amount: Option<String>,
+    pub archived: bool,
+    pub pinned: bool,
+    /// Whether the card was in the trash
+    pub deleted: bool,
+    pub created_at: String,
+    /// Totals over todos in the card currency
+    pub spent: String,
+    pub pending: String,
+    pub remaining: String,
+    /// Todos in another currency, left out of the totals
+    pub unconverted_count: i64,
+    pub todos: Vec<HistoricalTodoDto>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -60,6 +157,8 @@ pub struct SearchResultDto {
 pub struct AddTodoResult {
     pub todo: TodoDto,
     pub updated_card: CardDto,
+    /// Set when the change leaves a budget the todo counts toward over its amount
+    pub warning: Option<BudgetWarningDto>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -71,3 +170,274 @@ pub struct OkResponse {
 pub struct ArchiveResult {
     pub archived_count: i32,
 }
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SchemaVersionDto {
+    pub current: u32,
+    pub latest: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PurgeResult {
+    pub purged_count: i32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ForeignKeyViolationDto {
+    pub table: String,
+    pub rowid: Option<i64>,
+    pub parent: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IntegrityReportDto {
+    pub ok: bool,
+    pub integrity_errors: Vec<String>,
+    pub foreign_key_violations: Vec<ForeignKeyViolationDto>,
+    pub orphaned_search_rows: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TagTotalDto {
+    /// `None` for the bucket of todos without any tag
+    pub tag_id: Option<String>,
+    /// `None` for the untagged bucket, like `tag_id`
+    pub name: Option<String>,
+    /// Card currency `total` is in
+    pub currency: String,
+    pub total: String,
+    pub todo_count: i64,
+    /// Todos in another currency with no exchange rate, left out of `total`
+    pub unconverted_count: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecurrenceDto {
+    pub todo_id: String,
+    pub card_id: String,
+    pub title: String,
+    pub frequency: String,
+    pub interval: u32,
+    pub start_at: String,
+    pub end_at: Option<String>,
+    pub next_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecurrenceRunResult {
+    pub created_count: u32,
+}
+
+/// Which cards `export_csv` writes. Trashed cards are never exported.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExportFilter {
+    pub card_ids: Option<Vec<String>>,
+    pub include_archived: bool,
+    /// Bounds on the todo's `scheduledAt` (falling back to `createdAt`)
+    pub from: Option<String>,
+    pub to: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportResult {
+    pub path: String,
+    pub row_count: i64,
+}
+
+/// CSV header names to read each field from, for files not produced by
+/// `export_csv`. Unset fields use the export header names.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CsvMapping {
+    pub card_id: Option<String>,
+    pub card_title: Option<String>,
+    pub card_amount: Option<String>,
+    pub card_currency: Option<String>,
+    pub card_locked_amount: Option<String>,
+    pub card_archived: Option<String>,
+    pub card_archived_at: Option<String>,
+    pub card_created_at: Option<String>,
+    pub todo_title: Option<String>,
+    pub todo_amount: Option<String>,
+    pub todo_currency: Option<String>,
+    pub todo_done: Option<String>,
+    pub todo_scheduled_at: Option<String>,
+    pub todo_created_at: Option<String>,
+    pub tags: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportRowError {
+    /// Line number in the file, the header being line 1
+    pub row: u64,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportReport {
+    pub dry_run: bool,
+    pub cards_created: i32,
+    pub todos_created: i32,
+    pub duplicates_skipped: i32,
+    pub errors: Vec<ImportRowError>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExchangeRateDto {
+    pub base: String,
+    pub quote: String,
+    /// Units of `quote` per unit of `base`
+    pub rate: f64,
+    /// `manual` or `import`
+    pub source: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RateImportResult {
+    pub imported_count: i32,
+    pub errors: Vec<ImportRowError>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupResult {
+    pub path: String,
+    pub schema_version: u32,
+    pub card_count: i32,
+    pub todo_count: i32,
+    pub change_count: i32,
+}
+
+/// A rotating SQLite snapshot of tin.db in the `backups/` folder.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotDto {
+    pub id: String,
+    pub path: String,
+    pub created_at: String,
+    pub size_bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RestoreResult {
+    pub mode: String,
+    /// Schema version the backup was taken at
+    pub schema_version: u32,
+    pub cards_restored: i32,
+    pub todos_restored: i32,
+    pub changes_restored: i32,
+}
+
+/// One page of a list command.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Pass back as `cursor` to fetch the next page; `None` on the last page
+    pub next_cursor: Option<String>,
+    /// Matching items across all pages
+    pub total_count: i64,
+}
+
+/// Spending per period from `spending_by_period`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpendingReportDto {
+    pub granularity: Granularity,
+    /// First day of the first period
+    pub from: String,
+    /// Day after the last period
+    pub to: String,
+    pub include_archived: bool,
+    pub periods: Vec<PeriodSpendingDto>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PeriodSpendingDto {
+    /// First day of the period (`YYYY-MM-DD`, UTC)
+    pub start: String,
+    /// First day of the next period
+    pub end: String,
+    /// One entry per card currency with spending in this or the previous period
+    pub totals: Vec<SpendingTotalDto>,
+    /// Cards with spending in this or the previous period, largest first
+    pub cards: Vec<CardSpendingDto>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpendingTotalDto {
+    pub currency: String,
+    /// Done todos
+    pub spent: String,
+    /// Open todos
+    pub pending: String,
+    /// `spent + pending`
+    pub total: String,
+    pub todo_count: i64,
+    /// Todos left out of the sums for lack of an exchange rate
+    pub unconverted_count: i64,
+    /// `total` of the period before
+    pub previous_total: String,
+    /// `total - previous_total`
+    pub change: String,
+    /// `change` relative to `previous_total`; `None` when that is zero
+    pub change_percent: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CardSpendingDto {
+    pub card_id: String,
+    pub title: Option<String>,
+    pub currency: String,
+    pub spent: String,
+    pub pending: String,
+    pub total: String,
+    pub todo_count: i64,
+    pub unconverted_count: i64,
+    pub previous_total: String,
+    pub change: String,
+    pub change_percent: Option<f64>,
+}
+
+/// A spending limit on one card, one tag, or every card when neither is set.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BudgetDto {
+    pub id: String,
+    pub name: Option<String>,
+    pub card_id: Option<String>,
+    pub card_title: Option<String>,
+    pub tag_id: Option<String>,
+    pub tag_name: Option<String>,
+    /// Counts only todos dated in one calendar month
+    pub monthly: bool,
+    pub amount: String,
+    pub currency: String,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BudgetStatusDto {
+    pub budget: BudgetDto,
+    /// First day of the month measured, for monthly budgets
+    pub period_start: Option<String>,
+    /// First day of the following month
+    pub period_end: Option<String>,
+    pub spent: String,
+    pub pending: String,
+    /// `spent + pending`, in the budget currency
+    pub used: String,
+    /// `amount - used`; negative once exceeded
+    pub remaining: String,
+    pub percent_used: f64,
+    pub exceeded: bool,
+    /// Todos left out for lack of an exchange rate
+    pub unconverted_count: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BudgetWarningDto {
+    pub todo_id: String,
+    pub card_id: String,
+    pub message: String,
+    /// Every exceeded budget the todo counts toward
+    pub budgets: Vec<BudgetStatusDto>,
+}
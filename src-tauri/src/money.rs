@@ -0,0 +1,139 @@
+use crate::errors::AppError;
+use rusqlite::types::{FromSql, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
+use std::fmt;
+use std::str::FromStr;
+
+/// Number of decimal places kept for every amount.
+pub const SCALE: u32 = 6;
+
+/// Minor units per whole unit (10^SCALE).
+pub const UNIT: i64 = 1_000_000;
+
+/// An exact monetary amount stored as integer minor units (millionths).
+///
+/// Amounts travel to the frontend as strings with exactly `SCALE` decimals,
+/// so `"0.1"` parses to `100000` and renders back as `"0.100000"`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Money(i64);
+
+impl Money {
+    pub const ZERO: Money = Money(0);
+
+    /// Parse a user-supplied decimal string without going through floats.
+    pub fn parse(input: &str) -> Result<Money, AppError> {
+        let invalid = || AppError::InvalidAmount(input.to_string());
+        let s = input.trim();
+
+        let (negative, digits) = match s.as_bytes().first() {
+            Some(b'-') => (true, &s[1..]),
+            Some(b'+') => (false, &s[1..]),
+            _ => (false, s),
+        };
+
+        let (whole, frac) = match digits.split_once('.') {
+            Some((w, f)) => (w, f),
+            None => (digits, ""),
+        };
+
+        if whole.is_empty() && frac.is_empty() {
+            return Err(invalid());
+        }
+        if !whole.bytes().all(|b| b.is_ascii_digit()) || !frac.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(invalid());
+        }
+        if frac.len() > SCALE as usize {
+            return Err(invalid());
+        }
+
+        let whole_minor = if whole.is_empty() {
+            0
+        } else {
+            whole
+                .parse::<i64>()
+                .ok()
+                .and_then(|w| w.checked_mul(UNIT))
+                .ok_or_else(invalid)?
+        };
+        let frac_minor = if frac.is_empty() {
+            0
+        } else {
+            frac.parse::<i64>().map_err(|_| invalid())? * 10_i64.pow(SCALE - frac.len() as u32)
+        };
+
+        let minor = whole_minor.checked_add(frac_minor).ok_or_else(invalid)?;
+        Ok(Money(if negative { -minor } else { minor }))
+    }
+
+    /// `self + rhs`, or an error when the sum leaves the range of `Money`.
+    /// There are deliberately no `+`/`-` operators, which would wrap in
+    /// release builds.
+    pub fn checked_add(self, rhs: Money) -> Result<Money, AppError> {
+        self.0
+            .checked_add(rhs.0)
+            .map(Money)
+            .ok_or_else(|| AppError::InvalidAmount(format!("{} + {} is out of range", self, rhs)))
+    }
+
+    /// `self - rhs`, or an error when the difference leaves the range of `Money`.
+    pub fn checked_sub(self, rhs: Money) -> Result<Money, AppError> {
+        self.0
+            .checked_sub(rhs.0)
+            .map(Money)
+            .ok_or_else(|| AppError::InvalidAmount(format!("{} - {} is out of range", self, rhs)))
+    }
+
+    /// Whether the amount has at most `decimals` significant decimal places.
+    pub fn fits_decimals(self, decimals: u32) -> bool {
+        decimals >= SCALE || self.0 % 10_i64.pow(SCALE - decimals) == 0
+    }
+}
+
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.0 < 0 { "-" } else { "" };
+        let abs = self.0.unsigned_abs();
+        let unit = UNIT as u64;
+        write!(
+            f,
+            "{}{}.{:0width$}",
+            sign,
+            abs / unit,
+            abs % unit,
+            width = SCALE as usize
+        )
+    }
+}
+
+impl FromStr for Money {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Money::parse(s)
+    }
+}
+
+/// Serialized as the same decimal string the frontend receives.
+impl serde::Serialize for Money {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Money {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        Money::parse(&s).map_err(serde::de::Error::custom)
+    }
+}
+
+impl ToSql for Money {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.0))
+    }
+}
+
+impl FromSql for Money {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        i64::column_result(value).map(Money)
+    }
+}
@@ -0,0 +1,131 @@
+use crate::errors::AppError;
+use serde::{Deserialize, Serialize};
+
+/// Page size when the caller does not pass `limit`.
+pub const DEFAULT_LIMIT: u32 = 100;
+
+/// Largest page a caller may request.
+pub const MAX_LIMIT: u32 = 500;
+
+pub fn page_limit(limit: Option<u32>) -> Result<u32, AppError> {
+    match limit.unwrap_or(DEFAULT_LIMIT) {
+        0 => Err(AppError::invalid_field(
+            "limit",
+            "Page limit must be at least 1",
+        )),
+        limit => Ok(limit.min(MAX_LIMIT)),
+    }
+}
+
+/// Card orderings for the list commands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CardSort {
+    Created,
+    Updated,
+    Archived,
+    Title,
+    Amount,
+    /// `amount - spent - pending`
+    Remaining,
+}
+
+impl CardSort {
+    /// SQL expression over `CARD_SELECT`'s `c`. Never NULL, so keyset
+    /// comparisons stay simple.
+    pub fn sql(self) -> &'static str {
+        match self {
+            CardSort::Created => "c.createdAt",
+            CardSort::Updated => "c.updatedAt",
+            CardSort::Archived => "COALESCE(c.archivedAt, '')",
+            CardSort::Title => "COALESCE(c.title, '') COLLATE NOCASE",
+            CardSort::Amount => "c.amount",
+            CardSort::Remaining => "(c.amount - c.spent - c.pending)",
+        }
+    }
+
+    /// Whether the sort key is stored as integer money rather than text.
+    pub fn is_money(self) -> bool {
+        matches!(self, CardSort::Amount | CardSort::Remaining)
+    }
+
+    /// Titles read A to Z; dates and amounts newest or largest first.
+    pub fn default_order(self) -> SortOrder {
+        match self {
+            CardSort::Title => SortOrder::Asc,
+            _ => SortOrder::Desc,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    Asc,
+    Desc,
+}
+
+impl SortOrder {
+    pub fn sql(self) -> &'static str {
+        match self {
+            SortOrder::Asc => "ASC",
+            SortOrder::Desc => "DESC",
+        }
+    }
+
+    /// Comparison selecting rows that come after the cursor.
+    pub fn after(self) -> &'static str {
+        match self {
+            SortOrder::Asc => ">",
+            SortOrder::Desc => "<",
+        }
+    }
+}
+
+/// Position after the last item of a page. Clients treat the encoded form as
+/// opaque and pass it back unchanged to fetch the next page.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Cursor {
+    /// Ordering the cursor was issued for, e.g. `created:desc`
+    pub sort: String,
+    /// Sort key of the last item, or the row offset for ranked results
+    pub key: String,
+    /// Id of the last item, breaking ties between equal keys
+    pub id: String,
+}
+
+impl Cursor {
+    pub fn encode(&self) -> String {
+        serde_json::to_vec(self)
+            .unwrap_or_default()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+
+    /// Decode `encoded`, checking it was issued for the same `sort`.
+    pub fn decode(encoded: &str, sort: &str) -> Result<Cursor, AppError> {
+        let invalid = || AppError::invalid_field("cursor", "Invalid page cursor");
+
+        if encoded.len() % 2 != 0 || !encoded.is_ascii() {
+            return Err(invalid());
+        }
+        let bytes = (0..encoded.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&encoded[i..i + 2], 16))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| invalid())?;
+        let cursor: Cursor = serde_json::from_slice(&bytes).map_err(|_| invalid())?;
+
+        if cursor.sort != sort {
+            return Err(AppError::invalid_field(
+                "cursor",
+                format!(
+                    "Page cursor was issued for sort {}, not {}",
+                    cursor.sort, sort
+                ),
+            ));
+        }
+        Ok(cursor)
+    }
+}
@@ -0,0 +1,364 @@
+use crate::dates::{format_timestamp, parse_timestamp};
+use crate::errors::AppError;
+use crate::history;
+use crate::models::RecurrenceDto;
+use crate::store::{now_iso, Store};
+use chrono::{DateTime, Duration, Months, Utc};
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+
+/// Upper bound on occurrences generated for one rule in a single run, so a
+/// daily rule left alone for years cannot flood a card.
+const MAX_CATCH_UP: u32 = 366;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+impl Frequency {
+    pub fn parse(value: &str) -> Result<Frequency, AppError> {
+        match value {
+            "daily" => Ok(Frequency::Daily),
+            "weekly" => Ok(Frequency::Weekly),
+            "monthly" => Ok(Frequency::Monthly),
+            "yearly" => Ok(Frequency::Yearly),
+            _ => Err(AppError::invalid_field(
+                "frequency",
+                format!("Unknown recurrence frequency: {}", value),
+            )),
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Frequency::Daily => "daily",
+            Frequency::Weekly => "weekly",
+            Frequency::Monthly => "monthly",
+            Frequency::Yearly => "yearly",
+        }
+    }
+}
+
+/// The `n`-th occurrence after `start`. Always computed from the start so
+/// monthly rules anchored on the 31st do not drift to the 28th.
+pub fn occurrence_at(
+    start: DateTime<Utc>,
+    frequency: Frequency,
+    interval: u32,
+    n: u32,
+) -> Option<DateTime<Utc>> {
+    let steps = interval.checked_mul(n)?;
+    match frequency {
+        Frequency::Daily => start.checked_add_signed(Duration::days(steps as i64)),
+        Frequency::Weekly => start.checked_add_signed(Duration::weeks(steps as i64)),
+        Frequency::Monthly => start.checked_add_months(Months::new(steps)),
+        Frequency::Yearly => start.checked_add_months(Months::new(steps.checked_mul(12)?)),
+    }
+}
+
+const RECURRENCE_SELECT: &str =
+    "SELECT r.todoId, r.cardId, t.title, r.frequency, r.interval, r.startAt, r.endAt, r.nextAt
+     FROM Recurrence r JOIN Todo t ON t.id = r.todoId";
+
+fn map_recurrence_row(row: &rusqlite::Row) -> rusqlite::Result<RecurrenceDto> {
+    Ok(RecurrenceDto {
+        todo_id: row.get(0)?,
+        card_id: row.get(1)?,
+        title: row.get(2)?,
+        frequency: row.get(3)?,
+        interval: row.get(4)?,
+        start_at: row.get(5)?,
+        end_at: row.get(6)?,
+        next_at: row.get(7)?,
+    })
+}
+
+impl Store {
+    /// Makes `todo_id` the template of a recurring series. Occurrences go into
+    /// `card_id` (default: the template's card) every `interval` (default 1)
+    /// periods, starting one interval after the template's `scheduledAt` (or
+    /// `createdAt`) and stopping after `end_at`.
+    pub fn set_recurrence(
+        &mut self,
+        todo_id: &str,
+        frequency: Frequency,
+        interval: Option<u32>,
+        end_at: Option<&str>,
+        card_id: Option<&str>,
+    ) -> Result<RecurrenceDto, AppError> {
+        let interval = interval.unwrap_or(1);
+        if interval == 0 {
+            return Err(AppError::invalid_field(
+                "interval",
+                "Recurrence interval must be at least 1",
+            ));
+        }
+        let end = match end_at {
+            Some(e) => Some(parse_timestamp(e).ok_or_else(|| {
+                AppError::invalid_field("end_at", format!("Invalid end date: {}", e))
+            })?),
+            None => None,
+        };
+        let now = now_iso();
+
+        let tx = self.conn_mut().transaction()?;
+
+        type Template = (String, Option<String>, String, Option<String>, String);
+        let (template_card, scheduled_at, created_at, template_currency, card_currency): Template =
+            tx.query_row(
+                "SELECT t.cardId, t.scheduledAt, t.createdAt, t.currency, c.currency
+                 FROM Todo t JOIN Card c ON c.id = t.cardId WHERE t.id = ?1",
+                params![todo_id],
+                |row| {
+                    Ok((
+                        row.get(0)?,
+                        row.get(1)?,
+                        row.get(2)?,
+                        row.get(3)?,
+                        row.get(4)?,
+                    ))
+                },
+            )
+            .map_err(|e| match e {
+                rusqlite::Error::QueryReturnedNoRows => AppError::TodoNotFound(todo_id.to_string()),
+                _ => e.into(),
+            })?;
+
+        let target_card = card_id.map(String::from).unwrap_or(template_card);
+        let target_currency: String = tx
+            .query_row(
+                "SELECT currency FROM Card WHERE id = ?1 AND deletedAt IS NULL",
+                params![target_card],
+                |row| row.get(0),
+            )
+            .map_err(|e| match e {
+                rusqlite::Error::QueryReturnedNoRows => AppError::CardNotFound(target_card.clone()),
+                _ => e.into(),
+            })?;
+        // Occurrences copy the template's currency column; without one the
+        // amount would be read in the target card's currency instead
+        if template_currency.is_none() && target_currency != card_currency {
+            return Err(AppError::invalid_field(
+                "card_id",
+                format!(
+                    "Occurrences would turn {} amounts into {}; give the todo its own currency first",
+                    card_currency, target_currency
+                ),
+            ));
+        }
+
+        let start = scheduled_at
+            .as_deref()
+            .and_then(parse_timestamp)
+            .or_else(|| parse_timestamp(&created_at))
+            .unwrap_or_else(Utc::now);
+        let next = occurrence_at(start, frequency, interval, 1).ok_or_else(|| {
+            AppError::invalid_field("interval", "Recurrence interval is too large")
+        })?;
+        if end.is_some_and(|end| end < start) {
+            return Err(AppError::invalid_field(
+                "end_at",
+                "Recurrence end date is before its start",
+            ));
+        }
+
+        tx.execute(
+            "INSERT INTO Recurrence (todoId, cardId, frequency, interval, startAt, endAt, occurrences, nextAt, createdAt, updatedAt)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, 0, ?7, ?8, ?8)
+             ON CONFLICT(todoId) DO UPDATE SET
+                cardId = excluded.cardId, frequency = excluded.frequency, interval = excluded.interval,
+                startAt = excluded.startAt, endAt = excluded.endAt, nextAt = excluded.nextAt,
+                occurrences = 0, updatedAt = excluded.updatedAt",
+            params![
+                todo_id,
+                target_card,
+                frequency.as_str(),
+                interval,
+                format_timestamp(start),
+                end.map(format_timestamp),
+                format_timestamp(next),
+                now
+            ],
+        )?;
+
+        tx.commit()?;
+
+        let recurrence = self.conn().query_row(
+            &format!("{} WHERE r.todoId = ?1", RECURRENCE_SELECT),
+            params![todo_id],
+            map_recurrence_row,
+        )?;
+        Ok(recurrence)
+    }
+
+    pub fn clear_recurrence(&mut self, todo_id: &str) -> Result<(), AppError> {
+        let deleted = self
+            .conn()
+            .execute("DELETE FROM Recurrence WHERE todoId = ?1", params![todo_id])?;
+        if deleted == 0 {
+            return Err(AppError::RecurrenceNotFound(todo_id.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Every series, next due first.
+    pub fn list_recurrences(&self) -> Result<Vec<RecurrenceDto>, AppError> {
+        let mut stmt = self
+            .conn()
+            .prepare(&format!("{} ORDER BY r.nextAt ASC", RECURRENCE_SELECT))?;
+        let recurrences = stmt
+            .query_map([], map_recurrence_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(recurrences)
+    }
+
+    /// Creates todos for all occurrences due at `now`; see `materialize_due`.
+    pub fn run_recurrences(&mut self, now: DateTime<Utc>) -> Result<u32, AppError> {
+        materialize_due(self.conn_mut(), now)
+    }
+}
+
+struct DueRule {
+    todo_id: String,
+    card_id: String,
+    frequency: Frequency,
+    interval: u32,
+    start: DateTime<Utc>,
+    end: Option<DateTime<Utc>>,
+    occurrences: u32,
+}
+
+/// Create todos for every rule occurrence due at or before `now`.
+/// Returns the number of todos created.
+pub fn materialize_due(conn: &mut Connection, now: DateTime<Utc>) -> Result<u32, AppError> {
+    let now_str = format_timestamp(now);
+    let tx = conn.transaction()?;
+
+    let rules: Vec<DueRule> = {
+        let mut stmt = tx.prepare(
+            "SELECT r.todoId, r.cardId, r.frequency, r.interval, r.startAt, r.endAt, r.occurrences
+             FROM Recurrence r
+             JOIN Card c ON c.id = r.cardId
+             WHERE r.nextAt <= ?1 AND c.deletedAt IS NULL",
+        )?;
+        let rows = stmt.query_map(params![now_str], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, u32>(3)?,
+                row.get::<_, String>(4)?,
+                row.get::<_, Option<String>>(5)?,
+                row.get::<_, u32>(6)?,
+            ))
+        })?;
+
+        let mut rules = Vec::new();
+        for row in rows {
+            let (todo_id, card_id, frequency, interval, start, end, occurrences) = row?;
+            let start = match parse_timestamp(&start) {
+                Some(start) => start,
+                None => {
+                    log::warn!("Skipping recurrence {} with bad start {}", todo_id, start);
+                    continue;
+                }
+            };
+            rules.push(DueRule {
+                todo_id,
+                card_id,
+                frequency: Frequency::parse(&frequency)?,
+                interval,
+                start,
+                end: end.as_deref().and_then(parse_timestamp),
+                occurrences,
+            });
+        }
+        rules
+    };
+
+    let mut created = 0;
+    for rule in rules {
+        let mut n = rule.occurrences;
+        let mut generated = 0;
+
+        while generated < MAX_CATCH_UP {
+            let Some(at) = occurrence_at(rule.start, rule.frequency, rule.interval, n + 1) else {
+                break;
+            };
+            if at > now || rule.end.is_some_and(|end| at > end) {
+                break;
+            }
+            if insert_occurrence(&tx, &rule, &format_timestamp(at), &now_str)? {
+                created += 1;
+            }
+            n += 1;
+            generated += 1;
+        }
+
+        let next = occurrence_at(rule.start, rule.frequency, rule.interval, n + 1)
+            .filter(|at| rule.end.map_or(true, |end| *at <= end))
+            .map(format_timestamp);
+
+        match next {
+            Some(next) => tx.execute(
+                "UPDATE Recurrence SET occurrences = ?1, nextAt = ?2, updatedAt = ?3 WHERE todoId = ?4",
+                params![n, next, now_str, rule.todo_id],
+            )?,
+            // Past the end date: the rule is finished
+            None => tx.execute(
+                "DELETE FROM Recurrence WHERE todoId = ?1",
+                params![rule.todo_id],
+            )?,
+        };
+    }
+
+    tx.commit()?;
+    Ok(created)
+}
+
+fn insert_occurrence(
+    conn: &Connection,
+    rule: &DueRule,
+    scheduled_at: &str,
+    now: &str,
+) -> Result<bool, AppError> {
+    let todo_id = uuid::Uuid::new_v4().to_string();
+
+    let inserted = conn.execute(
+        "INSERT OR IGNORE INTO Todo (id, cardId, title, amount, currency, done, createdAt, scheduledAt, orderIndex, updatedAt, recurrenceOf)
+         SELECT ?1, ?2, t.title, t.amount, t.currency, 0, ?3, ?4,
+                (SELECT COALESCE(MAX(orderIndex), 0) + 1 FROM Todo WHERE cardId = ?2), ?3, t.id
+         FROM Todo t WHERE t.id = ?5",
+        params![todo_id, rule.card_id, now, scheduled_at, rule.todo_id],
+    )?;
+    if inserted == 0 {
+        // Already generated (unique on recurrenceOf + scheduledAt)
+        return Ok(false);
+    }
+
+    conn.execute(
+        "INSERT INTO TodoTag (todoId, tagId) SELECT ?1, tagId FROM TodoTag WHERE todoId = ?2",
+        params![todo_id, rule.todo_id],
+    )?;
+    conn.execute(
+        "UPDATE Card SET updatedAt = ?1 WHERE id = ?2",
+        params![now, rule.card_id],
+    )?;
+
+    let after = history::todo_snapshot(conn, &todo_id)?;
+    let payload = serde_json::json!({
+        "todo_id": todo_id,
+        "title": after["title"],
+        "amount": after["amount"],
+        "reason": "recurrence",
+        "recurrence_of": rule.todo_id,
+        "after": after
+    });
+    history::log_change(conn, &rule.card_id, "todo_added", &payload, now)?;
+    Ok(true)
+}
@@ -0,0 +1,277 @@
+//! Spending reports: todo amounts bucketed by day, week or month.
+//!
+//! A todo falls in the period of its `scheduledAt`, or of its `createdAt` when
+//! unscheduled. Periods are whole UTC days, ISO weeks (Monday first) or
+//! calendar months, and amounts are in each todo's card currency
+//! (`TodoCardAmount`), so totals are kept apart per currency.
+
+use crate::dates::format_timestamp;
+use crate::errors::AppError;
+use crate::models::{CardSpendingDto, PeriodSpendingDto, SpendingReportDto, SpendingTotalDto};
+use crate::money::Money;
+use crate::search_query::resolve_date;
+use chrono::{DateTime, Duration, Months, Utc};
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Most periods a single report may span.
+pub const MAX_PERIODS: i64 = 1000;
+
+/// Date a todo is reported under.
+const TODO_DATE_SQL: &str = "COALESCE(t.scheduledAt, t.createdAt)";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Granularity {
+    Day,
+    Week,
+    Month,
+}
+
+impl Granularity {
+    /// SQL for the first day of the period containing `timestamp`.
+    fn bucket(self, timestamp: &str) -> String {
+        match self {
+            Granularity::Day => format!("date({})", timestamp),
+            // Forward to Sunday, then back to that week's Monday
+            Granularity::Week => format!("date({}, 'weekday 0', '-6 days')", timestamp),
+            Granularity::Month => format!("date({}, 'start of month')", timestamp),
+        }
+    }
+
+    /// SQLite date modifier moving a period start to the next one.
+    fn step(self) -> &'static str {
+        match self {
+            Granularity::Day => "+1 day",
+            Granularity::Week => "+7 days",
+            Granularity::Month => "+1 month",
+        }
+    }
+
+    fn back(self) -> &'static str {
+        match self {
+            Granularity::Day => "-1 day",
+            Granularity::Week => "-7 days",
+            Granularity::Month => "-1 month",
+        }
+    }
+
+    /// Range reported when the caller gives no `from`: the last 30 days,
+    /// 12 weeks or 12 months.
+    fn default_from(self, to: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match self {
+            Granularity::Day => to.checked_sub_signed(Duration::days(30)),
+            Granularity::Week => to.checked_sub_signed(Duration::weeks(12)),
+            Granularity::Month => to.checked_sub_months(Months::new(12)),
+        }
+    }
+
+    /// Shortest possible period, for bounding the report size.
+    fn min_days(self) -> i64 {
+        match self {
+            Granularity::Day => 1,
+            Granularity::Week => 7,
+            Granularity::Month => 28,
+        }
+    }
+}
+
+/// Periods from the one containing `from` through the one containing the
+/// instant before `to`, plus the period before them as the baseline for the
+/// first period's change, followed by per-card sums for each period.
+///
+/// Parameters: `?1` the start timestamp, `?2` the last instant covered,
+/// `?3` whether archived cards count.
+fn with_sql(granularity: Granularity) -> String {
+    let step = granularity.step();
+    let todo_period = granularity.bucket(TODO_DATE_SQL);
+    format!(
+        "WITH RECURSIVE periods(start) AS (
+             SELECT date({first}, '{back}')
+             UNION ALL
+             SELECT date(start, '{step}') FROM periods WHERE date(start, '{step}') <= {last}
+         ),
+         card_spending AS (
+             SELECT {todo_period} AS period, t.cardId,
+                    COALESCE(SUM(CASE WHEN a.done = 1 THEN a.amount END), 0) AS spent,
+                    COALESCE(SUM(CASE WHEN a.done = 0 THEN a.amount END), 0) AS pending,
+                    COUNT(*) AS todoCount,
+                    SUM(a.unconverted) AS unconverted
+             FROM Todo t
+             JOIN TodoCardAmount a ON a.todoId = t.id
+             JOIN Card c ON c.id = t.cardId
+             WHERE t.amount IS NOT NULL
+               AND c.deletedAt IS NULL
+               AND (?3 OR c.archived = 0)
+               AND {todo_date} >= (SELECT MIN(start) FROM periods)
+               AND {todo_period} IN (SELECT start FROM periods)
+             GROUP BY period, t.cardId
+         )",
+        first = granularity.bucket("?1"),
+        last = granularity.bucket("?2"),
+        back = granularity.back(),
+        todo_date = TODO_DATE_SQL,
+    )
+}
+
+/// Rows of a period × `key` grid, each with the previous period's total and
+/// the change from it. Rows that are zero in both periods are left out.
+fn grid_sql(granularity: Granularity, spending: &str, key: &str) -> String {
+    format!(
+        "{with_sql},
+         spending AS ({spending}),
+         grid AS (
+             SELECT p.start, k.{key} AS groupKey,
+                    COALESCE(s.spent, 0) AS spent,
+                    COALESCE(s.pending, 0) AS pending,
+                    COALESCE(s.todoCount, 0) AS todoCount,
+                    COALESCE(s.unconverted, 0) AS unconverted,
+                    LAG(COALESCE(s.spent, 0) + COALESCE(s.pending, 0), 1, 0)
+                        OVER (PARTITION BY k.{key} ORDER BY p.start) AS previous
+             FROM periods p
+             CROSS JOIN (SELECT DISTINCT {key} FROM spending) k
+             LEFT JOIN spending s ON s.period = p.start AND s.{key} = k.{key}
+         )
+         SELECT start, groupKey, spent, pending, spent + pending, todoCount, unconverted, previous,
+                spent + pending - previous,
+                CASE WHEN previous != 0
+                     THEN ROUND((spent + pending - previous) * 100.0 / previous, 1)
+                END
+         FROM grid
+         WHERE start > (SELECT MIN(start) FROM periods) AND (todoCount > 0 OR previous != 0)
+         ORDER BY start, spent + pending DESC, groupKey",
+        with_sql = with_sql(granularity),
+    )
+}
+
+/// Spending per period between `from` (inclusive) and `to` (exclusive, default
+/// now), widened to whole periods. `from`/`to` take the same forms as the
+/// search `after:`/`before:` filters. Trashed cards never count; archived ones
+/// only with `include_archived`.
+pub fn spending_by_period(
+    conn: &Connection,
+    granularity: Granularity,
+    from: Option<&str>,
+    to: Option<&str>,
+    include_archived: bool,
+    now: DateTime<Utc>,
+) -> Result<SpendingReportDto, AppError> {
+    let resolve = |field: &str, value: &str| {
+        resolve_date(value, now).ok_or_else(|| {
+            AppError::invalid_field(field, format!("Invalid report date: {}", value))
+        })
+    };
+    let to = to.map(|to| resolve("to", to)).transpose()?.unwrap_or(now);
+    let from = match from {
+        Some(from) => resolve("from", from)?,
+        None => granularity
+            .default_from(to)
+            .ok_or_else(|| AppError::invalid_field("from", "Report range is out of bounds"))?,
+    };
+    if from >= to {
+        return Err(AppError::invalid_field(
+            "from",
+            "Report start must be before its end",
+        ));
+    }
+    if (to - from).num_days() / granularity.min_days() >= MAX_PERIODS {
+        return Err(AppError::invalid_field(
+            "from",
+            format!("Report spans more than {} periods", MAX_PERIODS),
+        ));
+    }
+
+    let first = format_timestamp(from);
+    let last = format_timestamp(to - Duration::milliseconds(1));
+    let query_params = params![first, last, include_archived];
+
+    let mut periods: BTreeMap<String, PeriodSpendingDto> = BTreeMap::new();
+    let mut stmt = conn.prepare(&format!(
+        "{}
+         SELECT start, date(start, '{}') FROM periods
+         WHERE start > (SELECT MIN(start) FROM periods)",
+        with_sql(granularity),
+        granularity.step()
+    ))?;
+    let mut rows = stmt.query(query_params)?;
+    while let Some(row) = rows.next()? {
+        let start: String = row.get(0)?;
+        periods.insert(
+            start.clone(),
+            PeriodSpendingDto {
+                start,
+                end: row.get(1)?,
+                totals: Vec::new(),
+                cards: Vec::new(),
+            },
+        );
+    }
+
+    let mut stmt = conn.prepare(&grid_sql(
+        granularity,
+        "SELECT cs.period, c.currency,
+                SUM(cs.spent) AS spent, SUM(cs.pending) AS pending,
+                SUM(cs.todoCount) AS todoCount, SUM(cs.unconverted) AS unconverted
+         FROM card_spending cs JOIN Card c ON c.id = cs.cardId
+         GROUP BY cs.period, c.currency",
+        "currency",
+    ))?;
+    let mut rows = stmt.query(query_params)?;
+    while let Some(row) = rows.next()? {
+        let start: String = row.get(0)?;
+        let total = SpendingTotalDto {
+            currency: row.get(1)?,
+            spent: row.get::<_, Money>(2)?.to_string(),
+            pending: row.get::<_, Money>(3)?.to_string(),
+            total: row.get::<_, Money>(4)?.to_string(),
+            todo_count: row.get(5)?,
+            unconverted_count: row.get(6)?,
+            previous_total: row.get::<_, Money>(7)?.to_string(),
+            change: row.get::<_, Money>(8)?.to_string(),
+            change_percent: row.get(9)?,
+        };
+        if let Some(period) = periods.get_mut(&start) {
+            period.totals.push(total);
+        }
+    }
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT g.*, c.title, c.currency FROM ({}) g JOIN Card c ON c.id = g.groupKey
+         ORDER BY 1, 5 DESC, c.title",
+        grid_sql(
+            granularity,
+            "SELECT period, cardId, spent, pending, todoCount, unconverted FROM card_spending",
+            "cardId",
+        )
+    ))?;
+    let mut rows = stmt.query(query_params)?;
+    while let Some(row) = rows.next()? {
+        let start: String = row.get(0)?;
+        let card = CardSpendingDto {
+            card_id: row.get(1)?,
+            title: row.get(10)?,
+            currency: row.get(11)?,
+            spent: row.get::<_, Money>(2)?.to_string(),
+            pending: row.get::<_, Money>(3)?.to_string(),
+            total: row.get::<_, Money>(4)?.to_string(),
+            todo_count: row.get(5)?,
+            unconverted_count: row.get(6)?,
+            previous_total: row.get::<_, Money>(7)?.to_string(),
+            change: row.get::<_, Money>(8)?.to_string(),
+            change_percent: row.get(9)?,
+        };
+        if let Some(period) = periods.get_mut(&start) {
+            period.cards.push(card);
+        }
+    }
+
+    let periods: Vec<PeriodSpendingDto> = periods.into_values().collect();
+    Ok(SpendingReportDto {
+        granularity,
+        from: periods.first().map(|p| p.start.clone()).unwrap_or_default(),
+        to: periods.last().map(|p| p.end.clone()).unwrap_or_default(),
+        include_archived,
+        periods,
+    })
+}
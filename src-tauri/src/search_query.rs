@@ -0,0 +1,479 @@
+//! Parser for the `search` query language.
+//!
+//! ```text
+//! coffee amount:>5 amount:<=20 done:false card:"Trip" scheduled:2026-10
+//! "exact phrase" -excluded taxi OR uber tag:work after:-7d before:today
+//! ```
+//!
+//! Space-separated items must all match; `OR` joins the items on either side
+//! of it and `-` negates a single item. Bare words match as prefixes through
+//! FTS5, quoted phrases match exactly, and `field:value` filters compile to
+//! parameterized SQL over the `Card` (`c`) and `Todo` (`td`) rows joined to
+//! each `search_index` row (`si`).
+
+use crate::dates::{format_timestamp, parse_timestamp};
+use crate::errors::AppError;
+use crate::money::Money;
+use chrono::{DateTime, Months, NaiveDate, TimeDelta, Utc};
+use rusqlite::ToSql;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    /// Items that must all match; each is a list of `OR` alternatives
+    pub clauses: Vec<Vec<Atom>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Atom {
+    pub negated: bool,
+    pub kind: AtomKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AtomKind {
+    /// Bare word, matched as a prefix
+    Term(String),
+    /// Quoted phrase, matched exactly
+    Phrase(String),
+    Filter(Filter),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Filter {
+    Amount(Comparison, Money),
+    Done(bool),
+    /// Case-insensitive substring of the card title
+    Card(String),
+    Tag(String),
+    /// `YYYY`, `YYYY-MM` or `YYYY-MM-DD` prefix of `scheduledAt`
+    Scheduled(String),
+    /// Rows dated at or after this timestamp (see `DATE_SQL`)
+    After(String),
+    /// Rows dated strictly before this timestamp
+    Before(String),
+}
+
+/// Keys recognized in `key:value` items. Other words containing a colon,
+/// like URLs, are searched as text.
+const FILTER_KEYS: &[&str] = &[
+    "amount",
+    "done",
+    "card",
+    "tag",
+    "scheduled",
+    "after",
+    "before",
+];
+
+/// Date compared by `after:` and `before:`: a todo's `scheduledAt`, falling
+/// back to its `createdAt`, or the card's `createdAt` for card rows.
+pub const DATE_SQL: &str =
+    "CASE WHEN si.todo_id IS NULL THEN c.createdAt ELSE COALESCE(td.scheduledAt, td.createdAt) END";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Lt,
+    Le,
+    Eq,
+    Ge,
+    Gt,
+}
+
+impl Comparison {
+    fn sql(self) -> &'static str {
+        match self {
+            Comparison::Lt => "<",
+            Comparison::Le => "<=",
+            Comparison::Eq => "=",
+            Comparison::Ge => ">=",
+            Comparison::Gt => ">",
+        }
+    }
+}
+
+/// SQL produced by `Query::compile`.
+#[derive(Default)]
+pub struct CompiledQuery {
+    /// FTS5 expression for `search_index MATCH`, when the query has text
+    /// that every result must contain
+    pub fts: Option<String>,
+    /// Conditions to AND into the `WHERE` clause
+    pub conditions: Vec<String>,
+    /// Values for the `?` placeholders in `conditions`, in order
+    pub params: Vec<Box<dyn ToSql>>,
+}
+
+impl Query {
+    pub fn is_empty(&self) -> bool {
+        self.clauses.is_empty()
+    }
+
+    pub fn compile(&self) -> CompiledQuery {
+        let mut compiled = CompiledQuery::default();
+        let mut fts = Vec::new();
+
+        for clause in &self.clauses {
+            // Positive text goes into the main MATCH so results keep FTS
+            // ranking and snippets; everything else becomes a SQL condition.
+            if clause
+                .iter()
+                .all(|atom| !atom.negated && atom.fts().is_some())
+            {
+                fts.push(any_of(clause.iter().filter_map(Atom::fts).collect()));
+                continue;
+            }
+
+            let alternatives = clause
+                .iter()
+                .map(|atom| atom.sql(&mut compiled.params))
+                .collect();
+            compiled.conditions.push(any_of(alternatives));
+        }
+
+        if !fts.is_empty() {
+            compiled.fts = Some(fts.join(" AND "));
+        }
+        compiled
+    }
+}
+
+impl Atom {
+    fn fts(&self) -> Option<String> {
+        match &self.kind {
+            AtomKind::Term(term) => Some(format!("{}*", fts_string(term))),
+            AtomKind::Phrase(phrase) => Some(fts_string(phrase)),
+            AtomKind::Filter(_) => None,
+        }
+    }
+
+    fn sql(&self, params: &mut Vec<Box<dyn ToSql>>) -> String {
+        let condition = match &self.kind {
+            AtomKind::Filter(filter) => filter.sql(params),
+            AtomKind::Term(_) | AtomKind::Phrase(_) => {
+                params.push(Box::new(self.fts().unwrap_or_default()));
+                "si.rowid IN (SELECT rowid FROM search_index WHERE search_index MATCH ?)"
+                    .to_string()
+            }
+        };
+
+        if self.negated {
+            // Todo filters are NULL on card rows; a negation should keep them
+            format!("NOT COALESCE({}, 0)", condition)
+        } else {
+            condition
+        }
+    }
+}
+
+impl Filter {
+    fn sql(&self, params: &mut Vec<Box<dyn ToSql>>) -> String {
+        match self {
+            Filter::Amount(cmp, amount) => {
+                params.push(Box::new(*amount));
+                format!("td.amount {} ?", cmp.sql())
+            }
+            Filter::Done(done) => {
+                params.push(Box::new(*done));
+                "td.done = ?".to_string()
+            }
+            Filter::Card(title) => {
+                params.push(Box::new(format!("%{}%", escape_like(title))));
+                "c.title LIKE ? ESCAPE '\\'".to_string()
+            }
+            Filter::Tag(name) => {
+                params.push(Box::new(name.clone()));
+                "EXISTS (SELECT 1 FROM TodoTag tt JOIN Tag t ON t.id = tt.tagId
+                         WHERE tt.todoId = si.todo_id AND t.name = ?)"
+                    .to_string()
+            }
+            Filter::Scheduled(prefix) => {
+                params.push(Box::new(format!("{}%", prefix)));
+                "td.scheduledAt LIKE ?".to_string()
+            }
+            Filter::After(timestamp) => {
+                params.push(Box::new(timestamp.clone()));
+                format!("{} >= ?", DATE_SQL)
+            }
+            Filter::Before(timestamp) => {
+                params.push(Box::new(timestamp.clone()));
+                format!("{} < ?", DATE_SQL)
+            }
+        }
+    }
+}
+
+/// Single condition, or the alternatives of an `OR` group in parentheses.
+fn any_of(mut alternatives: Vec<String>) -> String {
+    if alternatives.len() == 1 {
+        alternatives.remove(0)
+    } else {
+        format!("({})", alternatives.join(" OR "))
+    }
+}
+
+/// Quote `text` as an FTS5 string so operators and column filters in user
+/// input are matched literally.
+fn fts_string(text: &str) -> String {
+    format!("\"{}\"", text.replace('"', "\"\""))
+}
+
+fn escape_like(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+fn error(message: impl Into<String>, column: usize) -> AppError {
+    AppError::InvalidQuery(format!("{} at column {}", message.into(), column))
+}
+
+/// Split `input` into items and `OR` operators. Relative dates such as
+/// `after:-7d` are resolved against `now`.
+pub fn parse(input: &str, now: DateTime<Utc>) -> Result<Query, AppError> {
+    let mut query = Query {
+        clauses: Vec::new(),
+    };
+    let mut pending_or = false;
+
+    for token in tokenize(input)? {
+        if token.is_or() {
+            if pending_or || query.clauses.is_empty() {
+                return Err(error("OR needs a search term before it", token.column));
+            }
+            pending_or = true;
+            continue;
+        }
+
+        let Some(atom) = token.atom(now)? else {
+            continue;
+        };
+        match query.clauses.last_mut() {
+            Some(clause) if pending_or => clause.push(atom),
+            _ => query.clauses.push(vec![atom]),
+        }
+        pending_or = false;
+    }
+
+    if pending_or {
+        return Err(error(
+            "OR needs a search term after it",
+            input.chars().count() + 1,
+        ));
+    }
+    Ok(query)
+}
+
+#[derive(Debug)]
+struct Token {
+    /// 1-based character position, for error messages
+    column: usize,
+    negated: bool,
+    /// `card` in `card:"Trip"`
+    key: Option<String>,
+    value: String,
+    quoted: bool,
+}
+
+impl Token {
+    fn is_or(&self) -> bool {
+        !self.negated && self.key.is_none() && !self.quoted && self.value == "OR"
+    }
+
+    fn filter(&self) -> Option<(&str, &str)> {
+        self.key.as_deref().map(|key| (key, self.value.as_str()))
+    }
+
+    fn atom(&self, now: DateTime<Utc>) -> Result<Option<Atom>, AppError> {
+        let kind = match self.filter() {
+            Some((key, value)) => AtomKind::Filter(self.parse_filter(key, value, now)?),
+            None if self.quoted => {
+                if self.value.trim().is_empty() {
+                    return Ok(None);
+                }
+                AtomKind::Phrase(self.value.clone())
+            }
+            // Punctuation on its own has no FTS tokens to match
+            None if !self.value.chars().any(char::is_alphanumeric) => return Ok(None),
+            None => AtomKind::Term(self.value.clone()),
+        };
+        Ok(Some(Atom {
+            negated: self.negated,
+            kind,
+        }))
+    }
+
+    fn parse_filter(&self, key: &str, value: &str, now: DateTime<Utc>) -> Result<Filter, AppError> {
+        if value.is_empty() {
+            return Err(error(format!("{}: needs a value", key), self.column));
+        }
+
+        match key {
+            "amount" => {
+                let (cmp, number) = [
+                    ("<=", Comparison::Le),
+                    (">=", Comparison::Ge),
+                    ("<", Comparison::Lt),
+                    (">", Comparison::Gt),
+                    ("=", Comparison::Eq),
+                ]
+                .iter()
+                .find_map(|(op, cmp)| value.strip_prefix(op).map(|rest| (*cmp, rest)))
+                .unwrap_or((Comparison::Eq, value));
+                let amount = Money::parse(number)
+                    .map_err(|_| error(format!("Invalid amount \"{}\"", number), self.column))?;
+                Ok(Filter::Amount(cmp, amount))
+            }
+            "done" => match value.to_ascii_lowercase().as_str() {
+                "true" | "yes" | "1" => Ok(Filter::Done(true)),
+                "false" | "no" | "0" => Ok(Filter::Done(false)),
+                _ => Err(error(
+                    format!("done: expects true or false, got \"{}\"", value),
+                    self.column,
+                )),
+            },
+            "card" => Ok(Filter::Card(value.to_string())),
+            "tag" => Ok(Filter::Tag(value.to_string())),
+            "scheduled" => {
+                let valid = match value.len() {
+                    4 => value.bytes().all(|b| b.is_ascii_digit()),
+                    7 => NaiveDate::parse_from_str(&format!("{}-01", value), "%Y-%m-%d").is_ok(),
+                    10 => NaiveDate::parse_from_str(value, "%Y-%m-%d").is_ok(),
+                    _ => false,
+                };
+                if !valid {
+                    return Err(error(
+                        format!(
+                            "scheduled: expects YYYY, YYYY-MM or YYYY-MM-DD, got \"{}\"",
+                            value
+                        ),
+                        self.column,
+                    ));
+                }
+                Ok(Filter::Scheduled(value.to_string()))
+            }
+            "after" | "before" => {
+                let date = resolve_date(value, now).ok_or_else(|| {
+                    error(
+                        format!(
+                            "{}: expects a date like 2026-10-01, today or -7d, got \"{}\"",
+                            key, value
+                        ),
+                        self.column,
+                    )
+                })?;
+                let timestamp = format_timestamp(date);
+                Ok(if key == "after" {
+                    Filter::After(timestamp)
+                } else {
+                    Filter::Before(timestamp)
+                })
+            }
+            _ => Err(error(format!("Unknown filter \"{}:\"", key), self.column)),
+        }
+    }
+}
+
+/// Resolve an absolute date or timestamp, `today`/`yesterday`/`tomorrow`, or
+/// an offset from today such as `-7d`, `-2w`, `-3m`, `+1y`. Named and relative
+/// days start at midnight UTC.
+pub fn resolve_date(value: &str, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    let today = now.date_naive();
+    let day = match value.to_ascii_lowercase().as_str() {
+        "today" => Some(today),
+        "yesterday" => today.pred_opt(),
+        "tomorrow" => today.succ_opt(),
+        relative if relative.starts_with(['-', '+']) && relative.len() > 2 => {
+            let (number, unit) = relative.split_at(relative.len() - 1);
+            let count: i64 = number.parse().ok()?;
+            match unit {
+                "d" => today.checked_add_signed(TimeDelta::try_days(count)?),
+                "w" => today.checked_add_signed(TimeDelta::try_weeks(count)?),
+                "m" | "y" => {
+                    let months = if unit == "y" {
+                        count.checked_mul(12)?
+                    } else {
+                        count
+                    };
+                    let span = Months::new(u32::try_from(months.unsigned_abs()).ok()?);
+                    if months < 0 {
+                        today.checked_sub_months(span)
+                    } else {
+                        today.checked_add_months(span)
+                    }
+                }
+                _ => None,
+            }
+        }
+        _ => return parse_timestamp(value),
+    };
+    day.and_then(|d| d.and_hms_opt(0, 0, 0))
+        .map(|d| d.and_utc())
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, AppError> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        if chars[i].is_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        let column = i + 1;
+        let negated = chars[i] == '-' && chars.get(i + 1).is_some_and(|c| !c.is_whitespace());
+        if negated {
+            i += 1;
+        }
+
+        let mut word = String::new();
+        while i < chars.len() && !chars[i].is_whitespace() && chars[i] != '"' {
+            word.push(chars[i]);
+            i += 1;
+        }
+
+        let key = word
+            .split_once(':')
+            .map(|(key, _)| key.to_ascii_lowercase())
+            .filter(|key| FILTER_KEYS.contains(&key.as_str()));
+
+        let quote_allowed = word.is_empty() || (key.is_some() && word.ends_with(':'));
+        let (value, quoted) = if i < chars.len() && chars[i] == '"' {
+            if !quote_allowed {
+                return Err(error("Unexpected quote", i + 1));
+            }
+            let start = i;
+            i += 1;
+            let mut phrase = String::new();
+            while i < chars.len() && chars[i] != '"' {
+                phrase.push(chars[i]);
+                i += 1;
+            }
+            if i == chars.len() {
+                return Err(error("Unterminated quote", start + 1));
+            }
+            i += 1;
+            if i < chars.len() && !chars[i].is_whitespace() {
+                return Err(error("Expected a space after the closing quote", i + 1));
+            }
+            (phrase, true)
+        } else {
+            let value = match &key {
+                Some(key) => word[key.len() + 1..].to_string(),
+                None => word,
+            };
+            (value, false)
+        };
+
+        tokens.push(Token {
+            column,
+            negated,
+            key,
+            value,
+            quoted,
+        });
+    }
+
+    Ok(tokens)
+}
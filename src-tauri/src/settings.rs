@@ -0,0 +1,323 @@
+use crate::currency::{self, DEFAULT_CURRENCY};
+use crate::errors::AppError;
+use crate::snapshots::{KEEP_DAILY, KEEP_WEEKLY};
+use crate::store::{now_iso, Store, MAX_AGE_DAYS, TRASH_RETENTION_DAYS};
+use chrono::format::{Item, StrftimeItems};
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+
+/// Event emitted with the new `Settings` whenever they are saved.
+pub const SETTINGS_CHANGED_EVENT: &str = "settings-changed";
+
+/// Settings key holding the `ArchivePolicy`.
+pub const ARCHIVE_POLICY_KEY: &str = "archive_policy";
+
+/// User preferences. Each field is stored as its own `Settings` row, keyed by
+/// the field name, so sections can be saved independently and fields added
+/// later fall back to their defaults.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub archive_policy: ArchivePolicy,
+    /// Currency for new cards when none is given
+    pub default_currency: String,
+    /// chrono/strftime pattern the frontend uses to display dates
+    pub date_format: String,
+    pub backup_retention: BackupRetention,
+    pub api_server: ApiServer,
+    /// Days a deleted card stays in the trash before the archiver purges it
+    pub trash_retention_days: u32,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            archive_policy: ArchivePolicy::default(),
+            default_currency: DEFAULT_CURRENCY.to_string(),
+            date_format: "%Y-%m-%d".to_string(),
+            backup_retention: BackupRetention::default(),
+            api_server: ApiServer::default(),
+            trash_retention_days: TRASH_RETENTION_DAYS,
+        }
+    }
+}
+
+impl Settings {
+    /// Check every field, normalizing the currency code.
+    pub fn validate(mut self) -> Result<Settings, AppError> {
+        self.archive_policy
+            .validate()
+            .map_err(|e| e.within("archive_policy"))?;
+        self.backup_retention
+            .validate()
+            .map_err(|e| e.within("backup_retention"))?;
+        self.default_currency = currency::validate_code(&self.default_currency)?;
+        self.api_server = self
+            .api_server
+            .validate()
+            .map_err(|e| e.within("api_server"))?;
+
+        let format = self.date_format.trim();
+        if format.is_empty() || StrftimeItems::new(format).any(|item| item == Item::Error) {
+            return Err(AppError::invalid_field(
+                "date_format",
+                format!("Invalid date format: {}", self.date_format),
+            ));
+        }
+        self.date_format = format.to_string();
+
+        if !(1..=MAX_AGE_DAYS).contains(&self.trash_retention_days) {
+            return Err(AppError::invalid_field(
+                "trash_retention_days",
+                format!(
+                    "Trash retention must be between 1 and {} days",
+                    MAX_AGE_DAYS
+                ),
+            ));
+        }
+
+        Ok(self)
+    }
+}
+
+/// How many automatic snapshots the archiver keeps.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BackupRetention {
+    pub keep_daily: usize,
+    pub keep_weekly: usize,
+}
+
+impl Default for BackupRetention {
+    fn default() -> Self {
+        BackupRetention {
+            keep_daily: KEEP_DAILY,
+            keep_weekly: KEEP_WEEKLY,
+        }
+    }
+}
+
+impl BackupRetention {
+    pub fn validate(&self) -> Result<(), AppError> {
+        if self.keep_daily == 0 && self.keep_weekly == 0 {
+            return Err(AppError::invalid_field(
+                "keep_daily",
+                "At least one backup must be kept",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// The optional HTTP API served by `api`. Off by default.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ApiServer {
+    pub enabled: bool,
+    /// Interface to listen on: `127.0.0.1` keeps the API on this machine, a
+    /// LAN address or `0.0.0.0` opens it to the network
+    pub bind_address: String,
+    pub port: u16,
+    /// Bearer token every request must send
+    pub token: String,
+}
+
+impl Default for ApiServer {
+    fn default() -> Self {
+        ApiServer {
+            enabled: false,
+            bind_address: "127.0.0.1".to_string(),
+            port: 7878,
+            token: String::new(),
+        }
+    }
+}
+
+/// Shortest token accepted, so a LAN-facing API is not guarded by a PIN.
+const MIN_TOKEN_LEN: usize = 16;
+
+impl ApiServer {
+    /// Check the address and port. Enabling the API without a token generates one.
+    pub fn validate(mut self) -> Result<ApiServer, AppError> {
+        let address = self.bind_address.trim();
+        if address.parse::<IpAddr>().is_err() {
+            return Err(AppError::invalid_field(
+                "bind_address",
+                format!("Invalid bind address: {}", self.bind_address),
+            ));
+        }
+        self.bind_address = address.to_string();
+        if self.port == 0 {
+            return Err(AppError::invalid_field("port", "API port cannot be 0"));
+        }
+
+        self.token = self.token.trim().to_string();
+        if self.token.is_empty() && self.enabled {
+            self.token = uuid::Uuid::new_v4().simple().to_string();
+        }
+        if !self.token.is_empty() && self.token.len() < MIN_TOKEN_LEN {
+            return Err(AppError::invalid_field(
+                "token",
+                format!("API token must be at least {} characters", MIN_TOKEN_LEN),
+            ));
+        }
+
+        Ok(self)
+    }
+}
+
+/// Which timestamp the archive age is measured from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ArchiveBasis {
+    CreatedAt,
+    /// The card's `updatedAt` or its most recently updated todo
+    LastActivity,
+}
+
+impl ArchiveBasis {
+    /// SQL expression for the timestamp, over `Card` aliased as `c`.
+    pub fn sql(self) -> &'static str {
+        match self {
+            ArchiveBasis::CreatedAt => "c.createdAt",
+            ArchiveBasis::LastActivity => {
+                "MAX(c.updatedAt, COALESCE((SELECT MAX(updatedAt) FROM Todo WHERE cardId = c.id), c.updatedAt))"
+            }
+        }
+    }
+}
+
+/// When the archiver moves cards out of the main list.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ArchivePolicy {
+    pub enabled: bool,
+    pub age_days: u32,
+    pub based_on: ArchiveBasis,
+    pub skip_unfinished: bool,
+    pub skip_pinned: bool,
+}
+
+impl Default for ArchivePolicy {
+    fn default() -> Self {
+        ArchivePolicy {
+            enabled: true,
+            age_days: 30,
+            based_on: ArchiveBasis::LastActivity,
+            skip_unfinished: false,
+            skip_pinned: true,
+        }
+    }
+}
+
+impl ArchivePolicy {
+    pub fn validate(&self) -> Result<(), AppError> {
+        if !(1..=MAX_AGE_DAYS).contains(&self.age_days) {
+            return Err(AppError::invalid_field(
+                "age_days",
+                format!("Archive age must be between 1 and {} days", MAX_AGE_DAYS),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Store {
+    pub fn settings(&self) -> Result<Settings, AppError> {
+        load(self.conn())
+    }
+
+    /// Validates and saves every field, returning the normalized settings.
+    pub fn save_settings(&mut self, settings: Settings) -> Result<Settings, AppError> {
+        let settings = settings.validate()?;
+        let now = now_iso();
+
+        let tx = self.conn_mut().transaction()?;
+        save(&tx, &settings, &now)?;
+        tx.commit()?;
+        Ok(settings)
+    }
+
+    /// Validates and saves only the archive policy, returning all settings.
+    pub fn save_archive_policy(&mut self, policy: &ArchivePolicy) -> Result<Settings, AppError> {
+        policy.validate()?;
+        save_value(self.conn(), ARCHIVE_POLICY_KEY, policy, &now_iso())?;
+        load(self.conn())
+    }
+}
+
+/// Read all settings. Missing keys take their defaults; a value that no
+/// longer parses is logged and replaced by its default rather than failing.
+pub fn load(conn: &Connection) -> Result<Settings, AppError> {
+    let mut stmt = conn.prepare("SELECT key, value FROM Settings")?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut merged = to_map(&Settings::default())?;
+    for (key, value) in rows {
+        let Some(default) = merged.get(&key).cloned() else {
+            continue;
+        };
+        let parsed = serde_json::from_str::<serde_json::Value>(&value)
+            .map_err(|e| e.to_string())
+            .and_then(|value| {
+                let mut candidate = merged.clone();
+                candidate.insert(key.clone(), value.clone());
+                serde_json::from_value::<Settings>(serde_json::Value::Object(candidate))
+                    .map(|_| value)
+                    .map_err(|e| e.to_string())
+            });
+        match parsed {
+            Ok(value) => merged.insert(key, value),
+            Err(e) => {
+                log::warn!("Ignoring unreadable setting {}: {}", key, e);
+                merged.insert(key, default)
+            }
+        };
+    }
+
+    serde_json::from_value(serde_json::Value::Object(merged))
+        .map_err(|e| AppError::Internal(format!("Failed to decode settings: {}", e)))
+}
+
+/// Write every field of `settings`.
+pub fn save(conn: &Connection, settings: &Settings, now: &str) -> Result<(), AppError> {
+    for (key, value) in to_map(settings)? {
+        save_value(conn, &key, &value, now)?;
+    }
+    Ok(())
+}
+
+/// Write a single field, e.g. `ARCHIVE_POLICY_KEY`.
+pub fn save_value<T: Serialize>(
+    conn: &Connection,
+    key: &str,
+    value: &T,
+    now: &str,
+) -> Result<(), AppError> {
+    let value = serde_json::to_string(value)
+        .map_err(|e| AppError::Internal(format!("Failed to encode setting {}: {}", key, e)))?;
+    conn.execute(
+        "INSERT INTO Settings (key, value, updatedAt) VALUES (?1, ?2, ?3)
+         ON CONFLICT (key) DO UPDATE SET value = excluded.value, updatedAt = excluded.updatedAt",
+        params![key, value, now],
+    )?;
+    Ok(())
+}
+
+fn to_map(settings: &Settings) -> Result<serde_json::Map<String, serde_json::Value>, AppError> {
+    match serde_json::to_value(settings) {
+        Ok(serde_json::Value::Object(map)) => Ok(map),
+        Ok(_) => Err(AppError::Internal(
+            "Settings must encode as an object".into(),
+        )),
+        Err(e) => Err(AppError::Internal(format!(
+            "Failed to encode settings: {}",
+            e
+        ))),
+    }
+}
@@ -0,0 +1,207 @@
+use crate::db;
+use crate::errors::AppError;
+use crate::migrations;
+use chrono::{DateTime, Datelike, NaiveDate, NaiveDateTime, Utc};
+use rusqlite::{Connection, OpenFlags, MAIN_DB};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+/// Number of most recent days that keep their newest snapshot.
+pub const KEEP_DAILY: usize = 7;
+
+/// Number of most recent ISO weeks that keep their newest snapshot.
+pub const KEEP_WEEKLY: usize = 4;
+
+const FILE_PREFIX: &str = "tin-";
+const FILE_EXTENSION: &str = "db";
+/// Snapshot ids are their creation time, which also orders them.
+const ID_FORMAT: &str = "%Y%m%d-%H%M%S-%3f";
+
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub id: String,
+    pub path: PathBuf,
+    pub created_at: DateTime<Utc>,
+    pub size_bytes: u64,
+}
+
+/// Snapshots in `dir`, newest first. Files that are not snapshots are ignored.
+pub fn list(dir: &Path) -> Result<Vec<Snapshot>, AppError> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut snapshots = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().map_or(true, |ext| ext != FILE_EXTENSION) {
+            continue;
+        }
+        let Some(id) = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .and_then(|stem| stem.strip_prefix(FILE_PREFIX))
+        else {
+            continue;
+        };
+        let Ok(created_at) = NaiveDateTime::parse_from_str(id, ID_FORMAT) else {
+            continue;
+        };
+
+        snapshots.push(Snapshot {
+            id: id.to_string(),
+            size_bytes: std::fs::metadata(&path)?.len(),
+            path,
+            created_at: created_at.and_utc(),
+        });
+    }
+
+    snapshots.sort_by_key(|s| std::cmp::Reverse(s.created_at));
+    Ok(snapshots)
+}
+
+/// Copy the live database into `dir` with SQLite's online backup API. The
+/// copy is written under a temporary name and renamed once complete, so a
+/// crash never leaves a half-written snapshot behind.
+pub fn take(conn: &Connection, dir: &Path, now: DateTime<Utc>) -> Result<Snapshot, AppError> {
+    std::fs::create_dir_all(dir)?;
+
+    let id = now.format(ID_FORMAT).to_string();
+    let path = dir.join(format!("{}{}.{}", FILE_PREFIX, id, FILE_EXTENSION));
+    let partial = path.with_extension("partial");
+
+    conn.backup(MAIN_DB, &partial, None)?;
+    std::fs::rename(&partial, &path)?;
+
+    Ok(Snapshot {
+        id,
+        size_bytes: std::fs::metadata(&path)?.len(),
+        path,
+        created_at: now,
+    })
+}
+
+/// Take a snapshot unless one was already taken on `now`'s day.
+pub fn take_if_due(
+    conn: &Connection,
+    dir: &Path,
+    now: DateTime<Utc>,
+) -> Result<Option<Snapshot>, AppError> {
+    let taken_today = list(dir)?
+        .first()
+        .is_some_and(|latest| latest.created_at.date_naive() == now.date_naive());
+    if taken_today {
+        return Ok(None);
+    }
+    take(conn, dir, now).map(Some)
+}
+
+/// Delete snapshots that are neither the newest of one of the last
+/// `keep_daily` days nor the newest of one of the last `keep_weekly` weeks.
+/// Returns the number of files removed.
+pub fn rotate(dir: &Path, keep_daily: usize, keep_weekly: usize) -> Result<usize, AppError> {
+    let mut days: HashSet<NaiveDate> = HashSet::new();
+    let mut weeks: HashSet<(i32, u32)> = HashSet::new();
+    let mut removed = 0;
+
+    for snapshot in list(dir)? {
+        let day = snapshot.created_at.date_naive();
+        let week = (day.iso_week().year(), day.iso_week().week());
+
+        let mut keep = false;
+        if days.len() < keep_daily && days.insert(day) {
+            keep = true;
+        }
+        if weeks.len() < keep_weekly && weeks.insert(week) {
+            keep = true;
+        }
+
+        if !keep {
+            std::fs::remove_file(&snapshot.path)?;
+            removed += 1;
+        }
+    }
+
+    Ok(removed)
+}
+
+/// Replace the contents of the live database with snapshot `id`.
+///
+/// The snapshot is checked and brought up to the current schema on a copy
+/// before anything is overwritten, so a failed check or migration leaves the
+/// live database as it was. The current database is snapshotted first so the
+/// restore itself can be undone, and is put back if the copy fails partway.
+/// The caller holds the `DB` mutex, so no command sees a half-restored
+/// database.
+pub fn restore(
+    conn: &mut Connection,
+    dir: &Path,
+    id: &str,
+    now: DateTime<Utc>,
+) -> Result<Snapshot, AppError> {
+    let snapshot = list(dir)?
+        .into_iter()
+        .find(|s| s.id == id)
+        .ok_or_else(|| AppError::BackupNotFound(id.to_string()))?;
+
+    verify(&snapshot.path)?;
+    let staged = snapshot.path.with_extension("restoring");
+    let upgraded = std::fs::copy(&snapshot.path, &staged)
+        .map_err(AppError::from)
+        .and_then(|_| upgrade(&staged));
+    if let Err(e) = upgraded {
+        std::fs::remove_file(&staged).ok();
+        return Err(e);
+    }
+
+    let before = take(conn, dir, now);
+    let swapped = before.and_then(|before| {
+        let copied = conn.restore(MAIN_DB, &staged, None::<fn(rusqlite::backup::Progress)>);
+        if let Err(e) = copied {
+            log::warn!("Restoring snapshot {} failed, rolling back: {}", id, e);
+            conn.restore(
+                MAIN_DB,
+                &before.path,
+                None::<fn(rusqlite::backup::Progress)>,
+            )?;
+            return Err(e.into());
+        }
+        Ok(())
+    });
+    std::fs::remove_file(&staged).ok();
+    swapped?;
+
+    Ok(snapshot)
+}
+
+/// Older snapshots may predate recent migrations; apply them to the copy at
+/// `path`.
+fn upgrade(path: &Path) -> Result<(), AppError> {
+    let mut conn = db::open_connection(path)?;
+    migrations::run(&mut conn)?;
+    Ok(())
+}
+
+/// A snapshot is usable if SQLite can read it, finds no corruption and its
+/// schema is not newer than this build understands.
+fn verify(path: &Path) -> Result<(), AppError> {
+    let unreadable = |e: rusqlite::Error| AppError::InvalidBackup(format!("unreadable: {}", e));
+    let conn =
+        Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY).map_err(unreadable)?;
+
+    let integrity: String = conn
+        .query_row("PRAGMA integrity_check", [], |row| row.get(0))
+        .map_err(unreadable)?;
+    if integrity != "ok" {
+        return Err(AppError::InvalidBackup(format!("corrupt: {}", integrity)));
+    }
+
+    let version: u32 = conn
+        .query_row("PRAGMA user_version", [], |row| row.get(0))
+        .map_err(unreadable)?;
+    let latest = migrations::latest_version();
+    if version > latest {
+        return Err(AppError::SchemaTooNew(version, latest));
+    }
+    Ok(())
+}
@@ -0,0 +1,1285 @@
+//! `Store`: the card, todo, tag, search and archive operations over a
+//! connection it owns. Budgets, recurrences, exchange rates and settings add
+//! their operations in `impl Store` blocks in their own modules.
+//!
+//! The app keeps one `Store` behind `db::with_store` and the Tauri commands
+//! forward to it; tin-cli and the tests open their own, on a file or in
+//! memory. Operations never emit events, so callers decide what to notify.
+
+use crate::budgets;
+use crate::currency::{self, DEFAULT_CURRENCY};
+use crate::dates::format_timestamp;
+use crate::db;
+use crate::errors::AppError;
+use crate::history;
+use crate::migrations;
+use crate::models::*;
+use crate::money::Money;
+use crate::pagination::{self, CardSort, Cursor, SortOrder};
+use crate::search_query;
+use crate::settings;
+use chrono::Utc;
+use rusqlite::{params, Connection};
+use serde::Deserialize;
+use std::path::Path;
+
+/// Default for `Settings::trash_retention_days`.
+pub const TRASH_RETENTION_DAYS: u32 = 30;
+
+/// Upper bound on any age in days a caller can ask for, about a century.
+pub const MAX_AGE_DAYS: u32 = 36_500;
+
+pub(crate) fn generate_id() -> String {
+    uuid::Uuid::new_v4().to_string()
+}
+
+pub(crate) fn now_iso() -> String {
+    Utc::now().format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string()
+}
+
+/// Card columns plus balances derived from its todos, aliased as `c`.
+///
+/// Columns 8..12 are `spent` (done todos), `pending` (open todos), `remaining`
+/// (`amount - spent - pending`) and the same remainder against `lockedAmount`.
+/// Todo amounts are converted into the card currency (`TodoCardAmount`);
+/// column 14 counts todos left out for lack of an exchange rate.
+const CARD_SELECT: &str = "SELECT c.id, c.title, c.amount, c.lockedAmount, c.archived, c.createdAt, c.updatedAt, c.archivedAt,
+        c.spent, c.pending, c.amount - c.spent - c.pending, c.lockedAmount - c.spent - c.pending,
+        c.deletedAt, c.currency, c.unconverted, c.pinned
+     FROM (SELECT Card.*,
+                  (SELECT COALESCE(SUM(amount), 0) FROM TodoCardAmount WHERE cardId = Card.id AND done = 1) AS spent,
+                  (SELECT COALESCE(SUM(amount), 0) FROM TodoCardAmount WHERE cardId = Card.id AND done = 0) AS pending,
+                  (SELECT COUNT(*) FROM TodoCardAmount WHERE cardId = Card.id AND unconverted) AS unconverted
+           FROM Card) c";
+
+fn map_card_row(row: &rusqlite::Row) -> rusqlite::Result<CardDto> {
+    Ok(CardDto {
+        id: row.get(0)?,
+        title: row.get(1)?,
+        amount: row.get::<_, Money>(2)?.to_string(),
+        currency: row.get(13)?,
+        locked_amount: row.get::<_, Option<Money>>(3)?.map(|a| a.to_string()),
+        spent: row.get::<_, Money>(8)?.to_string(),
+        pending: row.get::<_, Money>(9)?.to_string(),
+        remaining: row.get::<_, Money>(10)?.to_string(),
+        locked_remaining: row.get::<_, Option<Money>>(11)?.map(|a| a.to_string()),
+        archived: row.get::<_, i32>(4)? != 0,
+        pinned: row.get::<_, i32>(15)? != 0,
+        created_at: row.get(5)?,
+        updated_at: row.get(6)?,
+        archived_at: row.get(7)?,
+        deleted_at: row.get(12)?,
+        unconverted_count: row.get(14)?,
+    })
+}
+
+pub(crate) fn fetch_todo_tags(conn: &Connection, todo_id: &str) -> Result<Vec<TagDto>, AppError> {
+    let mut stmt = conn.prepare(
+        "SELECT t.id, t.name, t.color FROM TodoTag tt JOIN Tag t ON t.id = tt.tagId
+         WHERE tt.todoId = ?1 ORDER BY t.name",
+    )?;
+    let tags = stmt
+        .query_map(params![todo_id], map_tag_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(tags)
+}
+
+fn map_tag_row(row: &rusqlite::Row) -> rusqlite::Result<TagDto> {
+    Ok(TagDto {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        color: row.get(2)?,
+    })
+}
+
+/// Trimmed `name`, unless it is empty or another tag than `tag_id` has it.
+fn validate_tag_name(conn: &Connection, name: &str, tag_id: &str) -> Result<String, AppError> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(AppError::invalid_field("name", "Tag name cannot be empty"));
+    }
+
+    let taken: bool = conn.query_row(
+        "SELECT COUNT(*) > 0 FROM Tag WHERE name = ?1 AND id != ?2",
+        params![name, tag_id],
+        |row| row.get(0),
+    )?;
+    if taken {
+        return Err(AppError::Conflict(format!("Tag already exists: {}", name)));
+    }
+
+    Ok(name.to_string())
+}
+
+/// Trashed cards only accept being restored or purged.
+fn ensure_not_trashed(card_id: &str, snapshot: &serde_json::Value) -> Result<(), AppError> {
+    if snapshot["deleted_at"].is_null() {
+        Ok(())
+    } else {
+        Err(AppError::CardNotFound(card_id.to_string()))
+    }
+}
+
+/// The card a todo belongs to, as long as that card is not in the trash.
+fn live_todo_card(conn: &Connection, todo_id: &str) -> Result<String, AppError> {
+    let (card_id, trashed): (String, bool) = conn
+        .query_row(
+            "SELECT t.cardId, c.deletedAt IS NOT NULL FROM Todo t JOIN Card c ON c.id = t.cardId WHERE t.id = ?1",
+            params![todo_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => AppError::TodoNotFound(todo_id.to_string()),
+            _ => e.into(),
+        })?;
+    if trashed {
+        return Err(AppError::CardNotFound(card_id));
+    }
+    Ok(card_id)
+}
+
+/// Timestamp `days` days before now, for age cutoffs.
+fn days_ago(days: i64, field: &str) -> Result<String, AppError> {
+    chrono::TimeDelta::try_days(days)
+        .and_then(|age| Utc::now().checked_sub_signed(age))
+        .map(format_timestamp)
+        .ok_or_else(|| AppError::invalid_field(field, format!("{} days is out of range", days)))
+}
+
+fn fetch_card(conn: &Connection, card_id: &str) -> Result<CardDto, AppError> {
+    conn.query_row(
+        &format!("{} WHERE c.id = ?1", CARD_SELECT),
+        params![card_id],
+        map_card_row,
+    )
+    .map_err(|e| match e {
+        rusqlite::Error::QueryReturnedNoRows => AppError::CardNotFound(card_id.to_string()),
+        _ => e.into(),
+    })
+}
+
+/// One keyset-paginated page of cards matching `filter` (SQL over `c`).
+fn card_page(
+    conn: &Connection,
+    filter: &str,
+    limit: Option<u32>,
+    cursor: Option<&str>,
+    sort: CardSort,
+    order: Option<SortOrder>,
+) -> Result<Page<CardDto>, AppError> {
+    let limit = pagination::page_limit(limit)?;
+    let order = order.unwrap_or_else(|| sort.default_order());
+    let tag = format!("{:?}:{:?}", sort, order).to_lowercase();
+    let key = sort.sql();
+
+    let mut conditions = filter.to_string();
+    let mut params: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+    if let Some(cursor) = cursor {
+        let cursor = Cursor::decode(cursor, &tag)?;
+        if sort.is_money() {
+            let amount = Money::parse(&cursor.key)
+                .map_err(|_| AppError::invalid_field("cursor", "Invalid page cursor"))?;
+            params.push(Box::new(amount));
+        } else {
+            params.push(Box::new(cursor.key));
+        }
+        params.push(Box::new(cursor.id));
+        conditions = format!(
+            "{} AND ({key} {op} ?1 OR ({key} = ?1 AND c.id {op} ?2))",
+            conditions,
+            key = key,
+            op = order.after()
+        );
+    }
+    params.push(Box::new(limit + 1));
+
+    let mut stmt = conn.prepare(&format!(
+        "{} WHERE {} ORDER BY {key} {dir}, c.id {dir} LIMIT ?{}",
+        CARD_SELECT,
+        conditions,
+        params.len(),
+        key = key,
+        dir = order.sql()
+    ))?;
+    let mut items = stmt
+        .query_map(
+            rusqlite::params_from_iter(params.iter().map(|p| p.as_ref())),
+            map_card_row,
+        )?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let next_cursor = if items.len() > limit as usize {
+        items.truncate(limit as usize);
+        items.last().map(|card| {
+            Cursor {
+                sort: tag.clone(),
+                key: card_sort_key(card, sort),
+                id: card.id.clone(),
+            }
+            .encode()
+        })
+    } else {
+        None
+    };
+
+    let total_count = conn.query_row(
+        &format!("SELECT COUNT(*) FROM Card c WHERE {}", filter),
+        [],
+        |row| row.get(0),
+    )?;
+
+    Ok(Page {
+        items,
+        next_cursor,
+        total_count,
+    })
+}
+
+/// The value `sort` orders `card` by, as stored in a page cursor.
+fn card_sort_key(card: &CardDto, sort: CardSort) -> String {
+    match sort {
+        CardSort::Created => card.created_at.clone(),
+        CardSort::Updated => card.updated_at.clone(),
+        CardSort::Archived => card.archived_at.clone().unwrap_or_default(),
+        CardSort::Title => card.title.clone().unwrap_or_default(),
+        CardSort::Amount => card.amount.clone(),
+        CardSort::Remaining => card.remaining.clone(),
+    }
+}
+
+/// Fields of a todo to change; `None` keeps the current value.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct TodoChanges {
+    pub title: Option<String>,
+    pub amount: Option<String>,
+    pub done: Option<bool>,
+    pub scheduled_at: Option<String>,
+    pub order_index: Option<i32>,
+    pub currency: Option<String>,
+}
+
+pub struct Store {
+    conn: Connection,
+}
+
+impl Store {
+    /// Opens the database at `path`, creating it if needed, and brings its
+    /// schema up to date.
+    pub fn open(path: &Path) -> Result<Store, AppError> {
+        Store::migrated(db::open_connection(path)?)
+    }
+
+    /// A fresh, fully migrated database that lives as long as the `Store`.
+    pub fn open_in_memory() -> Result<Store, AppError> {
+        Store::migrated(db::open_memory_connection()?)
+    }
+
+    fn migrated(mut conn: Connection) -> Result<Store, AppError> {
+        migrations::run(&mut conn)?;
+        Ok(Store { conn })
+    }
+
+    /// The underlying connection, for the operations `Store` does not cover.
+    pub fn conn(&self) -> &Connection {
+        &self.conn
+    }
+
+    pub fn conn_mut(&mut self) -> &mut Connection {
+        &mut self.conn
+    }
+
+    pub fn list_cards(
+        &self,
+        limit: Option<u32>,
+        cursor: Option<&str>,
+        sort: Option<CardSort>,
+        order: Option<SortOrder>,
+    ) -> Result<Page<CardDto>, AppError> {
+        card_page(
+            &self.conn,
+            "c.archived = 0 AND c.deletedAt IS NULL",
+            limit,
+            cursor,
+            sort.unwrap_or(CardSort::Created),
+            order,
+        )
+    }
+
+    pub fn list_archived_cards(
+        &self,
+        limit: Option<u32>,
+        cursor: Option<&str>,
+        sort: Option<CardSort>,
+        order: Option<SortOrder>,
+    ) -> Result<Page<CardDto>, AppError> {
+        card_page(
+            &self.conn,
+            "c.archived = 1 AND c.deletedAt IS NULL",
+            limit,
+            cursor,
+            sort.unwrap_or(CardSort::Archived),
+            order,
+        )
+    }
+
+    pub fn get_card(&self, card_id: &str) -> Result<CardWithTodosDto, AppError> {
+        let conn = &self.conn;
+        let card = fetch_card(conn, card_id)?;
+
+        let mut stmt = conn.prepare(
+            "SELECT id, cardId, title, amount, done, scheduledAt, orderIndex, createdAt, updatedAt, currency
+             FROM Todo WHERE cardId = ?1 ORDER BY orderIndex ASC, createdAt ASC",
+        )?;
+
+        let mut todos = stmt
+            .query_map(params![card_id], |row| {
+                Ok(TodoDto {
+                    id: row.get(0)?,
+                    card_id: row.get(1)?,
+                    title: row.get(2)?,
+                    amount: row.get::<_, Option<Money>>(3)?.map(|a| a.to_string()),
+                    currency: row.get(9)?,
+                    done: row.get::<_, i32>(4)? != 0,
+                    scheduled_at: row.get(5)?,
+                    order_index: row.get(6)?,
+                    created_at: row.get(7)?,
+                    updated_at: row.get(8)?,
+                    tags: Vec::new(),
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut stmt = conn.prepare(
+            "SELECT tt.todoId, t.id, t.name, t.color
+             FROM TodoTag tt
+             JOIN Tag t ON t.id = tt.tagId
+             JOIN Todo td ON td.id = tt.todoId
+             WHERE td.cardId = ?1
+             ORDER BY t.name",
+        )?;
+        for row in stmt.query_map(params![card_id], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                TagDto {
+                    id: row.get(1)?,
+                    name: row.get(2)?,
+                    color: row.get(3)?,
+                },
+            ))
+        })? {
+            let (todo_id, tag) = row?;
+            if let Some(todo) = todos.iter_mut().find(|t| t.id == todo_id) {
+                todo.tags.push(tag);
+            }
+        }
+
+        Ok(CardWithTodosDto {
+            id: card.id,
+            title: card.title,
+            amount: card.amount,
+            currency: card.currency,
+            locked_amount: card.locked_amount,
+            spent: card.spent,
+            pending: card.pending,
+            remaining: card.remaining,
+            locked_remaining: card.locked_remaining,
+            archived: card.archived,
+            pinned: card.pinned,
+            created_at: card.created_at,
+            updated_at: card.updated_at,
+            archived_at: card.archived_at,
+            deleted_at: card.deleted_at,
+            unconverted_count: card.unconverted_count,
+            todos,
+        })
+    }
+
+    /// `currency` defaults to the default currency setting.
+    pub fn create_card(
+        &mut self,
+        title: Option<&str>,
+        amount: &str,
+        currency: Option<&str>,
+    ) -> Result<CardDto, AppError> {
+        let id = generate_id();
+        let now = now_iso();
+        let title = title.map(str::to_string);
+
+        let currency = match currency {
+            Some(code) => currency::validate_code(code)?,
+            None => settings::load(&self.conn)?.default_currency,
+        };
+        let amount_m = currency::parse_amount(amount, &currency)?;
+
+        let tx = self.conn.transaction()?;
+
+        tx.execute(
+            "INSERT INTO Card (id, title, amount, currency, createdAt, updatedAt) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![id, title, amount_m, currency, now, now],
+        )?;
+
+        let payload = serde_json::json!({
+            "title": title,
+            "amount": amount_m.to_string(),
+            "after": history::card_snapshot(&tx, &id)?
+        });
+        history::log_change(&tx, &id, "created", &payload, &now)?;
+
+        tx.commit()?;
+
+        Ok(CardDto {
+            id: id.clone(),
+            title,
+            amount: amount_m.to_string(),
+            currency,
+            locked_amount: None,
+            spent: Money::ZERO.to_string(),
+            pending: Money::ZERO.to_string(),
+            remaining: amount_m.to_string(),
+            locked_remaining: None,
+            archived: false,
+            pinned: false,
+            created_at: now.clone(),
+            updated_at: now,
+            archived_at: None,
+            deleted_at: None,
+            unconverted_count: 0,
+        })
+    }
+
+    pub fn update_card(
+        &mut self,
+        card_id: &str,
+        title: Option<&str>,
+        amount: Option<&str>,
+        currency: Option<&str>,
+    ) -> Result<CardDto, AppError> {
+        let currency = currency.map(currency::validate_code).transpose()?;
+        let now = now_iso();
+
+        let tx = self.conn.transaction()?;
+
+        let existing: (Option<String>, Money, String) = tx
+            .query_row(
+                "SELECT title, amount, currency FROM Card WHERE id = ?1 AND deletedAt IS NULL",
+                params![card_id],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .map_err(|e| match e {
+                rusqlite::Error::QueryReturnedNoRows => AppError::CardNotFound(card_id.to_string()),
+                _ => e.into(),
+            })?;
+
+        let before = history::card_snapshot(&tx, card_id)?;
+        let new_title = title.map(str::to_string).or(existing.0);
+        // Changing the currency relabels the amount; it is not converted
+        let new_currency = currency.unwrap_or(existing.2);
+        let new_amount = match amount {
+            Some(a) => currency::parse_amount(a, &new_currency)?,
+            None => existing.1,
+        };
+
+        tx.execute(
+            "UPDATE Card SET title = ?1, amount = ?2, currency = ?3, updatedAt = ?4 WHERE id = ?5",
+            params![new_title, new_amount, new_currency, now, card_id],
+        )?;
+
+        let payload = serde_json::json!({
+            "title": new_title,
+            "amount": new_amount.to_string(),
+            "currency": new_currency,
+            "before": before,
+            "after": history::card_snapshot(&tx, card_id)?
+        });
+        history::log_change(&tx, card_id, "updated", &payload, &now)?;
+
+        tx.commit()?;
+
+        fetch_card(&self.conn, card_id)
+    }
+
+    pub fn set_locked_amount(
+        &mut self,
+        card_id: &str,
+        amount: Option<&str>,
+    ) -> Result<CardDto, AppError> {
+        let now = now_iso();
+
+        let tx = self.conn.transaction()?;
+
+        let before = history::card_snapshot(&tx, card_id)?;
+        if !before["deleted_at"].is_null() {
+            return Err(AppError::CardNotFound(card_id.to_string()));
+        }
+        let card_currency = before["currency"].as_str().unwrap_or(DEFAULT_CURRENCY);
+        let new_locked = match amount {
+            Some(a) => {
+                let locked = currency::parse_amount(a, card_currency)?;
+                if locked < Money::ZERO {
+                    return Err(AppError::InvalidAmount(a.to_string()));
+                }
+                Some(locked)
+            }
+            None => None,
+        };
+
+        tx.execute(
+            "UPDATE Card SET lockedAmount = ?1, updatedAt = ?2 WHERE id = ?3",
+            params![new_locked, now, card_id],
+        )?;
+
+        let payload = serde_json::json!({
+            "locked_amount": new_locked.map(|a| a.to_string()),
+            "previous_locked_amount": before["locked_amount"],
+            "before": before,
+            "after": history::card_snapshot(&tx, card_id)?
+        });
+        history::log_change(&tx, card_id, "locked_amount_updated", &payload, &now)?;
+
+        tx.commit()?;
+
+        fetch_card(&self.conn, card_id)
+    }
+
+    /// Moves the card (and with it, its todos) to the trash. Deleting a card
+    /// that is already there keeps its original `deletedAt`.
+    pub fn delete_card(&mut self, card_id: &str) -> Result<(), AppError> {
+        let now = now_iso();
+
+        let tx = self.conn.transaction()?;
+
+        let before = history::card_snapshot(&tx, card_id)?;
+        if !before["deleted_at"].is_null() {
+            return Ok(());
+        }
+        tx.execute(
+            "UPDATE Card SET deletedAt = ?1, updatedAt = ?2 WHERE id = ?3",
+            params![now, now, card_id],
+        )?;
+
+        let payload = serde_json::json!({
+            "title": before["title"],
+            "before": before,
+            "after": history::card_snapshot(&tx, card_id)?
+        });
+        history::log_change(&tx, card_id, "deleted", &payload, &now)?;
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn list_trashed_cards(&self) -> Result<Vec<CardDto>, AppError> {
+        let mut stmt = self.conn.prepare(&format!(
+            "{} WHERE c.deletedAt IS NOT NULL ORDER BY c.deletedAt DESC",
+            CARD_SELECT
+        ))?;
+
+        let cards = stmt
+            .query_map([], map_card_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(cards)
+    }
+
+    pub fn restore_card(&mut self, card_id: &str) -> Result<CardDto, AppError> {
+        let now = now_iso();
+
+        let tx = self.conn.transaction()?;
+
+        let before = history::card_snapshot(&tx, card_id)?;
+        if before["deleted_at"].is_null() {
+            drop(tx);
+            return fetch_card(&self.conn, card_id);
+        }
+
+        tx.execute(
+            "UPDATE Card SET deletedAt = NULL, updatedAt = ?1 WHERE id = ?2",
+            params![now, card_id],
+        )?;
+
+        let payload = serde_json::json!({
+            "title": before["title"],
+            "before": before,
+            "after": history::card_snapshot(&tx, card_id)?
+        });
+        history::log_change(&tx, card_id, "restored", &payload, &now)?;
+
+        tx.commit()?;
+
+        fetch_card(&self.conn, card_id)
+    }
+
+    /// Permanently removes cards that have been in the trash longer than
+    /// `older_than_days` (default `Settings::trash_retention_days`), along with
+    /// their todos and history.
+    pub fn purge_trash(&mut self, older_than_days: Option<i64>) -> Result<PurgeResult, AppError> {
+        let days = match older_than_days {
+            Some(days) if !(1..=MAX_AGE_DAYS as i64).contains(&days) => {
+                return Err(AppError::invalid_field(
+                    "older_than_days",
+                    format!("Must be between 1 and {} days", MAX_AGE_DAYS),
+                ));
+            }
+            Some(days) => days,
+            None => settings::load(&self.conn)?.trash_retention_days.into(),
+        };
+        let cutoff = days_ago(days, "older_than_days")?;
+
+        let purged = self.conn.execute(
+            "DELETE FROM Card WHERE deletedAt IS NOT NULL AND deletedAt <= ?1",
+            params![cutoff],
+        )?;
+
+        Ok(PurgeResult {
+            purged_count: purged as i32,
+        })
+    }
+
+    /// Adds a todo at the end of the card. `use_current_time` schedules it now
+    /// instead of at `scheduled_at`. The result carries a warning when the
+    /// todo takes a budget over its limit.
+    pub fn add_todo(
+        &mut self,
+        card_id: &str,
+        title: &str,
+        amount: Option<&str>,
+        use_current_time: bool,
+        scheduled_at: Option<&str>,
+        currency: Option<&str>,
+    ) -> Result<AddTodoResult, AppError> {
+        let todo_currency = currency.map(currency::validate_code).transpose()?;
+
+        let todo_id = generate_id();
+        let now = now_iso();
+        let actual_scheduled_at = if use_current_time {
+            Some(now.clone())
+        } else {
+            scheduled_at.map(str::to_string)
+        };
+
+        let tx = self.conn.transaction()?;
+
+        // Verify the card exists and is not in the trash (balances are derived
+        // from todos when the card is read)
+        let card_currency: String = tx
+            .query_row(
+                "SELECT currency FROM Card WHERE id = ?1 AND deletedAt IS NULL",
+                params![card_id],
+                |row| row.get(0),
+            )
+            .map_err(|e| match e {
+                rusqlite::Error::QueryReturnedNoRows => AppError::CardNotFound(card_id.to_string()),
+                _ => e.into(),
+            })?;
+        let todo_amount = amount
+            .map(|a| currency::parse_amount(a, todo_currency.as_deref().unwrap_or(&card_currency)))
+            .transpose()?;
+
+        // Update only updatedAt; amount stays the card's starting balance
+        tx.execute(
+            "UPDATE Card SET updatedAt = ?1 WHERE id = ?2",
+            params![now, card_id],
+        )?;
+
+        let max_order: i32 = tx
+            .query_row(
+                "SELECT COALESCE(MAX(orderIndex), 0) FROM Todo WHERE cardId = ?1",
+                params![card_id],
+                |row| row.get(0),
+            )
+            .unwrap_or(0);
+
+        tx.execute(
+            "INSERT INTO Todo (id, cardId, title, amount, currency, done, createdAt, scheduledAt, orderIndex, updatedAt)
+             VALUES (?1, ?2, ?3, ?4, ?5, 0, ?6, ?7, ?8, ?9)",
+            params![todo_id, card_id, title, todo_amount, todo_currency, now, actual_scheduled_at, max_order + 1, now],
+        )?;
+
+        let payload = serde_json::json!({
+            "todo_id": todo_id,
+            "title": title,
+            "amount": todo_amount.map(|a| a.to_string()),
+            "after": history::todo_snapshot(&tx, &todo_id)?
+        });
+        history::log_change(&tx, card_id, "todo_added", &payload, &now)?;
+
+        tx.commit()?;
+
+        let warning = budgets::check_todo(&self.conn, &todo_id)?;
+        let todo = TodoDto {
+            id: todo_id,
+            card_id: card_id.to_string(),
+            title: title.to_string(),
+            amount: todo_amount.map(|a| a.to_string()),
+            currency: todo_currency,
+            done: false,
+            scheduled_at: actual_scheduled_at,
+            order_index: max_order + 1,
+            created_at: now.clone(),
+            updated_at: now,
+            tags: Vec::new(),
+        };
+
+        let updated_card = fetch_card(&self.conn, card_id)?;
+
+        Ok(AddTodoResult {
+            todo,
+            updated_card,
+            warning,
+        })
+    }
+
+    /// Returns the todo with its card's recomputed balances, like `add_todo`.
+    pub fn update_todo(
+        &mut self,
+        todo_id: &str,
+        changes: TodoChanges,
+    ) -> Result<AddTodoResult, AppError> {
+        let currency = changes
+            .currency
+            .as_deref()
+            .map(currency::validate_code)
+            .transpose()?;
+        let now = now_iso();
+
+        let tx = self.conn.transaction()?;
+
+        let existing: (String, String, Option<Money>, bool, Option<String>, i32, String) = tx.query_row(
+            "SELECT cardId, title, amount, done, scheduledAt, orderIndex, createdAt FROM Todo WHERE id = ?1",
+            params![todo_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get::<_, i32>(3)? != 0, row.get(4)?, row.get(5)?, row.get(6)?)),
+        ).map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => AppError::TodoNotFound(todo_id.to_string()),
+            _ => e.into(),
+        })?;
+
+        let before = history::todo_snapshot(&tx, todo_id)?;
+        let new_title = changes.title.unwrap_or(existing.1);
+        let (todo_currency, card_currency, card_deleted): (Option<String>, String, bool) = tx
+            .query_row(
+                "SELECT t.currency, c.currency, c.deletedAt IS NOT NULL FROM Todo t JOIN Card c ON c.id = t.cardId WHERE t.id = ?1",
+                params![todo_id],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )?;
+        if card_deleted {
+            return Err(AppError::CardNotFound(existing.0));
+        }
+        let new_currency = currency.or(todo_currency);
+        let new_amount = match changes.amount {
+            Some(a) => Some(currency::parse_amount(
+                &a,
+                new_currency.as_deref().unwrap_or(&card_currency),
+            )?),
+            None => existing.2,
+        };
+        let new_done = changes.done.unwrap_or(existing.3);
+        let new_scheduled_at = changes.scheduled_at.or(existing.4);
+        let new_order_index = changes.order_index.unwrap_or(existing.5);
+
+        tx.execute(
+            "UPDATE Todo SET title = ?1, amount = ?2, currency = ?3, done = ?4, scheduledAt = ?5, orderIndex = ?6, updatedAt = ?7 WHERE id = ?8",
+            params![new_title, new_amount, new_currency, new_done as i32, new_scheduled_at, new_order_index, now, todo_id],
+        )?;
+
+        let payload = serde_json::json!({
+            "todo_id": todo_id,
+            "title": new_title,
+            "done": new_done,
+            "before": before,
+            "after": history::todo_snapshot(&tx, todo_id)?
+        });
+        history::log_change(&tx, &existing.0, "todo_updated", &payload, &now)?;
+
+        tx.commit()?;
+
+        let conn = &self.conn;
+        let tags = fetch_todo_tags(conn, todo_id)?;
+        let warning = budgets::check_todo(conn, todo_id)?;
+        let updated_card = fetch_card(conn, &existing.0)?;
+        let todo = TodoDto {
+            id: todo_id.to_string(),
+            card_id: existing.0,
+            title: new_title,
+            amount: new_amount.map(|a| a.to_string()),
+            currency: new_currency,
+            done: new_done,
+            scheduled_at: new_scheduled_at,
+            order_index: new_order_index,
+            created_at: existing.6,
+            updated_at: now,
+            tags,
+        };
+
+        Ok(AddTodoResult {
+            todo,
+            updated_card,
+            warning,
+        })
+    }
+
+    pub fn delete_todo(&mut self, todo_id: &str) -> Result<(), AppError> {
+        let now = now_iso();
+
+        let tx = self.conn.transaction()?;
+
+        let card_id = live_todo_card(&tx, todo_id)?;
+        let before = history::todo_snapshot(&tx, todo_id)?;
+        let title = before["title"].clone();
+        let recurrence = history::recurrence_snapshot(&tx, todo_id)?;
+        tx.execute("DELETE FROM Todo WHERE id = ?1", params![todo_id])?;
+
+        let payload = serde_json::json!({
+            "todo_id": todo_id,
+            "title": title,
+            "before": before,
+            "recurrence": recurrence
+        });
+        history::log_change(&tx, &card_id, "todo_deleted", &payload, &now)?;
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn list_tags(&self) -> Result<Vec<TagDto>, AppError> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT id, name, color FROM Tag ORDER BY name")?;
+        let tags = stmt
+            .query_map([], map_tag_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(tags)
+    }
+
+    pub fn create_tag(&mut self, name: &str, color: Option<&str>) -> Result<TagDto, AppError> {
+        let id = generate_id();
+        let now = now_iso();
+
+        let name = validate_tag_name(&self.conn, name, &id)?;
+        self.conn.execute(
+            "INSERT INTO Tag (id, name, color, createdAt, updatedAt) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![id, name, color, now, now],
+        )?;
+
+        Ok(TagDto {
+            id,
+            name,
+            color: color.map(String::from),
+        })
+    }
+
+    /// Renames or recolors a tag; `None` keeps the current value.
+    pub fn update_tag(
+        &mut self,
+        tag_id: &str,
+        name: Option<&str>,
+        color: Option<&str>,
+    ) -> Result<TagDto, AppError> {
+        let now = now_iso();
+
+        let existing = self
+            .conn
+            .query_row(
+                "SELECT id, name, color FROM Tag WHERE id = ?1",
+                params![tag_id],
+                map_tag_row,
+            )
+            .map_err(|e| match e {
+                rusqlite::Error::QueryReturnedNoRows => AppError::TagNotFound(tag_id.to_string()),
+                _ => e.into(),
+            })?;
+
+        let new_name = match name {
+            Some(n) => validate_tag_name(&self.conn, n, tag_id)?,
+            None => existing.name,
+        };
+        let new_color = color.map(String::from).or(existing.color);
+
+        self.conn.execute(
+            "UPDATE Tag SET name = ?1, color = ?2, updatedAt = ?3 WHERE id = ?4",
+            params![new_name, new_color, now, tag_id],
+        )?;
+
+        Ok(TagDto {
+            id: tag_id.to_string(),
+            name: new_name,
+            color: new_color,
+        })
+    }
+
+    pub fn delete_tag(&mut self, tag_id: &str) -> Result<(), AppError> {
+        let deleted = self
+            .conn
+            .execute("DELETE FROM Tag WHERE id = ?1", params![tag_id])?;
+        if deleted == 0 {
+            return Err(AppError::TagNotFound(tag_id.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Replaces the todo's tags with `tag_ids`.
+    pub fn set_todo_tags(
+        &mut self,
+        todo_id: &str,
+        tag_ids: &[String],
+    ) -> Result<TodoDto, AppError> {
+        let now = now_iso();
+
+        let tx = self.conn.transaction()?;
+
+        live_todo_card(&tx, todo_id)?;
+        let before = history::todo_snapshot(&tx, todo_id)?;
+        for tag_id in tag_ids {
+            tx.query_row("SELECT id FROM Tag WHERE id = ?1", params![tag_id], |_| {
+                Ok(())
+            })
+            .map_err(|e| match e {
+                rusqlite::Error::QueryReturnedNoRows => AppError::TagNotFound(tag_id.clone()),
+                _ => e.into(),
+            })?;
+        }
+
+        tx.execute("DELETE FROM TodoTag WHERE todoId = ?1", params![todo_id])?;
+        for tag_id in tag_ids {
+            tx.execute(
+                "INSERT OR IGNORE INTO TodoTag (todoId, tagId) VALUES (?1, ?2)",
+                params![todo_id, tag_id],
+            )?;
+        }
+        tx.execute(
+            "UPDATE Todo SET updatedAt = ?1 WHERE id = ?2",
+            params![now, todo_id],
+        )?;
+
+        let card_id = before["card_id"].as_str().unwrap_or_default().to_string();
+        let payload = serde_json::json!({
+            "todo_id": todo_id,
+            "title": before["title"],
+            "tag_ids": tag_ids,
+            "before": before,
+            "after": history::todo_snapshot(&tx, todo_id)?
+        });
+        history::log_change(&tx, &card_id, "todo_updated", &payload, &now)?;
+
+        tx.commit()?;
+
+        let todo = self.conn.query_row(
+            "SELECT id, cardId, title, amount, done, scheduledAt, orderIndex, createdAt, updatedAt, currency
+             FROM Todo WHERE id = ?1",
+            params![todo_id],
+            |row| {
+                Ok(TodoDto {
+                    id: row.get(0)?,
+                    card_id: row.get(1)?,
+                    title: row.get(2)?,
+                    amount: row.get::<_, Option<Money>>(3)?.map(|a| a.to_string()),
+                    currency: row.get(9)?,
+                    done: row.get::<_, i32>(4)? != 0,
+                    scheduled_at: row.get(5)?,
+                    order_index: row.get(6)?,
+                    created_at: row.get(7)?,
+                    updated_at: row.get(8)?,
+                    tags: Vec::new(),
+                })
+            },
+        )?;
+
+        Ok(TodoDto {
+            tags: fetch_todo_tags(&self.conn, todo_id)?,
+            ..todo
+        })
+    }
+
+    /// Sums todo amounts per tag, optionally for one card and a date range on
+    /// `scheduledAt` (falling back to `createdAt`). A todo with several tags
+    /// counts toward each of them; todos without any tag are summed into one
+    /// row with no `tag_id` or `name`. Amounts are converted into their card's
+    /// currency and totalled per card currency, so a tag used on EUR and INR
+    /// cards gets a row for each; todos with no known rate are only counted.
+    pub fn totals_by_tag(
+        &self,
+        card_id: Option<&str>,
+        from: Option<&str>,
+        to: Option<&str>,
+    ) -> Result<Vec<TagTotalDto>, AppError> {
+        let mut stmt = self.conn.prepare(
+            "SELECT t.id, t.name, c.currency, COALESCE(SUM(ta.amount), 0), COUNT(td.id),
+                    COALESCE(SUM(ta.unconverted), 0)
+             FROM Todo td
+             JOIN TodoCardAmount ta ON ta.todoId = td.id
+             JOIN Card c ON c.id = td.cardId
+             LEFT JOIN TodoTag tt ON tt.todoId = td.id
+             LEFT JOIN Tag t ON t.id = tt.tagId
+             WHERE c.deletedAt IS NULL
+               AND (?1 IS NULL OR td.cardId = ?1)
+               AND (?2 IS NULL OR COALESCE(td.scheduledAt, td.createdAt) >= ?2)
+               AND (?3 IS NULL OR COALESCE(td.scheduledAt, td.createdAt) <= ?3)
+             GROUP BY t.id, c.currency
+             ORDER BY c.currency, 4 DESC",
+        )?;
+
+        let totals = stmt
+            .query_map(params![card_id, from, to], |row| {
+                Ok(TagTotalDto {
+                    tag_id: row.get(0)?,
+                    name: row.get(1)?,
+                    currency: row.get(2)?,
+                    total: row.get::<_, Money>(3)?.to_string(),
+                    todo_count: row.get(4)?,
+                    unconverted_count: row.get(5)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(totals)
+    }
+
+    /// Full-text search with the filters described in `search_query`, best
+    /// matches first.
+    pub fn search(
+        &self,
+        query: &str,
+        limit: Option<u32>,
+        cursor: Option<&str>,
+    ) -> Result<Page<SearchResultDto>, AppError> {
+        let limit = pagination::page_limit(limit)?;
+        // Ranked results have no stable sort key, so the cursor holds an offset
+        let offset: u32 = match cursor {
+            Some(cursor) => Cursor::decode(cursor, "relevance")?
+                .key
+                .parse()
+                .map_err(|_| AppError::invalid_field("cursor", "Invalid page cursor"))?,
+            None => 0,
+        };
+
+        let query = search_query::parse(query, Utc::now())?;
+        if query.is_empty() {
+            return Ok(Page {
+                items: Vec::new(),
+                next_cursor: None,
+                total_count: 0,
+            });
+        }
+
+        let conn = &self.conn;
+        let compiled = query.compile();
+        let mut conditions = vec!["c.deletedAt IS NULL".to_string()];
+        let mut params: Vec<&dyn rusqlite::ToSql> = Vec::new();
+        if let Some(fts) = &compiled.fts {
+            conditions.push("search_index MATCH ?".to_string());
+            params.push(fts);
+        }
+        conditions.extend(compiled.conditions.iter().cloned());
+        params.extend(compiled.params.iter().map(|p| p.as_ref()));
+
+        // Filter-only queries (e.g. just `after:-7d`) list newest first
+        let (snippet, order) = if compiled.fts.is_some() {
+            (
+                "snippet(search_index, 4, '<b>', '</b>', '...', 32)".to_string(),
+                "rank".to_string(),
+            )
+        } else {
+            (
+                "COALESCE(NULLIF(si.todo_title, ''), si.card_title)".to_string(),
+                format!("{} DESC", search_query::DATE_SQL),
+            )
+        };
+
+        let from = format!(
+            "FROM search_index si
+             JOIN Card c ON c.id = si.card_id
+             LEFT JOIN Todo td ON td.id = si.todo_id
+             WHERE {}",
+            conditions.join(" AND ")
+        );
+
+        let total_count: i64 = conn.query_row(
+            &format!("SELECT COUNT(*) {}", from),
+            params.as_slice(),
+            |row| row.get(0),
+        )?;
+
+        let mut stmt = conn.prepare(&format!(
+            "SELECT si.card_id, si.todo_id, si.card_title, si.todo_title, {} AS snippet
+             {}
+             ORDER BY {} LIMIT ? OFFSET ?",
+            snippet, from, order
+        ))?;
+        params.push(&limit);
+        params.push(&offset);
+
+        let items = stmt
+            .query_map(params.as_slice(), |row| {
+                Ok(SearchResultDto {
+                    card_id: row.get(0)?,
+                    todo_id: row.get(1)?,
+                    card_title: row.get(2)?,
+                    todo_title: row.get(3)?,
+                    snippet: row.get(4)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let next = offset as i64 + items.len() as i64;
+        let next_cursor = (next < total_count).then(|| {
+            Cursor {
+                sort: "relevance".to_string(),
+                key: next.to_string(),
+                id: String::new(),
+            }
+            .encode()
+        });
+
+        Ok(Page {
+            items,
+            next_cursor,
+            total_count,
+        })
+    }
+
+    /// The ChangeLog across all cards, newest first (50 per page by default).
+    pub fn recent_changes(
+        &self,
+        limit: Option<u32>,
+        cursor: Option<&str>,
+    ) -> Result<Page<ChangeLogDto>, AppError> {
+        let limit = pagination::page_limit(Some(limit.unwrap_or(50)))?;
+
+        history::change_page(&self.conn, None, limit, cursor.map(str::to_string))
+    }
+
+    pub fn archive_card(&mut self, card_id: &str) -> Result<CardDto, AppError> {
+        let now = now_iso();
+
+        let tx = self.conn.transaction()?;
+
+        // Check card exists and capture its state for undo
+        let before = history::card_snapshot(&tx, card_id)?;
+        ensure_not_trashed(card_id, &before)?;
+
+        // Archive the card
+        tx.execute(
+            "UPDATE Card SET archived = 1, archivedAt = ?1, updatedAt = ?2 WHERE id = ?3",
+            params![now, now, card_id],
+        )?;
+
+        // Log the change
+        let payload = serde_json::json!({
+            "reason": "user_archive",
+            "before": before,
+            "after": history::card_snapshot(&tx, card_id)?
+        });
+        history::log_change(&tx, card_id, "archived", &payload, &now)?;
+
+        tx.commit()?;
+
+        // Return the updated card
+        fetch_card(&self.conn, card_id)
+    }
+
+    pub fn unarchive_card(&mut self, card_id: &str) -> Result<CardDto, AppError> {
+        let now = now_iso();
+
+        let tx = self.conn.transaction()?;
+
+        // Check card exists and capture its state for undo
+        let before = history::card_snapshot(&tx, card_id)?;
+        ensure_not_trashed(card_id, &before)?;
+
+        // Unarchive the card
+        tx.execute(
+            "UPDATE Card SET archived = 0, archivedAt = NULL, updatedAt = ?1 WHERE id = ?2",
+            params![now, card_id],
+        )?;
+
+        // Log the change
+        let payload = serde_json::json!({
+            "reason": "user_unarchive",
+            "before": before,
+            "after": history::card_snapshot(&tx, card_id)?
+        });
+        history::log_change(&tx, card_id, "unarchived", &payload, &now)?;
+
+        tx.commit()?;
+
+        // Return the updated card
+        fetch_card(&self.conn, card_id)
+    }
+
+    /// Archives cards matching the saved `ArchivePolicy`.
+    pub fn archive_old_cards(&mut self) -> Result<ArchiveResult, AppError> {
+        let now = now_iso();
+
+        let tx = self.conn.transaction()?;
+
+        let policy = settings::load(&tx)?.archive_policy;
+        if !policy.enabled {
+            return Ok(ArchiveResult { archived_count: 0 });
+        }
+        let threshold = days_ago(policy.age_days.into(), "age_days")?;
+
+        let mut stmt = tx.prepare(&format!(
+            "SELECT c.id FROM Card c
+             WHERE c.archived = 0 AND c.deletedAt IS NULL AND {} <= ?1
+               AND (?2 = 0 OR c.pinned = 0)
+               AND (?3 = 0 OR NOT EXISTS (SELECT 1 FROM Todo WHERE cardId = c.id AND done = 0))",
+            policy.based_on.sql()
+        ))?;
+
+        let card_ids: Vec<String> = stmt
+            .query_map(
+                params![threshold, policy.skip_pinned, policy.skip_unfinished],
+                |row| row.get(0),
+            )?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        drop(stmt);
+
+        for card_id in &card_ids {
+            let before = history::card_snapshot(&tx, card_id)?;
+            tx.execute(
+                "UPDATE Card SET archived = 1, archivedAt = ?1, updatedAt = ?2 WHERE id = ?3",
+                params![now, now, card_id],
+            )?;
+
+            let payload = serde_json::json!({
+                "reason": format!("auto_archive_{}_days", policy.age_days),
+                "policy": policy,
+                "before": before,
+                "after": history::card_snapshot(&tx, card_id)?
+            });
+            history::log_change(&tx, card_id, "archived", &payload, &now)?;
+        }
+
+        tx.commit()?;
+
+        Ok(ArchiveResult {
+            archived_count: card_ids.len() as i32,
+        })
+    }
+
+    /// Pins or unpins a card; pinned cards can be kept out of automatic archiving.
+    pub fn pin_card(&mut self, card_id: &str, pinned: bool) -> Result<CardDto, AppError> {
+        let now = now_iso();
+
+        let tx = self.conn.transaction()?;
+
+        let before = history::card_snapshot(&tx, card_id)?;
+        ensure_not_trashed(card_id, &before)?;
+        tx.execute(
+            "UPDATE Card SET pinned = ?1, updatedAt = ?2 WHERE id = ?3",
+            params![pinned as i32, now, card_id],
+        )?;
+
+        let payload = serde_json::json!({
+            "pinned": pinned,
+            "before": before,
+            "after": history::card_snapshot(&tx, card_id)?
+        });
+        history::log_change(&tx, card_id, "updated", &payload, &now)?;
+
+        tx.commit()?;
+
+        fetch_card(&self.conn, card_id)
+    }
+}
@@ -0,0 +1,356 @@
+//! Per-card history built from the `before`/`after` snapshots that every
+//! reversible ChangeLog entry carries (see `history`).
+
+use crate::currency::{self, DEFAULT_CURRENCY};
+use crate::dates::format_timestamp;
+use crate::errors::AppError;
+use crate::history;
+use crate::models::{
+    CardAtDto, ChangeLogDto, FieldDiffDto, HistoricalTodoDto, HistoryEntryDto, Page,
+};
+use crate::money::Money;
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// Card snapshot fields shown in diffs, with their display names.
+const CARD_FIELDS: &[(&str, &str)] = &[
+    ("title", "title"),
+    ("amount", "amount"),
+    ("locked_amount", "locked amount"),
+    ("currency", "currency"),
+    ("archived", "archived"),
+    ("pinned", "pinned"),
+    ("deleted_at", "in trash"),
+];
+
+const TODO_FIELDS: &[(&str, &str)] = &[
+    ("title", "title"),
+    ("amount", "amount"),
+    ("currency", "currency"),
+    ("done", "done"),
+    ("scheduled_at", "scheduled"),
+    ("tag_ids", "tags"),
+];
+
+fn is_todo_kind(kind: &str) -> bool {
+    kind.starts_with("todo_")
+}
+
+/// Newest-first page of `card_id`'s changes with field-level diffs.
+pub fn card_history(
+    conn: &Connection,
+    card_id: &str,
+    limit: u32,
+    cursor: Option<String>,
+) -> Result<Page<HistoryEntryDto>, AppError> {
+    // Fails with CardNotFound before returning an empty page for a bad id
+    let card = history::card_snapshot(conn, card_id)?;
+    let card_currency = card["currency"].as_str().unwrap_or(DEFAULT_CURRENCY);
+
+    let page = history::change_page(conn, Some(card_id), limit, cursor)?;
+    let items = page
+        .items
+        .into_iter()
+        .map(|change| history_entry(conn, change, card_currency))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(Page {
+        items,
+        next_cursor: page.next_cursor,
+        total_count: page.total_count,
+    })
+}
+
+fn history_entry(
+    conn: &Connection,
+    change: ChangeLogDto,
+    card_currency: &str,
+) -> Result<HistoryEntryDto, AppError> {
+    let before = change.payload.get("before").filter(|v| v.is_object());
+    let after = change.payload.get("after").filter(|v| v.is_object());
+    let todo = is_todo_kind(&change.kind);
+
+    let fields = if todo { TODO_FIELDS } else { CARD_FIELDS };
+    let mut diffs = Vec::new();
+    for (key, label) in fields {
+        let currency = after
+            .or(before)
+            .and_then(|s| s["currency"].as_str())
+            .unwrap_or(card_currency);
+        let old = before
+            .map(|s| render(conn, key, &s[*key], currency))
+            .transpose()?
+            .flatten();
+        let new = after
+            .map(|s| render(conn, key, &s[*key], currency))
+            .transpose()?
+            .flatten();
+
+        let changed = match (before, after) {
+            (Some(_), Some(_)) => old != new,
+            _ => old.is_some() || new.is_some(),
+        };
+        if changed {
+            diffs.push(FieldDiffDto {
+                field: label.to_string(),
+                old,
+                new,
+            });
+        }
+    }
+
+    let snapshot = after.or(before);
+    let todo_id = snapshot
+        .filter(|_| todo)
+        .and_then(|s| s["id"].as_str())
+        .map(str::to_string);
+    let todo_title = snapshot
+        .filter(|_| todo)
+        .and_then(|s| s["title"].as_str())
+        .unwrap_or("");
+
+    let mut summary = match change.kind.as_str() {
+        "created" => "Created card".to_string(),
+        "updated" => "Updated card".to_string(),
+        "locked_amount_updated" => "Changed locked amount".to_string(),
+        "archived" => "Archived card".to_string(),
+        "unarchived" => "Unarchived card".to_string(),
+        "deleted" => "Moved card to trash".to_string(),
+        "restored" => "Restored card from trash".to_string(),
+        "todo_added" => format!("Added todo \"{}\"", todo_title),
+        "todo_updated" => format!("Updated todo \"{}\"", todo_title),
+        "todo_deleted" => format!("Deleted todo \"{}\"", todo_title),
+        other => {
+            let text = other.replace('_', " ");
+            let mut chars = text.chars();
+            chars
+                .next()
+                .map(|first| first.to_uppercase().chain(chars).collect())
+                .unwrap_or_default()
+        }
+    };
+    if let Some(reason) = change.payload["reason"]
+        .as_str()
+        .filter(|r| !r.starts_with("user_"))
+    {
+        summary.push_str(&format!(" ({})", reason.replace('_', " ")));
+    }
+    // Additions and deletions list every field; only edits spell them out
+    if before.is_some() && after.is_some() && !diffs.is_empty() {
+        let changes: Vec<String> = diffs
+            .iter()
+            .map(|d| {
+                format!(
+                    "{} {} → {}",
+                    d.field,
+                    d.old.as_deref().unwrap_or("none"),
+                    d.new.as_deref().unwrap_or("none")
+                )
+            })
+            .collect();
+        summary.push_str(&format!(": {}", changes.join(", ")));
+    }
+
+    Ok(HistoryEntryDto {
+        id: change.id,
+        card_id: change.card_id,
+        kind: change.kind,
+        created_at: change.created_at,
+        undone: change.undone,
+        todo_id,
+        summary,
+        diffs,
+    })
+}
+
+/// Display form of one snapshot value, or `None` when it is unset.
+fn render(
+    conn: &Connection,
+    key: &str,
+    value: &Value,
+    currency: &str,
+) -> Result<Option<String>, AppError> {
+    Ok(match (key, value) {
+        (_, Value::Null) if key == "deleted_at" => Some("no".to_string()),
+        (_, Value::Null) => None,
+        ("amount" | "locked_amount", Value::String(amount)) => {
+            // Amounts that do not parse are shown as stored
+            Some(match Money::parse(amount) {
+                Ok(money) => currency::format_amount(money, currency),
+                Err(_) => amount.clone(),
+            })
+        }
+        ("deleted_at", _) => Some("yes".to_string()),
+        ("tag_ids", Value::Array(ids)) => {
+            let mut names = Vec::new();
+            for id in ids.iter().filter_map(Value::as_str) {
+                // Tags deleted since keep showing their id
+                let name = conn
+                    .query_row("SELECT name FROM Tag WHERE id = ?1", params![id], |row| {
+                        row.get::<_, String>(0)
+                    })
+                    .unwrap_or_else(|_| id.to_string());
+                names.push(name);
+            }
+            names.sort();
+            Some(if names.is_empty() {
+                "none".to_string()
+            } else {
+                names.join(", ")
+            })
+        }
+        (_, Value::Bool(flag)) => Some(if *flag { "yes" } else { "no" }.to_string()),
+        (_, Value::String(text)) => Some(text.clone()),
+        (_, other) => Some(other.to_string()),
+    })
+}
+
+/// Rebuild `card_id` and its todos as they were at `at` by walking back from
+/// the current state: later changes are reverted from their `before`
+/// snapshots, and changes that were live at `at` but undone since are
+/// re-applied from their `after` snapshots. Entries without snapshots (written
+/// before undo support) cannot be replayed and are skipped.
+pub fn card_at(conn: &Connection, card_id: &str, at: DateTime<Utc>) -> Result<CardAtDto, AppError> {
+    let as_of = format_timestamp(at);
+
+    let mut card = history::card_snapshot(conn, card_id)?;
+    let mut todos: BTreeMap<String, Value> = BTreeMap::new();
+    let todo_ids = conn
+        .prepare("SELECT id FROM Todo WHERE cardId = ?1")?
+        .query_map(params![card_id], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+    for id in todo_ids {
+        todos.insert(id.clone(), history::todo_snapshot(conn, &id)?);
+    }
+
+    let later = changes(
+        conn,
+        "cardId = ?1 AND createdAt > ?2 AND undoneAt IS NULL ORDER BY createdAt DESC, rowid DESC",
+        card_id,
+        &as_of,
+    )?;
+    for change in &later {
+        let snapshot = |field: &str| change.payload.get(field).filter(|v| v.is_object());
+        match (change.kind.as_str(), snapshot("before"), snapshot("after")) {
+            ("created", _, _) => {
+                return Err(AppError::invalid_field(
+                    "timestamp",
+                    format!("Card {} did not exist at {}", card_id, as_of),
+                ))
+            }
+            ("todo_added", _, Some(after)) => {
+                if let Some(id) = after["id"].as_str() {
+                    todos.remove(id);
+                }
+            }
+            ("todo_updated" | "todo_deleted", Some(before), _) => {
+                if let Some(id) = before["id"].as_str() {
+                    todos.insert(id.to_string(), before.clone());
+                }
+            }
+            (kind, Some(before), _) if !is_todo_kind(kind) => card = before.clone(),
+            _ => {}
+        }
+    }
+
+    let undone_since = changes(
+        conn,
+        "cardId = ?1 AND createdAt <= ?2 AND undoneAt > ?2 ORDER BY createdAt ASC, rowid ASC",
+        card_id,
+        &as_of,
+    )?;
+    for change in &undone_since {
+        let snapshot = |field: &str| change.payload.get(field).filter(|v| v.is_object());
+        match (change.kind.as_str(), snapshot("before"), snapshot("after")) {
+            ("todo_deleted", Some(before), _) => {
+                if let Some(id) = before["id"].as_str() {
+                    todos.remove(id);
+                }
+            }
+            ("todo_added" | "todo_updated", _, Some(after)) => {
+                if let Some(id) = after["id"].as_str() {
+                    todos.insert(id.to_string(), after.clone());
+                }
+            }
+            (kind, _, Some(after)) if !is_todo_kind(kind) => card = after.clone(),
+            _ => {}
+        }
+    }
+
+    let created_at = card["created_at"].as_str().unwrap_or_default().to_string();
+    if created_at.as_str() > as_of.as_str() {
+        return Err(AppError::invalid_field(
+            "timestamp",
+            format!("Card {} did not exist at {}", card_id, as_of),
+        ));
+    }
+
+    let currency = card["currency"]
+        .as_str()
+        .unwrap_or(DEFAULT_CURRENCY)
+        .to_string();
+    let amount = money(&card["amount"]).unwrap_or(Money::ZERO);
+
+    let mut todos: Vec<HistoricalTodoDto> = todos
+        .into_values()
+        .filter_map(|snapshot| serde_json::from_value(snapshot).ok())
+        .filter(|todo: &HistoricalTodoDto| todo.created_at.as_str() <= as_of.as_str())
+        .collect();
+    todos.sort_by(|a, b| (a.order_index, &a.created_at).cmp(&(b.order_index, &b.created_at)));
+
+    let mut spent = Money::ZERO;
+    let mut pending = Money::ZERO;
+    let mut unconverted_count = 0;
+    for todo in &todos {
+        let Some(todo_amount) = todo.amount.as_deref().and_then(|a| Money::parse(a).ok()) else {
+            continue;
+        };
+        if todo.currency.as_deref().is_some_and(|c| c != currency) {
+            unconverted_count += 1;
+        } else if todo.done {
+            spent = spent.checked_add(todo_amount)?;
+        } else {
+            pending = pending.checked_add(todo_amount)?;
+        }
+    }
+
+    Ok(CardAtDto {
+        id: card_id.to_string(),
+        as_of,
+        title: card["title"].as_str().map(str::to_string),
+        amount: amount.to_string(),
+        currency,
+        locked_amount: money(&card["locked_amount"]).map(|a| a.to_string()),
+        archived: card["archived"].as_bool().unwrap_or(false),
+        pinned: card["pinned"].as_bool().unwrap_or(false),
+        deleted: !card["deleted_at"].is_null(),
+        created_at,
+        spent: spent.to_string(),
+        pending: pending.to_string(),
+        remaining: amount.checked_sub(spent)?.checked_sub(pending)?.to_string(),
+        unconverted_count,
+        todos,
+    })
+}
+
+fn money(value: &Value) -> Option<Money> {
+    value.as_str().and_then(|a| Money::parse(a).ok())
+}
+
+fn changes(
+    conn: &Connection,
+    filter: &str,
+    card_id: &str,
+    as_of: &str,
+) -> Result<Vec<ChangeLogDto>, AppError> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT id, cardId, kind, payload, createdAt, undoneAt FROM ChangeLog WHERE {}",
+        filter
+    ))?;
+    let changes = stmt
+        .query_map(params![card_id, as_of], history::map_change_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(changes)
+}
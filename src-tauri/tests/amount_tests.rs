@@ -1,121 +1,225 @@
-//! Tests for amount calculations and financial operations
-mod common;
+//! Tests for amount calculations and financial operations, run through
+//! `Store` against an in-memory database
+use tin_lib::store::{Store, TodoChanges};
 
-use common::create_test_db;
-use rusqlite::params;
+fn store() -> Store {
+    Store::open_in_memory().expect("Failed to open in-memory store")
+}
 
 #[test]
-fn test_atomic_deduction_on_todo_add() {
-    let db = create_test_db();
-    let conn = db.lock().unwrap();
-
-    conn.execute(
-        "INSERT INTO Card (id, title, amount, createdAt, updatedAt) VALUES (?1, ?2, ?3, ?4, ?5)",
-        params![
-            "card-amount-1",
-            "Test",
-            100.0,
-            "2024-01-01T00:00:00.000Z",
-            "2024-01-01T00:00:00.000Z"
-        ],
-    )
-    .unwrap();
+fn test_todo_add_deducts_from_remaining() {
+    let mut store = store();
 
-    let current_amount: f64 = conn
-        .query_row(
-            "SELECT amount FROM Card WHERE id = ?1",
-            params!["card-amount-1"],
-            |row| row.get(0),
-        )
+    let card = store.create_card(Some("Test"), "100", None).unwrap();
+    let result = store
+        .add_todo(&card.id, "Groceries", Some("25.50"), true, None, None)
         .unwrap();
 
-    let todo_amount = 25.50;
-    let new_amount = current_amount - todo_amount;
+    assert_eq!(
+        result.updated_card.amount, "100.000000",
+        "Starting balance is kept"
+    );
+    assert_eq!(
+        result.updated_card.remaining, "74.500000",
+        "Remaining should be 74.50 after deduction"
+    );
+    assert_eq!(store.get_card(&card.id).unwrap().remaining, "74.500000");
+}
 
-    conn.execute(
-        "UPDATE Card SET amount = ?1 WHERE id = ?2",
-        params![new_amount, "card-amount-1"],
-    )
-    .unwrap();
+#[test]
+fn test_negative_balance_allowed() {
+    let mut store = store();
 
-    let final_amount: f64 = conn
-        .query_row(
-            "SELECT amount FROM Card WHERE id = ?1",
-            params!["card-amount-1"],
-            |row| row.get(0),
-        )
+    let card = store.create_card(Some("Test"), "50", None).unwrap();
+    let result = store
+        .add_todo(&card.id, "Too much", Some("100"), true, None, None)
         .unwrap();
 
-    assert!(
-        (final_amount - 74.5).abs() < 0.001,
-        "Amount should be 74.50 after deduction"
+    assert_eq!(
+        result.updated_card.remaining, "-50.000000",
+        "Negative balance should be allowed"
     );
 }
 
 #[test]
-fn test_negative_balance_allowed() {
-    let db = create_test_db();
-    let conn = db.lock().unwrap();
-
-    conn.execute(
-        "INSERT INTO Card (id, title, amount, createdAt, updatedAt) VALUES (?1, ?2, ?3, ?4, ?5)",
-        params![
-            "card-amount-2",
-            "Test",
-            50.0,
-            "2024-01-01T00:00:00.000Z",
-            "2024-01-01T00:00:00.000Z"
-        ],
-    )
-    .unwrap();
+fn test_precision_maintained_for_decimals() {
+    let mut store = store();
+
+    // KWD has three minor-unit decimals, more than most currencies
+    let card = store
+        .create_card(Some("Precision Test"), "123.456", Some("KWD"))
+        .unwrap();
 
-    conn.execute(
-        "UPDATE Card SET amount = amount - 100.0 WHERE id = ?1",
-        params!["card-amount-2"],
+    assert_eq!(
+        store.get_card(&card.id).unwrap().amount,
+        "123.456000",
+        "Decimal precision should be maintained"
+    );
+}
+
+#[test]
+fn test_minor_unit_sums_do_not_drift() {
+    let mut store = store();
+
+    let card = store.create_card(Some("Drift"), "0", None).unwrap();
+    for _ in 0..10 {
+        store
+            .add_todo(&card.id, "Tenth", Some("0.1"), true, None, None)
+            .unwrap();
+    }
+
+    let card = store.get_card(&card.id).unwrap();
+    assert_eq!(
+        card.pending, "1.000000",
+        "Ten 0.1 amounts should sum to exactly 1"
+    );
+    assert_eq!(card.remaining, "-1.000000");
+}
+
+#[test]
+fn test_money_migration_converts_real_columns() {
+    let conn = rusqlite::Connection::open_in_memory().unwrap();
+
+    conn.execute_batch(
+        "CREATE TABLE Card (id TEXT PRIMARY KEY NOT NULL, title TEXT, amount REAL NOT NULL DEFAULT 0, lockedAmount REAL);
+         CREATE TABLE Todo (id TEXT PRIMARY KEY NOT NULL, cardId TEXT NOT NULL, title TEXT NOT NULL, amount REAL);
+         INSERT INTO Card (id, title, amount, lockedAmount) VALUES ('c1', 'Legacy', 0.3, NULL);
+         INSERT INTO Card (id, title, amount, lockedAmount) VALUES ('c2', 'Locked', 123.456789, 50.25);
+         INSERT INTO Todo (id, cardId, title, amount) VALUES ('t1', 'c1', 'Coffee', 0.1);
+         INSERT INTO Todo (id, cardId, title, amount) VALUES ('t2', 'c1', 'Note', NULL);",
     )
     .unwrap();
 
-    let final_amount: f64 = conn
+    conn.execute_batch(include_str!("../migrations/0002_money_minor_units.sql"))
+        .unwrap();
+
+    let (amount, locked): (i64, Option<i64>) = conn
         .query_row(
-            "SELECT amount FROM Card WHERE id = ?1",
-            params!["card-amount-2"],
-            |row| row.get(0),
+            "SELECT amount, lockedAmount FROM Card WHERE id = 'c2'",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
         )
         .unwrap();
+    assert_eq!(amount, 123_456_789);
+    assert_eq!(locked, Some(50_250_000));
 
-    assert!(
-        (final_amount - (-50.0)).abs() < 0.001,
-        "Negative balance should be allowed"
+    let amount: i64 = conn
+        .query_row("SELECT amount FROM Card WHERE id = 'c1'", [], |row| {
+            row.get(0)
+        })
+        .unwrap();
+    assert_eq!(amount, 300_000, "0.3 should convert without float error");
+
+    let todo_amounts: Vec<Option<i64>> = conn
+        .prepare("SELECT amount FROM Todo ORDER BY id")
+        .unwrap()
+        .query_map([], |row| row.get(0))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(todo_amounts, vec![Some(100_000), None]);
+}
+
+#[test]
+fn test_locked_remaining_subtracts_todo_amounts() {
+    let mut store = store();
+
+    let locked = store.create_card(Some("Locked"), "500", None).unwrap();
+    let unlocked = store.create_card(Some("Unlocked"), "500", None).unwrap();
+    store.set_locked_amount(&locked.id, Some("200")).unwrap();
+    for (card, amount) in [
+        (&locked.id, Some("75.25")),
+        (&locked.id, Some("24.75")),
+        (&locked.id, None),
+        (&unlocked.id, Some("10")),
+    ] {
+        store
+            .add_todo(card, "Item", amount, true, None, None)
+            .unwrap();
+    }
+
+    let card = store.get_card(&locked.id).unwrap();
+    assert_eq!(card.locked_amount.as_deref(), Some("200.000000"));
+    assert_eq!(
+        card.locked_remaining.as_deref(),
+        Some("100.000000"),
+        "200 locked - 100 spent = 100"
+    );
+
+    let card = store.get_card(&unlocked.id).unwrap();
+    assert_eq!(
+        card.locked_remaining, None,
+        "No locked amount means no locked remaining"
     );
+
+    let card = store.set_locked_amount(&locked.id, None).unwrap();
+    assert_eq!(card.locked_remaining, None);
 }
 
 #[test]
-fn test_precision_maintained_for_decimals() {
-    let db = create_test_db();
-    let conn = db.lock().unwrap();
-
-    conn.execute(
-        "INSERT INTO Card (id, title, amount, createdAt, updatedAt) VALUES (?1, ?2, ?3, ?4, ?5)",
-        params![
-            "card-amount-3",
-            "Precision Test",
-            123.456789,
-            "2024-01-01T00:00:00.000Z",
-            "2024-01-01T00:00:00.000Z"
-        ],
-    )
-    .unwrap();
+fn test_spent_and_pending_split_by_done() {
+    let mut store = store();
 
-    let amount: f64 = conn
-        .query_row(
-            "SELECT amount FROM Card WHERE id = ?1",
-            params!["card-amount-3"],
-            |row| row.get(0),
+    let card = store.create_card(Some("Balance"), "100", None).unwrap();
+    let paid = store
+        .add_todo(&card.id, "Paid", Some("30"), true, None, None)
+        .unwrap();
+    store
+        .add_todo(&card.id, "Open", Some("12.50"), true, None, None)
+        .unwrap();
+    store
+        .add_todo(&card.id, "Note", None, true, None, None)
+        .unwrap();
+    store
+        .update_todo(
+            &paid.todo.id,
+            TodoChanges {
+                done: Some(true),
+                ..Default::default()
+            },
         )
         .unwrap();
 
-    assert!(
-        (amount - 123.456789).abs() < 0.0001,
-        "Decimal precision should be maintained"
+    let card = store.get_card(&card.id).unwrap();
+    assert_eq!(card.spent, "30.000000");
+    assert_eq!(card.pending, "12.500000");
+    assert_eq!(card.remaining, "57.500000");
+}
+
+#[test]
+fn test_todo_amounts_convert_to_card_currency() {
+    let mut store = store();
+
+    let card = store
+        .create_card(Some("Paris"), "500", Some("EUR"))
+        .unwrap();
+    store
+        .add_todo(&card.id, "Item", Some("10"), true, None, None)
+        .unwrap();
+    store
+        .add_todo(&card.id, "Item", Some("11"), true, None, Some("USD"))
+        .unwrap();
+    store
+        .add_todo(&card.id, "Item", Some("100"), true, None, Some("INR"))
+        .unwrap();
+
+    // Only the opposite direction is known for USD; INR has no rate at all
+    store
+        .conn()
+        .execute(
+            "INSERT INTO ExchangeRate (base, quote, rate) VALUES ('EUR', 'USD', 1.1)",
+            [],
+        )
+        .unwrap();
+
+    let card = store.get_card(&card.id).unwrap();
+    assert_eq!(card.pending, "20.000000", "10 EUR + 11 USD / 1.1");
+    assert_eq!(card.remaining, "480.000000");
+    assert_eq!(card.unconverted_count, 1, "INR is left out");
+
+    let invalid = store.conn().execute(
+        "INSERT INTO ExchangeRate (base, quote, rate) VALUES ('EUR', 'INR', 0)",
+        [],
     );
+    assert!(invalid.is_err(), "Rates must be positive");
 }
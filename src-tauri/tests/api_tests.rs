@@ -0,0 +1,293 @@
+//! Tests for the HTTP API's routing, run against an in-memory store
+use serde_json::{json, Value};
+use tin_lib::api::{route, ApiError, ApiRequest};
+use tin_lib::errors::AppError;
+use tin_lib::store::Store;
+use tiny_http::Method;
+
+const TOKEN: &str = "s3cret-token";
+
+fn store() -> Store {
+    Store::open_in_memory().expect("Failed to open in-memory store")
+}
+
+fn request(method: Method, url: &str, body: &str) -> ApiRequest {
+    ApiRequest {
+        method,
+        url: url.to_string(),
+        authorization: Some(format!("Bearer {}", TOKEN)),
+        body: Some(body.as_bytes().to_vec()),
+    }
+}
+
+/// Status and JSON body, whether the request succeeded or not.
+fn send(store: &mut Store, request: ApiRequest) -> (u16, Value) {
+    match route(store, TOKEN, &request) {
+        Ok(reply) => (reply.status, serde_json::from_str(&reply.body).unwrap()),
+        Err(e) => (e.status(), e.body()),
+    }
+}
+
+#[test]
+fn test_requests_need_the_bearer_token() {
+    let mut store = store();
+
+    for authorization in [
+        None,
+        Some("Bearer wrong-token!"),
+        Some("Bearer "),
+        Some("Bearer"),
+        Some(TOKEN),
+        Some("Basic s3cret-token"),
+        Some("bearer s3cret-token"),
+    ] {
+        let request = ApiRequest {
+            authorization: authorization.map(String::from),
+            ..request(Method::Get, "/api/cards", "")
+        };
+        let (status, body) = send(&mut store, request);
+        assert_eq!(status, 401, "{:?}", authorization);
+        assert_eq!(body["code"], "UNAUTHORIZED");
+    }
+
+    let request = ApiRequest {
+        authorization: Some(format!("Bearer  {} ", TOKEN)),
+        ..request(Method::Get, "/api/cards", "")
+    };
+    assert_eq!(send(&mut store, request).0, 200, "Padding is trimmed");
+}
+
+#[test]
+fn test_empty_configured_token_rejects_everything() {
+    let mut store = store();
+
+    for authorization in ["Bearer ", "Bearer", ""] {
+        let request = ApiRequest {
+            authorization: Some(authorization.to_string()),
+            ..request(Method::Get, "/api/cards", "")
+        };
+        assert!(matches!(
+            route(&mut store, "", &request),
+            Err(ApiError::Unauthorized)
+        ));
+    }
+}
+
+#[test]
+fn test_unknown_paths_and_methods() {
+    let mut store = store();
+
+    for url in ["/", "/api", "/api/nope", "/api/cards/a/b/c", "/cards"] {
+        let (status, body) = send(&mut store, request(Method::Get, url, ""));
+        assert_eq!(status, 404, "{}", url);
+        assert_eq!(body["code"], "NOT_FOUND");
+    }
+
+    for (method, url, allow) in [
+        (Method::Delete, "/api/cards", "GET, POST"),
+        (Method::Put, "/api/cards/card-1", "GET, PATCH, DELETE"),
+        (Method::Get, "/api/cards/card-1/todos", "POST"),
+        (Method::Get, "/api/todos/todo-1", "PATCH, DELETE"),
+        (Method::Post, "/api/search", "GET"),
+        (Method::Get, "/api/archive", "POST"),
+    ] {
+        let result = route(&mut store, TOKEN, &request(method.clone(), url, ""));
+        match result {
+            Err(ApiError::MethodNotAllowed(methods)) => assert_eq!(methods, allow, "{}", url),
+            other => panic!("{} {} gave {:?}", method, url, other),
+        }
+    }
+
+    let (status, body) = send(&mut store, request(Method::Delete, "/api/cards", ""));
+    assert_eq!(status, 405);
+    assert_eq!(body["code"], "METHOD_NOT_ALLOWED");
+}
+
+#[test]
+fn test_cards_and_todos_round_trip_as_json() {
+    let mut store = store();
+
+    let (status, card) = send(
+        &mut store,
+        request(
+            Method::Post,
+            "/api/cards",
+            r#"{"title": "Food", "amount": "100", "currency": "EUR"}"#,
+        ),
+    );
+    assert_eq!(status, 201);
+    assert_eq!(card["amount"], "100.000000");
+    assert_eq!(card["currency"], "EUR");
+    let id = card["id"].as_str().unwrap();
+
+    let (status, result) = send(
+        &mut store,
+        request(
+            Method::Post,
+            &format!("/api/cards/{}/todos", id),
+            r#"{"title": "Milk", "amount": "2.50"}"#,
+        ),
+    );
+    assert_eq!(status, 201);
+    assert_eq!(result["updated_card"]["remaining"], "97.500000");
+    let todo_id = result["todo"]["id"].as_str().unwrap();
+
+    let (status, result) = send(
+        &mut store,
+        request(
+            Method::Patch,
+            &format!("/api/todos/{}", todo_id),
+            r#"{"done": true}"#,
+        ),
+    );
+    assert_eq!(status, 200);
+    assert_eq!(result["updated_card"]["spent"], "2.500000");
+
+    let (status, page) = send(
+        &mut store,
+        request(Method::Get, "/api/cards?limit=10&sort=title", ""),
+    );
+    assert_eq!(status, 200);
+    assert_eq!(page["total_count"], 1);
+    assert_eq!(page["items"][0]["id"], id);
+
+    let (status, body) = send(
+        &mut store,
+        request(Method::Delete, &format!("/api/cards/{}", id), ""),
+    );
+    assert_eq!((status, body), (200, json!({ "ok": true })));
+}
+
+#[test]
+fn test_bad_bodies_and_query_values_are_rejected() {
+    let mut store = store();
+
+    for body in [
+        "",
+        "not json",
+        "[]",
+        r#"{"title": "No amount"}"#,
+        r#"{"amount": 5}"#,
+    ] {
+        let (status, error) = send(&mut store, request(Method::Post, "/api/cards", body));
+        assert_eq!(status, 400, "{:?}", body);
+        assert_eq!(error["code"], "BAD_REQUEST");
+    }
+
+    let too_large = ApiRequest {
+        body: None,
+        ..request(Method::Post, "/api/cards", "")
+    };
+    let (status, error) = send(&mut store, too_large);
+    assert_eq!(status, 413);
+    assert_eq!(error["code"], "PAYLOAD_TOO_LARGE");
+
+    for url in [
+        "/api/cards?limit=ten",
+        "/api/cards?sort=size",
+        "/api/cards?order=up",
+        "/api/search",
+    ] {
+        let (status, error) = send(&mut store, request(Method::Get, url, ""));
+        assert_eq!(status, 400, "{}", url);
+        assert_eq!(error["code"], "BAD_REQUEST");
+    }
+}
+
+#[test]
+fn test_app_errors_map_to_http_status() {
+    let mut store = store();
+
+    for (method, url, body, status, code) in [
+        (Method::Get, "/api/cards/missing", "", 404, "CARD_NOT_FOUND"),
+        (
+            Method::Delete,
+            "/api/todos/missing",
+            "",
+            404,
+            "TODO_NOT_FOUND",
+        ),
+        (
+            Method::Post,
+            "/api/cards",
+            r#"{"amount": "lots"}"#,
+            400,
+            "INVALID_AMOUNT",
+        ),
+        (
+            Method::Post,
+            "/api/cards",
+            r#"{"amount": "1", "currency": "XYZ"}"#,
+            400,
+            "INVALID_CURRENCY",
+        ),
+        (Method::Get, "/api/cards?limit=0", "", 400, "VALIDATION"),
+        (Method::Get, "/api/cards?cursor=zz", "", 400, "VALIDATION"),
+        (
+            Method::Get,
+            "/api/search?q=%22open",
+            "",
+            400,
+            "INVALID_QUERY",
+        ),
+    ] {
+        let (actual, error) = send(&mut store, request(method, url, body));
+        assert_eq!(
+            (actual, error["code"].as_str()),
+            (status, Some(code)),
+            "{}",
+            url
+        );
+    }
+
+    let (_, error) = send(&mut store, request(Method::Get, "/api/cards/missing", ""));
+    assert_eq!(error["details"], json!({ "id": "missing" }));
+
+    for (error, status) in [
+        (AppError::Conflict("duplicate".into()), 409),
+        (AppError::DbLocked, 503),
+        (AppError::DbCorrupt("bad page".into()), 500),
+        (
+            AppError::RateNotFound {
+                base: "EUR".into(),
+                quote: "USD".into(),
+            },
+            404,
+        ),
+        (AppError::RecurrenceNotFound("todo".into()), 404),
+        (AppError::BackupNotFound("backup.json".into()), 404),
+        (AppError::invalid_field("age_days", "too old"), 400),
+        (AppError::NothingToUndo, 409),
+        (AppError::Internal("oops".into()), 500),
+    ] {
+        assert_eq!(ApiError::App(error).status(), status);
+    }
+}
+
+#[test]
+fn test_path_segments_are_percent_decoded() {
+    let mut store = store();
+
+    for (url, id) in [
+        ("/api/cards/a%2Fb", "a/b"),
+        ("/api/cards/caf%C3%A9", "café"),
+        ("/api/cards/%+1", "%+1"),
+        ("/api/cards/%-1", "%-1"),
+        ("/api/cards/%zz", "%zz"),
+        ("/api/cards/100%", "100%"),
+        ("/api/cards/%4", "%4"),
+    ] {
+        let (status, error) = send(&mut store, request(Method::Get, url, ""));
+        assert_eq!(status, 404, "{}", url);
+        assert_eq!(error["details"], json!({ "id": id }), "{}", url);
+    }
+
+    let card = store.create_card(Some("Food"), "10", None).unwrap();
+    let encoded: String = card.id.bytes().map(|b| format!("%{:02X}", b)).collect();
+    let (status, body) = send(
+        &mut store,
+        request(Method::Get, &format!("/api/cards/{}", encoded), ""),
+    );
+    assert_eq!(status, 200);
+    assert_eq!(body["id"], card.id.as_str());
+}
@@ -97,3 +97,129 @@ fn test_auto_archive_threshold_30_days() {
         "Should find exactly 1 card older than 30 days"
     );
 }
+
+#[test]
+fn test_trashed_cards_excluded_and_purged_after_retention() {
+    let db = create_test_db();
+    let conn = db.lock().unwrap();
+
+    let now = Utc::now();
+    let long_ago = (now - Duration::days(45))
+        .format("%Y-%m-%dT%H:%M:%S%.3fZ")
+        .to_string();
+    let yesterday = (now - Duration::days(1))
+        .format("%Y-%m-%dT%H:%M:%S%.3fZ")
+        .to_string();
+    let cutoff = (now - Duration::days(30))
+        .format("%Y-%m-%dT%H:%M:%S%.3fZ")
+        .to_string();
+
+    for (id, deleted_at) in [
+        ("card-live", None),
+        ("card-trash-old", Some(long_ago.clone())),
+        ("card-trash-new", Some(yesterday.clone())),
+    ] {
+        conn.execute(
+            "INSERT INTO Card (id, title, amount, deletedAt, createdAt, updatedAt) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![id, "Card", 0_i64, deleted_at, long_ago, long_ago],
+        )
+        .unwrap();
+    }
+    conn.execute(
+        "INSERT INTO Todo (id, cardId, title, createdAt, updatedAt) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params!["todo-trash", "card-trash-old", "Gone", long_ago, long_ago],
+    )
+    .unwrap();
+
+    let live: i32 = conn
+        .query_row(
+            "SELECT COUNT(*) FROM Card WHERE archived = 0 AND deletedAt IS NULL",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(live, 1, "Trashed cards should not be listed");
+
+    let purged = conn
+        .execute(
+            "DELETE FROM Card WHERE deletedAt IS NOT NULL AND deletedAt <= ?1",
+            params![cutoff],
+        )
+        .unwrap();
+    assert_eq!(purged, 1, "Only cards past retention should be purged");
+
+    let todos: i32 = conn
+        .query_row("SELECT COUNT(*) FROM Todo", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(todos, 0, "Purging a card should remove its todos");
+}
+
+#[test]
+fn test_auto_archive_policy_last_activity_and_exclusions() {
+    let db = create_test_db();
+    let conn = db.lock().unwrap();
+
+    let now = Utc::now();
+    let old_date = (now - Duration::days(60))
+        .format("%Y-%m-%dT%H:%M:%S%.3fZ")
+        .to_string();
+    let recent_date = (now - Duration::days(2))
+        .format("%Y-%m-%dT%H:%M:%S%.3fZ")
+        .to_string();
+    let threshold = (now - Duration::days(30))
+        .format("%Y-%m-%dT%H:%M:%S%.3fZ")
+        .to_string();
+
+    for (id, pinned) in [
+        ("card-stale", 0),
+        ("card-active-todo", 0),
+        ("card-pinned", 1),
+        ("card-unfinished", 0),
+    ] {
+        conn.execute(
+            "INSERT INTO Card (id, title, amount, pinned, createdAt, updatedAt) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![id, "Card", 0_i64, pinned, old_date, old_date],
+        )
+        .unwrap();
+    }
+    conn.execute(
+        "INSERT INTO Todo (id, cardId, title, done, createdAt, updatedAt) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params!["todo-recent", "card-active-todo", "Touched", 1, old_date, recent_date],
+    )
+    .unwrap();
+    conn.execute(
+        "INSERT INTO Todo (id, cardId, title, done, createdAt, updatedAt) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params!["todo-open", "card-unfinished", "Open", 0, old_date, old_date],
+    )
+    .unwrap();
+
+    let candidates = |skip_pinned: bool, skip_unfinished: bool| -> Vec<String> {
+        let mut stmt = conn
+            .prepare(
+                "SELECT c.id FROM Card c
+                 WHERE c.archived = 0 AND c.deletedAt IS NULL
+                   AND MAX(c.updatedAt, COALESCE((SELECT MAX(updatedAt) FROM Todo WHERE cardId = c.id), c.updatedAt)) <= ?1
+                   AND (?2 = 0 OR c.pinned = 0)
+                   AND (?3 = 0 OR NOT EXISTS (SELECT 1 FROM Todo WHERE cardId = c.id AND done = 0))
+                 ORDER BY c.id",
+            )
+            .unwrap();
+        stmt.query_map(params![threshold, skip_pinned, skip_unfinished], |row| {
+            row.get(0)
+        })
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap()
+    };
+
+    assert_eq!(
+        candidates(false, false),
+        vec!["card-pinned", "card-stale", "card-unfinished"],
+        "Recent todo activity should keep a card out of the archive"
+    );
+    assert_eq!(
+        candidates(true, true),
+        vec!["card-stale"],
+        "Pinned cards and cards with unfinished todos should be skipped"
+    );
+}
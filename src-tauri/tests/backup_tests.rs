@@ -0,0 +1,294 @@
+//! Tests for JSON backups: validation, replace and merge restores
+use rusqlite::params;
+use std::path::PathBuf;
+use tin_lib::backup::{self, BackupDocument, RestoreMode};
+use tin_lib::errors::AppError;
+use tin_lib::store::{Store, TodoChanges};
+
+const NOW: &str = "2026-10-18T12:00:00.000Z";
+
+fn store() -> Store {
+    Store::open_in_memory().expect("Failed to open in-memory store")
+}
+
+/// An empty directory unique to this test process and `name`
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("tin-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).expect("Failed to create temp dir");
+    dir
+}
+
+/// A store with two cards, tagged todos, a budget and a rate.
+fn populated() -> Store {
+    let mut store = store();
+    let trip = store.create_card(Some("Trip"), "300", Some("EUR")).unwrap();
+    let rent = store.create_card(Some("Rent"), "900", None).unwrap();
+    let tickets = store
+        .add_todo(&trip.id, "Train tickets", Some("80"), true, None, None)
+        .unwrap();
+    store
+        .add_todo(&rent.id, "October", Some("900"), true, None, None)
+        .unwrap();
+    store
+        .update_todo(
+            &tickets.todo.id,
+            TodoChanges {
+                done: Some(true),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    let conn = store.conn();
+    conn.execute_batch(
+        "INSERT INTO Tag (id, name) VALUES ('travel', 'Travel');
+         INSERT INTO ExchangeRate (base, quote, rate) VALUES ('EUR', 'USD', 1.1);
+         INSERT INTO Settings (key, value, updatedAt) VALUES ('date_format', '\"%d.%m.%Y\"', '2026-01-01');",
+    )
+    .unwrap();
+    conn.execute(
+        "INSERT INTO TodoTag (todoId, tagId) VALUES (?1, 'travel')",
+        params![tickets.todo.id],
+    )
+    .unwrap();
+    conn.execute(
+        "INSERT INTO Budget (id, name, tagId, monthly, amount, currency, createdAt, updatedAt)
+         VALUES ('travel-budget', 'Travel', 'travel', 1, 500000000, 'EUR', ?1, ?1)",
+        params![NOW],
+    )
+    .unwrap();
+    store.delete_card(&rent.id).unwrap();
+    store
+}
+
+/// The document without the fields that depend on when it was taken.
+fn contents(store: &Store) -> serde_json::Value {
+    let mut value = serde_json::to_value(backup::snapshot(store.conn(), NOW).unwrap()).unwrap();
+    value.as_object_mut().unwrap().remove("app_version");
+    value
+}
+
+fn card_count(store: &Store) -> i64 {
+    store
+        .conn()
+        .query_row("SELECT COUNT(*) FROM Card", [], |row| row.get(0))
+        .unwrap()
+}
+
+#[test]
+fn test_export_then_replace_into_empty_database_round_trips() {
+    let dir = temp_dir("backup-round-trip");
+    let path = dir.join("backup.json");
+    let source = populated();
+
+    let exported = backup::export(source.conn(), &path, NOW).unwrap();
+    assert_eq!(exported.card_count, 2);
+    assert_eq!(exported.todo_count, 2);
+
+    let document = backup::read(&path).unwrap();
+    let mut target = store();
+    let result = backup::restore(target.conn_mut(), &document, RestoreMode::Replace).unwrap();
+    assert_eq!(result.cards_restored, 2);
+    assert_eq!(result.todos_restored, 2);
+    assert_eq!(result.changes_restored, exported.change_count);
+
+    assert_eq!(contents(&target), contents(&source));
+    // The search index is rebuilt from the restored rows, trash excluded
+    let hits = target.search("tickets", None, None).unwrap();
+    assert_eq!(hits.total_count, 1);
+    assert_eq!(target.search("october", None, None).unwrap().total_count, 0);
+    // Settings are not part of a backup
+    let date_format: Option<String> = target
+        .conn()
+        .query_row(
+            "SELECT value FROM Settings WHERE key = 'date_format'",
+            [],
+            |row| row.get(0),
+        )
+        .ok();
+    assert_eq!(date_format, None);
+
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn test_replace_drops_rows_missing_from_the_backup() {
+    let source = populated();
+    let document = backup::snapshot(source.conn(), NOW).unwrap();
+
+    let mut target = store();
+    target.create_card(Some("Local only"), "5", None).unwrap();
+    backup::restore(target.conn_mut(), &document, RestoreMode::Replace).unwrap();
+
+    assert_eq!(contents(&target), contents(&source));
+}
+
+#[test]
+fn test_merge_keeps_local_rows_and_the_newer_side_of_overlaps() {
+    let source = populated();
+    let mut document = backup::snapshot(source.conn(), NOW).unwrap();
+    let trip = document
+        .cards
+        .iter_mut()
+        .find(|c| c.title.as_deref() == Some("Trip"))
+        .unwrap();
+    let trip_id = trip.id.clone();
+
+    // The target already has the trip card, edited later than the backup
+    let mut target = store();
+    backup::restore(target.conn_mut(), &document, RestoreMode::Replace).unwrap();
+    target
+        .update_card(&trip_id, Some("Trip (edited)"), None, None)
+        .unwrap();
+    let local = target.create_card(Some("Local only"), "5", None).unwrap();
+
+    // ...and the backup has a newer version of the rent card
+    let rent = document
+        .cards
+        .iter_mut()
+        .find(|c| c.title.as_deref() == Some("Rent"))
+        .unwrap();
+    rent.title = Some("Rent (from backup)".into());
+    rent.updated_at = "2999-01-01T00:00:00.000Z".into();
+    let rent_id = rent.id.clone();
+
+    let result = backup::restore(target.conn_mut(), &document, RestoreMode::Merge).unwrap();
+    assert_eq!(result.cards_restored, 1, "Only the newer rent card");
+    assert_eq!(result.todos_restored, 0);
+    assert_eq!(result.changes_restored, 0, "Changes are matched by id");
+
+    let title = |id: &str| -> Option<String> {
+        target
+            .conn()
+            .query_row("SELECT title FROM Card WHERE id = ?1", params![id], |row| {
+                row.get(0)
+            })
+            .unwrap()
+    };
+    assert_eq!(title(&trip_id).as_deref(), Some("Trip (edited)"));
+    assert_eq!(title(&rent_id).as_deref(), Some("Rent (from backup)"));
+    assert_eq!(title(&local.id).as_deref(), Some("Local only"));
+    assert_eq!(card_count(&target), 3);
+}
+
+#[test]
+fn test_merged_changes_stay_out_of_the_undo_stack() {
+    let source = populated();
+    let document = backup::snapshot(source.conn(), NOW).unwrap();
+
+    let mut target = store();
+    let local = target.create_card(Some("Local"), "5", None).unwrap();
+    let result = backup::restore(target.conn_mut(), &document, RestoreMode::Merge).unwrap();
+    assert!(result.changes_restored > 0);
+
+    let undone = target.undo_last_change().unwrap();
+    assert_eq!(undone.kind, "created");
+    assert_eq!(undone.card_id, local.id);
+    assert!(matches!(
+        target.undo_last_change(),
+        Err(AppError::NothingToUndo)
+    ));
+}
+
+#[test]
+fn test_rows_may_reference_rows_later_in_the_document() {
+    let mut source = store();
+    let card = source.create_card(Some("Gym"), "50", None).unwrap();
+    let template = source
+        .add_todo(&card.id, "Membership", Some("30"), true, None, None)
+        .unwrap()
+        .todo;
+    let occurrence = source
+        .add_todo(&card.id, "Membership", Some("30"), false, Some(NOW), None)
+        .unwrap()
+        .todo;
+    source
+        .conn()
+        .execute(
+            "UPDATE Todo SET recurrenceOf = ?1 WHERE id = ?2",
+            params![template.id, occurrence.id],
+        )
+        .unwrap();
+
+    let mut document = backup::snapshot(source.conn(), NOW).unwrap();
+    // The occurrence now comes before its template
+    document.todos.sort_by_key(|t| t.recurrence_of.is_none());
+    assert_eq!(document.todos[0].id, occurrence.id);
+
+    let mut target = store();
+    backup::restore(target.conn_mut(), &document, RestoreMode::Replace).unwrap();
+    assert_eq!(contents(&target), contents(&source));
+    let violations: i64 = target
+        .conn()
+        .query_row("SELECT COUNT(*) FROM pragma_foreign_key_check", [], |row| {
+            row.get(0)
+        })
+        .unwrap();
+    assert_eq!(violations, 0);
+}
+
+type Breakage = fn(&mut BackupDocument);
+
+#[test]
+fn test_invalid_documents_leave_the_database_untouched() {
+    let broken: [(&str, Breakage); 8] = [
+        ("format", |d| d.format = "something-else".into()),
+        ("format version", |d| d.format_version = 99),
+        ("schema version", |d| d.schema_version = 9999),
+        ("dangling todo", |d| d.todos[0].card_id = "missing".into()),
+        ("dangling change", |d| {
+            d.changes[0].card_id = "missing".into()
+        }),
+        ("dangling tag link", |d| {
+            d.todo_tags[0].tag_id = "missing".into()
+        }),
+        ("duplicate card", |d| d.cards[1].id = d.cards[0].id.clone()),
+        ("bad currency", |d| d.cards[0].currency = "euro".into()),
+    ];
+
+    for (name, breakage) in broken {
+        let mut target = populated();
+        let before = contents(&target);
+
+        let mut document = backup::snapshot(populated().conn(), NOW).unwrap();
+        breakage(&mut document);
+        let err =
+            backup::restore(target.conn_mut(), &document, RestoreMode::Replace).expect_err(name);
+        match name {
+            "schema version" => assert!(matches!(err, AppError::SchemaTooNew(9999, _))),
+            "bad currency" => assert_eq!(err.code(), "INVALID_CURRENCY"),
+            _ => assert!(
+                matches!(err, AppError::InvalidBackup(_)),
+                "{}: {}",
+                name,
+                err
+            ),
+        }
+        assert_eq!(contents(&target), before, "{}", name);
+    }
+}
+
+#[test]
+fn test_read_rejects_files_that_are_not_backups() {
+    let dir = temp_dir("backup-read");
+    let path = dir.join("notes.json");
+    std::fs::write(&path, r#"{"hello": "world"}"#).unwrap();
+
+    assert!(matches!(
+        backup::read(&path),
+        Err(AppError::InvalidBackup(_))
+    ));
+    assert!(matches!(
+        backup::read(&dir.join("missing.json")),
+        Err(AppError::Io(_))
+    ));
+
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn test_unknown_restore_mode_names_the_field() {
+    assert_eq!(RestoreMode::parse("merge").unwrap(), RestoreMode::Merge);
+    let err = RestoreMode::parse("append").unwrap_err();
+    assert_eq!(err.details(), Some(serde_json::json!({ "field": "mode" })));
+}
@@ -0,0 +1,203 @@
+//! Tests for budgets and recurring todos, run through `Store` against an
+//! in-memory database
+use tin_lib::budgets::BudgetDefinition;
+use tin_lib::errors::AppError;
+use tin_lib::recurrence::Frequency;
+use tin_lib::store::Store;
+
+fn store() -> Store {
+    Store::open_in_memory().expect("Failed to open in-memory store")
+}
+
+fn budget(amount: &str) -> BudgetDefinition {
+    BudgetDefinition {
+        amount: amount.to_string(),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_budget_status_sums_covered_todos() {
+    let mut store = store();
+    let card = store.create_card(Some("Groceries"), "100", None).unwrap();
+    store
+        .add_todo(&card.id, "Milk", Some("30"), true, None, None)
+        .unwrap();
+    let result = store
+        .add_todo(&card.id, "Cheese", Some("25"), true, None, None)
+        .unwrap();
+    assert!(result.warning.is_none());
+
+    let created = store
+        .set_budget(
+            None,
+            BudgetDefinition {
+                card_id: Some(card.id.clone()),
+                ..budget("50")
+            },
+        )
+        .unwrap();
+    assert_eq!(created.currency, "USD");
+
+    let status = store
+        .budget_status(Some(&created.id), chrono::Utc::now())
+        .unwrap();
+    assert_eq!(status[0].used, "55.000000");
+    assert_eq!(status[0].remaining, "-5.000000");
+    assert!(status[0].exceeded);
+
+    let warning = store
+        .add_todo(&card.id, "Bread", Some("1"), true, None, None)
+        .unwrap()
+        .warning
+        .expect("Budget is over");
+    assert_eq!(warning.budgets[0].budget.id, created.id);
+
+    store.delete_budget(&created.id).unwrap();
+    assert!(matches!(
+        store.delete_budget(&created.id),
+        Err(AppError::BudgetNotFound(_))
+    ));
+}
+
+#[test]
+fn test_budget_definitions_are_validated() {
+    let mut store = store();
+    let card = store.create_card(Some("Groceries"), "100", None).unwrap();
+    let tag = store.create_tag("Food", None).unwrap();
+
+    let both = BudgetDefinition {
+        card_id: Some(card.id.clone()),
+        tag_id: Some(tag.id.clone()),
+        ..budget("10")
+    };
+    assert_eq!(
+        store.set_budget(None, both).unwrap_err().code(),
+        "VALIDATION"
+    );
+    assert_eq!(
+        store.set_budget(None, budget("0")).unwrap_err().code(),
+        "INVALID_AMOUNT"
+    );
+    assert_eq!(
+        store
+            .set_budget(Some("missing"), budget("10"))
+            .unwrap_err()
+            .code(),
+        "BUDGET_NOT_FOUND"
+    );
+    store.delete_card(&card.id).unwrap();
+    let trashed = BudgetDefinition {
+        card_id: Some(card.id.clone()),
+        ..budget("10")
+    };
+    assert!(matches!(
+        store.set_budget(None, trashed),
+        Err(AppError::CardNotFound(id)) if id == card.id
+    ));
+}
+
+#[test]
+fn test_recurrences_create_due_occurrences() {
+    let mut store = store();
+    let card = store.create_card(Some("Rent"), "3000", None).unwrap();
+    let template = store
+        .add_todo(
+            &card.id,
+            "Rent",
+            Some("1000"),
+            false,
+            Some("2026-01-31T09:00:00.000Z"),
+            None,
+        )
+        .unwrap()
+        .todo;
+
+    let rule = store
+        .set_recurrence(&template.id, Frequency::Monthly, None, None, None)
+        .unwrap();
+    assert_eq!(rule.next_at, "2026-02-28T09:00:00.000Z");
+    assert_eq!(store.list_recurrences().unwrap().len(), 1);
+
+    let now = chrono::DateTime::parse_from_rfc3339("2026-04-01T00:00:00Z")
+        .unwrap()
+        .with_timezone(&chrono::Utc);
+    assert_eq!(store.run_recurrences(now).unwrap(), 2);
+    assert_eq!(
+        store.run_recurrences(now).unwrap(),
+        0,
+        "Runs are idempotent"
+    );
+    assert_eq!(store.get_card(&card.id).unwrap().pending, "3000.000000");
+
+    assert_eq!(
+        store
+            .set_recurrence(&template.id, Frequency::Daily, Some(0), None, None)
+            .unwrap_err()
+            .code(),
+        "VALIDATION"
+    );
+    store.clear_recurrence(&template.id).unwrap();
+    assert!(store.list_recurrences().unwrap().is_empty());
+    assert!(matches!(
+        store.clear_recurrence(&template.id),
+        Err(AppError::RecurrenceNotFound(id)) if id == template.id
+    ));
+}
+
+#[test]
+fn test_recurrences_keep_the_template_currency() {
+    let mut store = store();
+    let home = store
+        .create_card(Some("Rent"), "3000", Some("USD"))
+        .unwrap();
+    let abroad = store
+        .create_card(Some("Flat"), "3000", Some("EUR"))
+        .unwrap();
+    let template = store
+        .add_todo(&home.id, "Rent", Some("1000"), true, None, None)
+        .unwrap()
+        .todo;
+
+    assert!(matches!(
+        store.set_recurrence(&template.id, Frequency::Monthly, None, None, Some(&abroad.id)),
+        Err(AppError::InvalidField { field, .. }) if field == "card_id"
+    ));
+
+    let priced = store
+        .add_todo(&home.id, "Rent", Some("1000"), true, None, Some("USD"))
+        .unwrap()
+        .todo;
+    let rule = store
+        .set_recurrence(&priced.id, Frequency::Monthly, None, None, Some(&abroad.id))
+        .unwrap();
+    assert_eq!(rule.card_id, abroad.id);
+}
+
+#[test]
+fn test_budget_usage_overflow_is_an_error() {
+    let mut store = store();
+    let card = store.create_card(Some("Huge"), "1", None).unwrap();
+    // Each amount fits, their sum does not
+    for (id, done) in [("big-1", 1), ("big-2", 0)] {
+        store
+            .conn()
+            .execute(
+                "INSERT INTO Todo (id, cardId, title, amount, done) VALUES (?1, ?2, 'Big', ?3, ?4)",
+                rusqlite::params![id, card.id, 5_000_000_000_000_000_000_i64, done],
+            )
+            .unwrap();
+    }
+    store
+        .set_budget(
+            None,
+            BudgetDefinition {
+                card_id: Some(card.id.clone()),
+                ..budget("10")
+            },
+        )
+        .unwrap();
+
+    let err = store.budget_status(None, chrono::Utc::now()).unwrap_err();
+    assert_eq!(err.code(), "INVALID_AMOUNT");
+}
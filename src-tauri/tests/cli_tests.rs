@@ -0,0 +1,170 @@
+//! Tests for `tin-cli` argument parsing
+use std::path::PathBuf;
+use tin_lib::cli::{parse, Command, Invocation};
+use tin_lib::pagination::CardSort;
+
+fn args(line: &str) -> Vec<String> {
+    line.split_whitespace().map(String::from).collect()
+}
+
+fn command(line: &str) -> Command {
+    parse(args(line)).unwrap().command
+}
+
+fn usage_error(line: &str) -> String {
+    parse(args(line)).unwrap_err()
+}
+
+#[test]
+fn test_list_parses_sort_limit_and_archived() {
+    assert_eq!(
+        command("list"),
+        Command::List {
+            archived: false,
+            sort: None,
+            limit: None,
+        }
+    );
+    assert_eq!(
+        command("list --archived --sort remaining --limit=20"),
+        Command::List {
+            archived: true,
+            sort: Some(CardSort::Remaining),
+            limit: Some(20),
+        }
+    );
+}
+
+#[test]
+fn test_global_options_apply_anywhere_on_the_line() {
+    assert_eq!(
+        parse(args("list --json --data-dir /tmp/tin")).unwrap(),
+        Invocation {
+            data_dir: Some(PathBuf::from("/tmp/tin")),
+            json: true,
+            command: Command::List {
+                archived: false,
+                sort: None,
+                limit: None,
+            },
+        }
+    );
+
+    let invocation = parse(args("--data-dir=/tmp/tin archive")).unwrap();
+    assert_eq!(invocation.data_dir, Some(PathBuf::from("/tmp/tin")));
+    assert!(!invocation.json);
+}
+
+#[test]
+fn test_add_card_takes_title_amount_and_currency() {
+    assert_eq!(
+        command("add-card Groceries 250 --currency EUR"),
+        Command::AddCard {
+            title: "Groceries".to_string(),
+            amount: "250".to_string(),
+            currency: Some("EUR".to_string()),
+        }
+    );
+}
+
+#[test]
+fn test_add_todo_amount_is_optional() {
+    assert_eq!(
+        command("add-todo Groceries Milk"),
+        Command::AddTodo {
+            card: "Groceries".to_string(),
+            title: "Milk".to_string(),
+            amount: None,
+            scheduled: None,
+            currency: None,
+        }
+    );
+    assert_eq!(
+        command("add-todo card-1 Milk 2.50 --scheduled 2026-10-20 --currency USD"),
+        Command::AddTodo {
+            card: "card-1".to_string(),
+            title: "Milk".to_string(),
+            amount: Some("2.50".to_string()),
+            scheduled: Some("2026-10-20".to_string()),
+            currency: Some("USD".to_string()),
+        }
+    );
+}
+
+#[test]
+fn test_search_joins_query_words() {
+    assert_eq!(
+        command("search milk tag:food --limit 5"),
+        Command::Search {
+            query: "milk tag:food".to_string(),
+            limit: Some(5),
+        }
+    );
+}
+
+#[test]
+fn test_export_parses_filters_and_backup_switch() {
+    assert_eq!(
+        command("export out.csv --archived --from 2026-01-01 --to 2026-06-30"),
+        Command::Export {
+            path: PathBuf::from("out.csv"),
+            archived: true,
+            from: Some("2026-01-01".to_string()),
+            to: Some("2026-06-30".to_string()),
+            backup: false,
+        }
+    );
+    assert_eq!(
+        command("export tin.json --backup"),
+        Command::Export {
+            path: PathBuf::from("tin.json"),
+            archived: false,
+            from: None,
+            to: None,
+            backup: true,
+        }
+    );
+}
+
+#[test]
+fn test_archive_card_is_optional() {
+    assert_eq!(command("archive"), Command::Archive { card: None });
+    assert_eq!(
+        command("archive Groceries"),
+        Command::Archive {
+            card: Some("Groceries".to_string()),
+        }
+    );
+}
+
+#[test]
+fn test_help_and_empty_line_show_usage() {
+    assert_eq!(command(""), Command::Help);
+    assert_eq!(command("--help"), Command::Help);
+    assert_eq!(command("add-card --help"), Command::Help);
+    assert_eq!(command("--json"), Command::Help);
+}
+
+#[test]
+fn test_usage_errors() {
+    for (line, message) in [
+        ("frobnicate", "Unknown command frobnicate"),
+        ("list extra", "Wrong number of arguments for list"),
+        (
+            "add-card Groceries",
+            "Wrong number of arguments for add-card",
+        ),
+        ("add-todo a b c d", "Wrong number of arguments for add-todo"),
+        ("search", "Wrong number of arguments for search"),
+        ("export", "Wrong number of arguments for export"),
+        ("archive a b", "Wrong number of arguments for archive"),
+        ("list --verbose", "Unknown option --verbose"),
+        ("list --json=yes", "Unknown option --json"),
+        ("list --sort", "--sort needs a value"),
+        ("list --sort size", "Unknown sort size"),
+        ("list --limit many", "Invalid --limit: many"),
+        ("search milk --limit -1", "Invalid --limit: -1"),
+    ] {
+        assert_eq!(usage_error(line), message, "{:?}", line);
+    }
+}
@@ -5,7 +5,26 @@ use std::sync::Mutex;
 /// Create an in-memory test database with the full schema
 pub fn create_test_db() -> Mutex<Connection> {
     let conn = Connection::open_in_memory().expect("Failed to create in-memory database");
-    conn.execute_batch(include_str!("../migrations/init.sql"))
-        .expect("Failed to initialize test database schema");
+    conn.pragma_update(None, "foreign_keys", true)
+        .expect("Failed to enable foreign keys");
+    for sql in migration_scripts() {
+        conn.execute_batch(&sql)
+            .expect("Failed to initialize test database schema");
+    }
     Mutex::new(conn)
 }
+
+/// Numbered migration scripts from `migrations/`, in version order
+pub fn migration_scripts() -> Vec<String> {
+    let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/migrations");
+    let mut paths: Vec<_> = std::fs::read_dir(dir)
+        .expect("Failed to read migrations directory")
+        .map(|entry| entry.expect("Failed to read migration entry").path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "sql"))
+        .collect();
+    paths.sort();
+    paths
+        .iter()
+        .map(|path| std::fs::read_to_string(path).expect("Failed to read migration"))
+        .collect()
+}
@@ -0,0 +1,279 @@
+//! Tests for CSV export and import, run against in-memory stores
+use rusqlite::params;
+use serde_json::json;
+use std::path::PathBuf;
+use tin_lib::csv_io;
+use tin_lib::models::{CsvMapping, ExportFilter};
+use tin_lib::store::Store;
+
+const NOW: &str = "2026-10-18T12:00:00.000Z";
+
+fn store() -> Store {
+    Store::open_in_memory().expect("Failed to open in-memory store")
+}
+
+/// An empty directory unique to this test process and `name`
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("tin-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).expect("Failed to create temp dir");
+    dir
+}
+
+#[test]
+fn test_export_then_import_round_trips_cards_and_todos() {
+    let dir = temp_dir("csv-round-trip");
+    let path = dir.join("export.csv");
+
+    let mut source = store();
+    let trip = source
+        .create_card(Some("Trip"), "300", Some("EUR"))
+        .unwrap();
+    let tickets = source
+        .add_todo(
+            &trip.id,
+            "Tickets",
+            Some("80"),
+            false,
+            Some("2026-05-01T09:00:00.000Z"),
+            None,
+        )
+        .unwrap()
+        .todo;
+    source
+        .add_todo(&trip.id, "Hotel", Some("120"), true, None, Some("USD"))
+        .unwrap();
+    source.create_card(Some("Empty"), "10", None).unwrap();
+    let conn = source.conn();
+    conn.execute_batch(
+        "UPDATE Card SET createdAt = '2026-04-01T00:00:00.000Z';
+         INSERT INTO Tag (id, name) VALUES ('travel', 'Travel');",
+    )
+    .unwrap();
+    conn.execute(
+        "UPDATE Todo SET createdAt = '2026-04-02T00:00:00.000Z', done = 1 WHERE id = ?1",
+        params![tickets.id],
+    )
+    .unwrap();
+    conn.execute(
+        "INSERT INTO TodoTag (todoId, tagId) VALUES (?1, 'travel')",
+        params![tickets.id],
+    )
+    .unwrap();
+    source.archive_card(&trip.id).unwrap();
+    source
+        .conn()
+        .execute(
+            "UPDATE Card SET archivedAt = '2026-06-01T00:00:00.000Z' WHERE id = ?1",
+            params![trip.id],
+        )
+        .unwrap();
+
+    let filter = ExportFilter {
+        include_archived: true,
+        ..Default::default()
+    };
+    let exported = csv_io::export(source.conn(), &path, &filter).unwrap();
+    assert_eq!(exported.row_count, 3, "Two todo rows and one empty card");
+
+    let mut target = store();
+    let report =
+        csv_io::import(target.conn_mut(), &path, &CsvMapping::default(), false, NOW).unwrap();
+    assert!(report.errors.is_empty(), "{:?}", report.errors);
+    assert_eq!(report.cards_created, 2);
+    assert_eq!(report.todos_created, 2);
+
+    let archived = target.list_archived_cards(None, None, None, None).unwrap();
+    assert_eq!(archived.items.len(), 1);
+    let card = target.get_card(&archived.items[0].id).unwrap();
+    assert_eq!(card.title.as_deref(), Some("Trip"));
+    assert_eq!(card.currency, "EUR");
+    assert_eq!(card.created_at, "2026-04-01T00:00:00.000Z");
+    assert_eq!(
+        card.archived_at.as_deref(),
+        Some("2026-06-01T00:00:00.000Z"),
+        "Archived cards keep when they were archived"
+    );
+    let imported = card.todos.iter().find(|t| t.title == "Tickets").unwrap();
+    assert!(imported.done);
+    assert_eq!(imported.created_at, "2026-04-02T00:00:00.000Z");
+    assert_eq!(
+        imported.scheduled_at.as_deref(),
+        Some("2026-05-01T09:00:00.000Z")
+    );
+    assert_eq!(imported.tags.len(), 1);
+    assert_eq!(imported.tags[0].name, "Travel");
+    let hotel = card.todos.iter().find(|t| t.title == "Hotel").unwrap();
+    assert_eq!(hotel.currency.as_deref(), Some("USD"));
+    assert_eq!(
+        target
+            .list_cards(None, None, None, None)
+            .unwrap()
+            .total_count,
+        1
+    );
+
+    // Importing the same file again only finds duplicates
+    let again =
+        csv_io::import(target.conn_mut(), &path, &CsvMapping::default(), false, NOW).unwrap();
+    assert_eq!(again.cards_created, 0);
+    assert_eq!(again.todos_created, 0);
+    assert_eq!(again.duplicates_skipped, 2);
+
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn test_identical_rows_in_one_file_are_all_imported() {
+    let dir = temp_dir("csv-identical-rows");
+    let path = dir.join("expenses.csv");
+    std::fs::write(
+        &path,
+        "card_title,card_amount,todo_title,todo_amount,todo_scheduled_at\n\
+         Lunch,100,Coffee,3,2026-10-01\n\
+         Lunch,100,Coffee,3,2026-10-01\n",
+    )
+    .unwrap();
+
+    let mut store = store();
+    let report =
+        csv_io::import(store.conn_mut(), &path, &CsvMapping::default(), false, NOW).unwrap();
+    assert_eq!(report.todos_created, 2);
+    assert_eq!(report.duplicates_skipped, 0);
+
+    // A file with three of them against the two already stored adds one
+    std::fs::write(
+        &path,
+        "card_title,card_amount,todo_title,todo_amount,todo_scheduled_at\n\
+         Lunch,100,Coffee,3,2026-10-01\n\
+         Lunch,100,Coffee,3,2026-10-01\n\
+         Lunch,100,Coffee,3,2026-10-01\n",
+    )
+    .unwrap();
+    let report =
+        csv_io::import(store.conn_mut(), &path, &CsvMapping::default(), false, NOW).unwrap();
+    assert_eq!(report.todos_created, 1);
+    assert_eq!(report.duplicates_skipped, 2);
+
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn test_cards_without_a_currency_use_the_default_setting() {
+    let dir = temp_dir("csv-default-currency");
+    let path = dir.join("expenses.csv");
+    std::fs::write(
+        &path,
+        "card_title,card_amount
+Rent,1000
+",
+    )
+    .unwrap();
+
+    let mut store = store();
+    let mut settings = store.settings().unwrap();
+    settings.default_currency = "EUR".into();
+    store.save_settings(settings).unwrap();
+
+    let report =
+        csv_io::import(store.conn_mut(), &path, &CsvMapping::default(), false, NOW).unwrap();
+    assert_eq!(report.cards_created, 1);
+    let cards = store.list_cards(None, None, None, None).unwrap();
+    assert_eq!(cards.items[0].currency, "EUR");
+
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn test_dry_run_reports_without_writing() {
+    let dir = temp_dir("csv-dry-run");
+    let path = dir.join("expenses.csv");
+    std::fs::write(
+        &path,
+        "card_title,card_amount,todo_title,todo_amount\nGroceries,100,Milk,2.50\nGroceries,100,Bread,4\n",
+    )
+    .unwrap();
+
+    let mut store = store();
+    let report =
+        csv_io::import(store.conn_mut(), &path, &CsvMapping::default(), true, NOW).unwrap();
+    assert!(report.dry_run);
+    assert_eq!(report.cards_created, 1);
+    assert_eq!(report.todos_created, 2);
+    assert_eq!(
+        store
+            .list_cards(None, None, None, None)
+            .unwrap()
+            .total_count,
+        0
+    );
+    assert_eq!(store.recent_changes(None, None).unwrap().total_count, 0);
+
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn test_bad_rows_are_reported_and_skipped() {
+    let dir = temp_dir("csv-row-errors");
+    let path = dir.join("expenses.csv");
+    let mut contents = b"card_title,card_amount,todo_title,todo_amount,todo_done\n".to_vec();
+    contents.extend_from_slice(b"Groceries,100,Milk,2.50,no\n");
+    contents.extend_from_slice(b"Groceries,abc,Bread,4,no\n");
+    contents.extend_from_slice(b"Groceries,100,Caf\xe9,3,no\n");
+    contents.extend_from_slice(b"Groceries,100,Eggs,3,maybe\n");
+    contents.extend_from_slice(b"Groceries,100,Butter,5,yes\n");
+    std::fs::write(&path, contents).unwrap();
+
+    let mut store = store();
+    let report =
+        csv_io::import(store.conn_mut(), &path, &CsvMapping::default(), false, NOW).unwrap();
+    assert_eq!(report.todos_created, 2);
+    assert_eq!(
+        report.errors.iter().map(|e| e.row).collect::<Vec<_>>(),
+        vec![3, 4, 5]
+    );
+    assert!(
+        report.errors[1].message.contains("UTF-8"),
+        "{:?}",
+        report.errors[1]
+    );
+
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn test_missing_columns_name_the_mapping_field() {
+    let dir = temp_dir("csv-missing-columns");
+    let path = dir.join("expenses.csv");
+    std::fs::write(&path, "title,amount\nGroceries,100\n").unwrap();
+    let mut store = store();
+
+    let err =
+        csv_io::import(store.conn_mut(), &path, &CsvMapping::default(), true, NOW).unwrap_err();
+    assert_eq!(err.code(), "VALIDATION");
+    assert_eq!(
+        err.details(),
+        Some(json!({ "field": "mapping.card_amount" }))
+    );
+
+    let mapping = CsvMapping {
+        card_title: Some("name".into()),
+        card_amount: Some("amount".into()),
+        ..Default::default()
+    };
+    let err = csv_io::import(store.conn_mut(), &path, &mapping, true, NOW).unwrap_err();
+    assert_eq!(
+        err.details(),
+        Some(json!({ "field": "mapping.card_title" }))
+    );
+    assert_eq!(
+        err.to_string(),
+        "Validation error: CSV has no column named name"
+    );
+
+    std::fs::write(&path, "base,quote\nEUR,USD\n").unwrap();
+    let err = csv_io::import_rates(store.conn_mut(), &path, NOW).unwrap_err();
+    assert_eq!(err.details(), Some(json!({ "field": "rate" })));
+
+    std::fs::remove_dir_all(&dir).ok();
+}
@@ -1,8 +1,11 @@
-//! Tests for error handling and transaction safety
+//! Tests for error handling, error codes and transaction safety
 mod common;
 
 use common::create_test_db;
-use rusqlite::params;
+use rusqlite::{params, Connection};
+use serde_json::json;
+use tin_lib::errors::AppError;
+use tin_lib::store::Store;
 
 #[test]
 fn test_duplicate_primary_key_rejected() {
@@ -108,3 +111,143 @@ fn test_transaction_commit_persists() {
 
     assert_eq!(count, 1, "Committed transaction should persist");
 }
+
+#[test]
+fn test_errors_serialize_with_code_and_details() {
+    let value = serde_json::to_value(AppError::CardNotFound("card-1".into())).unwrap();
+    assert_eq!(
+        value,
+        json!({
+            "code": "CARD_NOT_FOUND",
+            "message": "Card not found: card-1",
+            "details": { "id": "card-1" },
+        })
+    );
+
+    let value = serde_json::to_value(AppError::NothingToUndo).unwrap();
+    assert_eq!(value["code"], "NOTHING_TO_UNDO");
+    assert_eq!(value["details"], serde_json::Value::Null);
+}
+
+#[test]
+fn test_validation_errors_name_the_field() {
+    let store = Store::open_in_memory().unwrap();
+
+    let err = store.list_cards(Some(0), None, None, None).unwrap_err();
+    assert_eq!(err.code(), "VALIDATION");
+    assert_eq!(err.details(), Some(json!({ "field": "limit" })));
+
+    let err = store
+        .list_cards(None, Some("garbage"), None, None)
+        .unwrap_err();
+    assert_eq!(err.details(), Some(json!({ "field": "cursor" })));
+
+    let nested = AppError::invalid_field("port", "API port cannot be 0").within("api_server");
+    assert_eq!(
+        nested.details(),
+        Some(json!({ "field": "api_server.port" }))
+    );
+    assert_eq!(nested.to_string(), "Validation error: API port cannot be 0");
+}
+
+#[test]
+fn test_duplicate_keys_map_to_conflict() {
+    let store = Store::open_in_memory().unwrap();
+    let conn = store.conn();
+
+    // PRIMARY KEY
+    let insert = || conn.execute("INSERT INTO Card (id, amount) VALUES ('same-id', 100)", []);
+    insert().unwrap();
+    let err = AppError::from(insert().unwrap_err());
+    assert_eq!(err.code(), "CONFLICT");
+
+    // UNIQUE, which for tag names ignores case
+    conn.execute("INSERT INTO Tag (id, name) VALUES ('t1', 'Food')", [])
+        .unwrap();
+    let err = AppError::from(
+        conn.execute("INSERT INTO Tag (id, name) VALUES ('t2', 'food')", [])
+            .unwrap_err(),
+    );
+    assert_eq!(err.code(), "CONFLICT");
+}
+
+#[test]
+fn test_other_constraint_failures_map_to_validation() {
+    let store = Store::open_in_memory().unwrap();
+    let conn = store.conn();
+    conn.execute("INSERT INTO Card (id, amount) VALUES ('card-1', 100)", [])
+        .unwrap();
+
+    for (constraint, sql) in [
+        (
+            "CHECK",
+            "INSERT INTO ExchangeRate (base, quote, rate) VALUES ('EUR', 'USD', 0)",
+        ),
+        (
+            "NOT NULL",
+            "INSERT INTO Todo (id, cardId, title) VALUES ('todo-1', 'card-1', NULL)",
+        ),
+        (
+            "FOREIGN KEY",
+            "INSERT INTO Todo (id, cardId, title) VALUES ('todo-2', 'missing', 'Milk')",
+        ),
+    ] {
+        let err = AppError::from(conn.execute(sql, []).unwrap_err());
+        assert_eq!(err.code(), "VALIDATION", "{} failure: {}", constraint, err);
+        assert!(
+            err.to_string().contains("constraint failed"),
+            "{} failure: {}",
+            constraint,
+            err
+        );
+    }
+}
+
+#[test]
+fn test_missing_exchange_rate_names_the_pair() {
+    let err = AppError::RateNotFound {
+        base: "EUR".into(),
+        quote: "USD".into(),
+    };
+    assert_eq!(
+        serde_json::to_value(&err).unwrap(),
+        json!({
+            "code": "RATE_NOT_FOUND",
+            "message": "No exchange rate for EUR/USD",
+            "details": { "base": "EUR", "quote": "USD" },
+        })
+    );
+}
+
+#[test]
+fn test_locked_and_corrupt_databases_have_their_own_codes() {
+    let dir = std::env::temp_dir().join(format!("tin-error-tests-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let path = dir.join("locked.db");
+    let writer = Connection::open(&path).unwrap();
+    writer
+        .execute_batch("CREATE TABLE t (x INTEGER); BEGIN EXCLUSIVE; INSERT INTO t VALUES (1);")
+        .unwrap();
+    let reader = Connection::open(&path).unwrap();
+    let err = AppError::from(
+        reader
+            .query_row("SELECT COUNT(*) FROM t", [], |row| row.get::<_, i64>(0))
+            .unwrap_err(),
+    );
+    assert_eq!(err.code(), "DB_LOCKED");
+    drop(writer);
+
+    let path = dir.join("corrupt.db");
+    std::fs::write(&path, vec![0x5a; 4096]).unwrap();
+    let conn = Connection::open(&path).unwrap();
+    let err = AppError::from(
+        conn.query_row("SELECT COUNT(*) FROM sqlite_master", [], |row| {
+            row.get::<_, i64>(0)
+        })
+        .unwrap_err(),
+    );
+    assert_eq!(err.code(), "DB_CORRUPT");
+
+    std::fs::remove_dir_all(&dir).ok();
+}
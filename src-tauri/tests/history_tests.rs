@@ -0,0 +1,162 @@
+//! Tests for undoing changes and reconstructing a card's past state from
+//! ChangeLog snapshots
+mod common;
+
+use common::create_test_db;
+use rusqlite::params;
+use tin_lib::recurrence::Frequency;
+use tin_lib::store::Store;
+
+fn store() -> Store {
+    Store::open_in_memory().expect("Failed to open in-memory store")
+}
+
+#[test]
+fn test_point_in_time_replays_only_changes_live_at_that_time() {
+    let db = create_test_db();
+    let conn = db.lock().unwrap();
+
+    conn.execute(
+        "INSERT INTO Card (id, title, amount, createdAt, updatedAt) VALUES ('c', 'Now', 0, '2026-10-01T00:00:00.000Z', '2026-10-05T00:00:00.000Z')",
+        [],
+    )
+    .unwrap();
+    let changes = [
+        // (id, createdAt, undoneAt, before title)
+        ("a", "2026-10-02T00:00:00.000Z", None, "First"),
+        (
+            "b",
+            "2026-10-03T00:00:00.000Z",
+            Some("2026-10-06T00:00:00.000Z"),
+            "Second",
+        ),
+        ("c", "2026-10-04T00:00:00.000Z", None, "Third"),
+        (
+            "d",
+            "2026-10-05T00:00:00.000Z",
+            Some("2026-10-05T12:00:00.000Z"),
+            "Fourth",
+        ),
+    ];
+    for (id, created_at, undone_at, before) in changes {
+        let payload = format!(r#"{{"before":{{"title":"{}"}},"after":{{}}}}"#, before);
+        conn.execute(
+            "INSERT INTO ChangeLog (id, cardId, kind, payload, createdAt, undoneAt) VALUES (?1, 'c', 'updated', ?2, ?3, ?4)",
+            params![id, payload, created_at, undone_at],
+        )
+        .unwrap();
+    }
+
+    let ids = |sql: &str| -> Vec<String> {
+        conn.prepare(sql)
+            .unwrap()
+            .query_map(params!["c", "2026-10-03T12:00:00.000Z"], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    };
+
+    // Reverted newest first from the current state
+    assert_eq!(
+        ids("SELECT id FROM ChangeLog WHERE cardId = ?1 AND createdAt > ?2 AND undoneAt IS NULL ORDER BY createdAt DESC, rowid DESC"),
+        vec!["c"],
+        "Changes undone before now are already absent from the current state"
+    );
+    // Re-applied oldest first
+    assert_eq!(
+        ids("SELECT id FROM ChangeLog WHERE cardId = ?1 AND createdAt <= ?2 AND undoneAt > ?2 ORDER BY createdAt ASC, rowid ASC"),
+        vec!["b"],
+        "Only changes that were live at the timestamp and undone later are replayed"
+    );
+}
+
+#[test]
+fn test_undo_skips_changes_made_by_recurrences() {
+    let mut store = store();
+    let card = store.create_card(Some("Rent"), "3000", None).unwrap();
+    let template = store
+        .add_todo(
+            &card.id,
+            "Rent",
+            Some("1000"),
+            false,
+            Some("2026-01-31T09:00:00.000Z"),
+            None,
+        )
+        .unwrap()
+        .todo;
+    store
+        .set_recurrence(&template.id, Frequency::Monthly, None, None, None)
+        .unwrap();
+    let now = chrono::DateTime::parse_from_rfc3339("2026-03-01T00:00:00Z")
+        .unwrap()
+        .with_timezone(&chrono::Utc);
+    assert_eq!(store.run_recurrences(now).unwrap(), 1);
+
+    let undone = store.undo_last_change().unwrap();
+    assert_eq!(undone.kind, "todo_added");
+    assert_eq!(undone.payload["after"]["id"], template.id.as_str());
+    assert_eq!(
+        store.get_card(&card.id).unwrap().todos.len(),
+        1,
+        "Only the template is removed; the generated occurrence stays"
+    );
+}
+
+#[test]
+fn test_undoing_a_template_deletion_restores_its_recurrence() {
+    let mut store = store();
+    let card = store.create_card(Some("Rent"), "3000", None).unwrap();
+    let template = store
+        .add_todo(
+            &card.id,
+            "Rent",
+            Some("1000"),
+            false,
+            Some("2026-01-31T09:00:00.000Z"),
+            None,
+        )
+        .unwrap()
+        .todo;
+    store
+        .set_recurrence(&template.id, Frequency::Monthly, None, None, None)
+        .unwrap();
+
+    store.delete_todo(&template.id).unwrap();
+    assert!(store.list_recurrences().unwrap().is_empty());
+
+    store.undo_last_change().unwrap();
+    let rules = store.list_recurrences().unwrap();
+    assert_eq!(rules.len(), 1);
+    assert_eq!(rules[0].todo_id, template.id);
+    assert_eq!(rules[0].next_at, "2026-02-28T09:00:00.000Z");
+}
+
+#[test]
+fn test_undo_fails_when_the_change_no_longer_applies() {
+    let mut store = store();
+    let card = store.create_card(Some("Groceries"), "100", None).unwrap();
+    let todo = store
+        .add_todo(&card.id, "Milk", Some("3"), true, None, None)
+        .unwrap()
+        .todo;
+    // Removed behind the ChangeLog's back
+    store
+        .conn()
+        .execute("DELETE FROM Todo WHERE id = ?1", params![todo.id])
+        .unwrap();
+
+    assert_eq!(
+        store.undo_last_change().unwrap_err().code(),
+        "IRREVERSIBLE_CHANGE"
+    );
+    let undone: Option<String> = store
+        .conn()
+        .query_row(
+            "SELECT undoneAt FROM ChangeLog WHERE kind = 'todo_added'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert!(undone.is_none(), "A failed undo leaves the entry in effect");
+}
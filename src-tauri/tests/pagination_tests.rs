@@ -0,0 +1,318 @@
+//! Tests for keyset pagination of card lists, run through `Store`
+use rusqlite::params;
+use serde_json::json;
+use tin_lib::models::CardDto;
+use tin_lib::pagination::{page_limit, CardSort, Cursor, SortOrder, DEFAULT_LIMIT, MAX_LIMIT};
+use tin_lib::store::Store;
+
+fn store() -> Store {
+    Store::open_in_memory().expect("Failed to open in-memory store")
+}
+
+#[test]
+fn test_keyset_pages_cover_ties_exactly_once() {
+    let mut store = store();
+
+    // Several cards share a creation time, so the id must break ties
+    let mut ids = Vec::new();
+    for i in 0..7 {
+        let card = store.create_card(Some("Card"), "0", None).unwrap();
+        let created_at = format!("2026-10-0{}T00:00:00.000Z", 1 + i / 3);
+        store
+            .conn()
+            .execute(
+                "UPDATE Card SET createdAt = ?1 WHERE id = ?2",
+                params![created_at, card.id],
+            )
+            .unwrap();
+        ids.push((created_at, card.id));
+    }
+    ids.sort();
+    ids.reverse();
+
+    let mut seen: Vec<String> = Vec::new();
+    let mut cursor: Option<String> = None;
+    loop {
+        let page = store
+            .list_cards(Some(2), cursor.as_deref(), Some(CardSort::Created), None)
+            .unwrap();
+        assert_eq!(page.total_count, 7);
+        assert!(page.items.len() <= 2);
+        seen.extend(page.items.into_iter().map(|c| c.id));
+        cursor = page.next_cursor;
+        if cursor.is_none() {
+            break;
+        }
+    }
+
+    assert_eq!(
+        seen,
+        ids.into_iter().map(|(_, id)| id).collect::<Vec<_>>(),
+        "Every card should appear once, newest first"
+    );
+}
+
+/// Six cards with repeated titles, amounts and timestamps, so every sort has
+/// ties for the id to break. Titles differ in case and two are missing.
+fn seeded_store(archived: bool) -> Store {
+    let mut store = store();
+    let cards = [
+        (Some("banana"), "10", Some("2")),
+        (Some("Apple"), "20", None),
+        (None, "10", Some("12.5")),
+        (Some("apple"), "5", None),
+        (Some("Cherry"), "20", Some("2")),
+        (None, "10", None),
+    ];
+    for (i, (title, amount, todo)) in cards.into_iter().enumerate() {
+        let card = store.create_card(title, amount, None).unwrap();
+        if let Some(todo) = todo {
+            store
+                .add_todo(&card.id, "Item", Some(todo), true, None, None)
+                .unwrap();
+        }
+        if archived {
+            store.archive_card(&card.id).unwrap();
+        }
+        let stamp = |day: usize| format!("2026-10-0{}T00:00:00.000Z", day);
+        store
+            .conn()
+            .execute(
+                "UPDATE Card SET createdAt = ?1, updatedAt = ?2,
+                 archivedAt = CASE WHEN archived THEN ?3 END WHERE id = ?4",
+                params![
+                    stamp(1 + i / 2),
+                    stamp(1 + i % 2),
+                    stamp(1 + i / 3),
+                    card.id
+                ],
+            )
+            .unwrap();
+    }
+    store
+}
+
+/// Ids of every card, fetched two at a time by following `next_cursor`.
+fn page_through(
+    store: &Store,
+    archived: bool,
+    sort: CardSort,
+    order: Option<SortOrder>,
+) -> Vec<String> {
+    let mut ids = Vec::new();
+    let mut cursor: Option<String> = None;
+    loop {
+        let page = if archived {
+            store.list_archived_cards(Some(2), cursor.as_deref(), Some(sort), order)
+        } else {
+            store.list_cards(Some(2), cursor.as_deref(), Some(sort), order)
+        }
+        .unwrap();
+        ids.extend(page.items.into_iter().map(|c| c.id));
+        cursor = page.next_cursor;
+        if cursor.is_none() {
+            return ids;
+        }
+    }
+}
+
+/// The order `sort` should produce, worked out independently of the SQL.
+fn expected_order(cards: &[CardDto], sort: CardSort, order: SortOrder) -> Vec<String> {
+    let money = |amount: &str| amount.replace('.', "").parse::<i64>().unwrap();
+    let key = |card: &CardDto| -> (i64, String) {
+        match sort {
+            CardSort::Created => (0, card.created_at.clone()),
+            CardSort::Updated => (0, card.updated_at.clone()),
+            CardSort::Archived => (0, card.archived_at.clone().unwrap_or_default()),
+            CardSort::Title => (0, card.title.clone().unwrap_or_default().to_lowercase()),
+            CardSort::Amount => (money(&card.amount), String::new()),
+            CardSort::Remaining => (money(&card.remaining), String::new()),
+        }
+    };
+    let mut sorted: Vec<&CardDto> = cards.iter().collect();
+    sorted.sort_by(|a, b| (key(a), &a.id).cmp(&(key(b), &b.id)));
+    if order == SortOrder::Desc {
+        sorted.reverse();
+    }
+    sorted.into_iter().map(|c| c.id.clone()).collect()
+}
+
+#[test]
+fn test_every_sort_pages_through_all_cards_in_order() {
+    let sorts = [
+        CardSort::Created,
+        CardSort::Updated,
+        CardSort::Archived,
+        CardSort::Title,
+        CardSort::Amount,
+        CardSort::Remaining,
+    ];
+
+    for archived in [false, true] {
+        let store = seeded_store(archived);
+        let cards = if archived {
+            store.list_archived_cards(None, None, None, None)
+        } else {
+            store.list_cards(None, None, None, None)
+        }
+        .unwrap()
+        .items;
+        assert_eq!(cards.len(), 6);
+
+        for sort in sorts {
+            for order in [SortOrder::Asc, SortOrder::Desc] {
+                assert_eq!(
+                    page_through(&store, archived, sort, Some(order)),
+                    expected_order(&cards, sort, order),
+                    "{:?} {:?} (archived: {})",
+                    sort,
+                    order,
+                    archived
+                );
+            }
+            assert_eq!(
+                page_through(&store, archived, sort, None),
+                expected_order(&cards, sort, sort.default_order()),
+                "{:?} default order",
+                sort
+            );
+        }
+    }
+}
+
+#[test]
+fn test_title_sort_ignores_case_and_puts_missing_titles_first() {
+    let store = seeded_store(false);
+
+    let titles: Vec<Option<String>> = store
+        .list_cards(None, None, Some(CardSort::Title), None)
+        .unwrap()
+        .items
+        .into_iter()
+        .map(|c| c.title)
+        .collect();
+    let titles: Vec<Option<&str>> = titles.iter().map(|t| t.as_deref()).collect();
+
+    assert_eq!(&titles[..2], &[None, None]);
+    assert_eq!(
+        titles[2..]
+            .iter()
+            .map(|t| t.unwrap().to_lowercase())
+            .collect::<Vec<_>>(),
+        ["apple", "apple", "banana", "cherry"]
+    );
+}
+
+#[test]
+fn test_remaining_sort_subtracts_todos() {
+    let store = seeded_store(false);
+
+    let remaining: Vec<String> = store
+        .list_cards(None, None, Some(CardSort::Remaining), Some(SortOrder::Asc))
+        .unwrap()
+        .items
+        .into_iter()
+        .map(|c| c.remaining)
+        .collect();
+
+    assert_eq!(
+        remaining,
+        [
+            "-2.500000",
+            "5.000000",
+            "8.000000",
+            "10.000000",
+            "18.000000",
+            "20.000000"
+        ]
+    );
+}
+
+#[test]
+fn test_cursor_round_trips() {
+    let cursor = Cursor {
+        sort: "title:asc".to_string(),
+        key: "Ünïcode \"quoted\"".to_string(),
+        id: "card-1".to_string(),
+    };
+
+    let encoded = cursor.encode();
+    assert!(encoded.chars().all(|c| c.is_ascii_hexdigit()));
+    assert_eq!(Cursor::decode(&encoded, "title:asc").unwrap(), cursor);
+}
+
+#[test]
+fn test_malformed_cursors_are_rejected() {
+    let valid = Cursor {
+        sort: "created:desc".to_string(),
+        key: "2026-10-01T00:00:00.000Z".to_string(),
+        id: "card-1".to_string(),
+    }
+    .encode();
+
+    for encoded in [
+        "",
+        "abc",
+        "zz",
+        "+1+1",
+        "é1",
+        &valid[..valid.len() - 2],
+        "7b7d",
+    ] {
+        let err = Cursor::decode(encoded, "created:desc").unwrap_err();
+        assert_eq!(
+            err.details(),
+            Some(json!({ "field": "cursor" })),
+            "{:?} should be an invalid cursor",
+            encoded
+        );
+    }
+}
+
+#[test]
+fn test_cursor_from_another_sort_is_rejected() {
+    let store = seeded_store(false);
+
+    let page = store
+        .list_cards(Some(2), None, Some(CardSort::Title), None)
+        .unwrap();
+    let cursor = page.next_cursor.unwrap();
+
+    for (sort, order) in [
+        (CardSort::Created, None),
+        (CardSort::Title, Some(SortOrder::Desc)),
+    ] {
+        let err = store
+            .list_cards(Some(2), Some(&cursor), Some(sort), order)
+            .unwrap_err();
+        assert_eq!(err.code(), "VALIDATION");
+        assert_eq!(err.details(), Some(json!({ "field": "cursor" })));
+        assert!(
+            err.to_string().contains("issued for sort title:asc"),
+            "{}",
+            err
+        );
+    }
+
+    // Active and archived lists share the tag, so the same cursor works on both
+    assert!(store
+        .list_archived_cards(Some(2), Some(&cursor), Some(CardSort::Title), None)
+        .is_ok());
+}
+
+#[test]
+fn test_page_limit_is_defaulted_and_clamped() {
+    assert_eq!(page_limit(None).unwrap(), DEFAULT_LIMIT);
+    assert_eq!(page_limit(Some(7)).unwrap(), 7);
+    assert_eq!(page_limit(Some(MAX_LIMIT)).unwrap(), MAX_LIMIT);
+    assert_eq!(page_limit(Some(10_000)).unwrap(), MAX_LIMIT);
+
+    let err = page_limit(Some(0)).unwrap_err();
+    assert_eq!(err.details(), Some(json!({ "field": "limit" })));
+
+    let store = seeded_store(false);
+    assert!(store.list_cards(Some(0), None, None, None).is_err());
+    let page = store.list_cards(Some(10_000), None, None, None).unwrap();
+    assert_eq!(page.items.len(), 6);
+    assert_eq!(page.next_cursor, None);
+}
@@ -0,0 +1,86 @@
+//! Tests for the period bucketing used by spending reports
+mod common;
+
+use common::create_test_db;
+use rusqlite::params;
+
+#[test]
+fn test_week_buckets_start_on_monday() {
+    let db = create_test_db();
+    let conn = db.lock().unwrap();
+
+    // 2026-10-12 is a Monday and 2026-10-18 the Sunday of the same week
+    for (timestamp, expected) in [
+        ("2026-10-12T00:00:00.000Z", "2026-10-12"),
+        ("2026-10-15T23:59:59.999Z", "2026-10-12"),
+        ("2026-10-18T12:00:00.000Z", "2026-10-12"),
+        ("2026-10-19T00:00:00.000Z", "2026-10-19"),
+        // Legacy `datetime('now')` values
+        ("2026-10-18 09:30:00", "2026-10-12"),
+    ] {
+        let week: String = conn
+            .query_row(
+                "SELECT date(?1, 'weekday 0', '-6 days')",
+                params![timestamp],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(week, expected, "Week of {}", timestamp);
+    }
+}
+
+#[test]
+fn test_period_change_compares_with_empty_previous_periods() {
+    let db = create_test_db();
+    let conn = db.lock().unwrap();
+
+    conn.execute(
+        "INSERT INTO Card (id, title, amount) VALUES ('c', 'Card', 0)",
+        [],
+    )
+    .unwrap();
+    for (id, amount, scheduled_at) in [
+        ("a", 8_000_000_i64, "2026-01-10T00:00:00.000Z"),
+        ("b", 6_000_000, "2026-03-02T00:00:00.000Z"),
+    ] {
+        conn.execute(
+            "INSERT INTO Todo (id, cardId, title, amount, scheduledAt) VALUES (?1, 'c', 'Todo', ?2, ?3)",
+            params![id, amount, scheduled_at],
+        )
+        .unwrap();
+    }
+
+    // Every month in the range is generated, so February's empty total sits
+    // between January and March instead of March comparing against January
+    let mut stmt = conn
+        .prepare(
+            "WITH RECURSIVE periods(start) AS (
+                 SELECT '2026-01-01'
+                 UNION ALL
+                 SELECT date(start, '+1 month') FROM periods WHERE start < '2026-03-01'
+             ),
+             spending AS (
+                 SELECT date(scheduledAt, 'start of month') AS period, SUM(amount) AS total
+                 FROM Todo GROUP BY period
+             )
+             SELECT p.start, COALESCE(s.total, 0),
+                    LAG(COALESCE(s.total, 0), 1, 0) OVER (ORDER BY p.start)
+             FROM periods p LEFT JOIN spending s ON s.period = p.start
+             ORDER BY p.start",
+        )
+        .unwrap();
+    let rows: Vec<(String, i64, i64)> = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+
+    assert_eq!(
+        rows,
+        vec![
+            ("2026-01-01".to_string(), 8_000_000, 0),
+            ("2026-02-01".to_string(), 0, 8_000_000),
+            ("2026-03-01".to_string(), 6_000_000, 0),
+        ]
+    );
+}
@@ -1,7 +1,7 @@
 //! Tests for database schema integrity
 mod common;
 
-use common::create_test_db;
+use common::{create_test_db, migration_scripts};
 use rusqlite::params;
 
 #[test]
@@ -164,3 +164,392 @@ fn test_cascade_delete_removes_todos() {
 
     assert_eq!(todo_count, 0, "Todos should be cascade deleted with card");
 }
+
+#[test]
+fn test_migration_files_are_numbered_sequentially() {
+    let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/migrations");
+    let mut versions: Vec<u32> = std::fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .filter(|name| name.ends_with(".sql"))
+        .map(|name| {
+            name[..4]
+                .parse()
+                .expect("Migration name should start with NNNN_")
+        })
+        .collect();
+    versions.sort();
+
+    let expected: Vec<u32> = (1..=versions.len() as u32).collect();
+    assert_eq!(
+        versions, expected,
+        "Migrations should be numbered 0001, 0002, ..."
+    );
+}
+
+#[test]
+fn test_migrations_apply_cleanly_in_one_transaction_each() {
+    let mut conn = rusqlite::Connection::open_in_memory().unwrap();
+
+    for (i, sql) in migration_scripts().iter().enumerate() {
+        let tx = conn.transaction().unwrap();
+        tx.execute_batch(sql).unwrap();
+        tx.pragma_update(None, "user_version", i as u32 + 1)
+            .unwrap();
+        tx.commit().unwrap();
+    }
+
+    let version: u32 = conn
+        .query_row("PRAGMA user_version", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(version as usize, migration_scripts().len());
+
+    let amount_type: String = conn
+        .query_row(
+            "SELECT type FROM pragma_table_info('Card') WHERE name = 'amount'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(
+        amount_type, "INTEGER",
+        "Amounts should end up as minor units"
+    );
+}
+
+#[test]
+fn test_failed_migration_rolls_back_user_version() {
+    let mut conn = rusqlite::Connection::open_in_memory().unwrap();
+
+    let tx = conn.transaction().unwrap();
+    tx.pragma_update(None, "user_version", 7).unwrap();
+    let result = tx.execute_batch("CREATE TABLE Broken (id TEXT); SELECT * FROM missing_table;");
+    assert!(result.is_err());
+    drop(tx);
+
+    let version: u32 = conn
+        .query_row("PRAGMA user_version", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(
+        version, 0,
+        "user_version should roll back with the migration"
+    );
+}
+
+#[test]
+fn test_changelog_tracks_undone_entries() {
+    let db = create_test_db();
+    let conn = db.lock().unwrap();
+
+    conn.execute(
+        "INSERT INTO Card (id, title, amount, createdAt, updatedAt) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            "card-undo",
+            "Undo",
+            0_i64,
+            "2024-01-01T00:00:00.000Z",
+            "2024-01-01T00:00:00.000Z"
+        ],
+    )
+    .unwrap();
+    conn.execute(
+        "INSERT INTO ChangeLog (id, cardId, kind, payload, createdAt) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            "change-1",
+            "card-undo",
+            "updated",
+            r#"{"before":{"title":"Old"},"after":{"title":"Undo"}}"#,
+            "2024-01-01T00:00:00.000Z"
+        ],
+    )
+    .unwrap();
+
+    let undone_at: Option<String> = conn
+        .query_row(
+            "SELECT undoneAt FROM ChangeLog WHERE id = ?1",
+            params!["change-1"],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert!(undone_at.is_none(), "New changes should start in effect");
+
+    let reversible: i32 = conn
+        .query_row(
+            "SELECT COUNT(*) FROM ChangeLog WHERE json_type(payload, '$.before') = 'object'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(reversible, 1, "Snapshots should be queryable as JSON");
+}
+
+#[test]
+fn test_foreign_keys_enabled_on_connection() {
+    let db = create_test_db();
+    let conn = db.lock().unwrap();
+
+    let enabled: bool = conn
+        .query_row("PRAGMA foreign_keys", [], |row| row.get(0))
+        .unwrap();
+    assert!(enabled, "Foreign keys should be enforced");
+}
+
+#[test]
+fn test_cascade_delete_removes_changelog_and_search_rows() {
+    let db = create_test_db();
+    let conn = db.lock().unwrap();
+
+    conn.execute(
+        "INSERT INTO Card (id, title, amount, createdAt, updatedAt) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            "card-fk",
+            "Cascade",
+            0_i64,
+            "2024-01-01T00:00:00.000Z",
+            "2024-01-01T00:00:00.000Z"
+        ],
+    )
+    .unwrap();
+    conn.execute(
+        "INSERT INTO Todo (id, cardId, title, createdAt, updatedAt) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            "todo-fk",
+            "card-fk",
+            "Searchable",
+            "2024-01-01T00:00:00.000Z",
+            "2024-01-01T00:00:00.000Z"
+        ],
+    )
+    .unwrap();
+    conn.execute(
+        "INSERT INTO ChangeLog (id, cardId, kind, payload, createdAt) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            "change-fk",
+            "card-fk",
+            "created",
+            "{}",
+            "2024-01-01T00:00:00.000Z"
+        ],
+    )
+    .unwrap();
+
+    conn.execute("DELETE FROM Card WHERE id = ?1", params!["card-fk"])
+        .unwrap();
+
+    let changes: i32 = conn
+        .query_row("SELECT COUNT(*) FROM ChangeLog", [], |row| row.get(0))
+        .unwrap();
+    let search_rows: i32 = conn
+        .query_row("SELECT COUNT(*) FROM search_index", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(changes, 0, "ChangeLog rows should cascade with the card");
+    assert_eq!(
+        search_rows, 0,
+        "Search rows should follow the card and its todos"
+    );
+}
+
+#[test]
+fn test_orphan_cleanup_migration_removes_dangling_rows() {
+    let db = create_test_db();
+    let conn = db.lock().unwrap();
+
+    // Simulate a database written without foreign key enforcement
+    conn.pragma_update(None, "foreign_keys", false).unwrap();
+    conn.execute(
+        "INSERT INTO Todo (id, cardId, title, createdAt, updatedAt) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            "todo-orphan",
+            "missing-card",
+            "Orphan",
+            "2024-01-01T00:00:00.000Z",
+            "2024-01-01T00:00:00.000Z"
+        ],
+    )
+    .unwrap();
+    conn.execute(
+        "INSERT INTO ChangeLog (id, cardId, kind, payload, createdAt) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            "change-orphan",
+            "missing-card",
+            "created",
+            "{}",
+            "2024-01-01T00:00:00.000Z"
+        ],
+    )
+    .unwrap();
+
+    let violations: i32 = conn
+        .query_row("SELECT COUNT(*) FROM pragma_foreign_key_check", [], |row| {
+            row.get(0)
+        })
+        .unwrap();
+    assert_eq!(
+        violations, 2,
+        "foreign_key_check should report both orphans"
+    );
+
+    conn.execute_batch(include_str!("../migrations/0005_orphan_cleanup.sql"))
+        .unwrap();
+
+    let violations: i32 = conn
+        .query_row("SELECT COUNT(*) FROM pragma_foreign_key_check", [], |row| {
+            row.get(0)
+        })
+        .unwrap();
+    let search_rows: i32 = conn
+        .query_row("SELECT COUNT(*) FROM search_index", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(violations, 0, "Cleanup should remove orphaned rows");
+    assert_eq!(search_rows, 0, "Cleanup should remove orphaned search rows");
+}
+
+#[test]
+fn test_recurrence_occurrences_are_unique_per_date() {
+    let db = create_test_db();
+    let conn = db.lock().unwrap();
+
+    conn.execute(
+        "INSERT INTO Card (id, title, amount, createdAt, updatedAt) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            "card-rec",
+            "Bills",
+            0_i64,
+            "2024-01-01T00:00:00.000Z",
+            "2024-01-01T00:00:00.000Z"
+        ],
+    )
+    .unwrap();
+    conn.execute(
+        "INSERT INTO Todo (id, cardId, title, scheduledAt, createdAt, updatedAt) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params!["todo-rent", "card-rec", "Rent", "2024-01-01T00:00:00.000Z", "2024-01-01T00:00:00.000Z", "2024-01-01T00:00:00.000Z"],
+    )
+    .unwrap();
+    conn.execute(
+        "INSERT INTO Recurrence (todoId, cardId, frequency, interval, startAt, nextAt) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params!["todo-rent", "card-rec", "monthly", 1, "2024-01-01T00:00:00.000Z", "2024-02-01T00:00:00.000Z"],
+    )
+    .unwrap();
+
+    let insert_occurrence = |id: &str| {
+        conn.execute(
+            "INSERT INTO Todo (id, cardId, title, scheduledAt, recurrenceOf, createdAt, updatedAt) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![id, "card-rec", "Rent", "2024-02-01T00:00:00.000Z", "todo-rent", "2024-02-01T00:00:00.000Z", "2024-02-01T00:00:00.000Z"],
+        )
+    };
+    assert!(insert_occurrence("todo-rent-feb").is_ok());
+    assert!(
+        insert_occurrence("todo-rent-feb-again").is_err(),
+        "The same occurrence should not be generated twice"
+    );
+
+    let bad_frequency = conn.execute(
+        "UPDATE Recurrence SET frequency = 'hourly' WHERE todoId = ?1",
+        params!["todo-rent"],
+    );
+    assert!(
+        bad_frequency.is_err(),
+        "Unknown frequencies should be rejected"
+    );
+
+    conn.execute("DELETE FROM Todo WHERE id = ?1", params!["todo-rent"])
+        .unwrap();
+
+    let rules: i32 = conn
+        .query_row("SELECT COUNT(*) FROM Recurrence", [], |row| row.get(0))
+        .unwrap();
+    let link: Option<String> = conn
+        .query_row(
+            "SELECT recurrenceOf FROM Todo WHERE id = ?1",
+            params!["todo-rent-feb"],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(rules, 0, "Deleting the template should end the series");
+    assert_eq!(link, None, "Generated todos should outlive their template");
+}
+
+#[test]
+fn test_settings_upsert_replaces_value() {
+    let db = create_test_db();
+    let conn = db.lock().unwrap();
+
+    let upsert = "INSERT INTO Settings (key, value, updatedAt) VALUES (?1, ?2, ?3)
+         ON CONFLICT (key) DO UPDATE SET value = excluded.value, updatedAt = excluded.updatedAt";
+    conn.execute(
+        upsert,
+        params!["default_currency", "\"USD\"", "2026-01-01T00:00:00.000Z"],
+    )
+    .unwrap();
+    conn.execute(
+        upsert,
+        params!["default_currency", "\"EUR\"", "2026-02-01T00:00:00.000Z"],
+    )
+    .unwrap();
+
+    let (count, value, updated_at): (i32, String, String) = conn
+        .query_row(
+            "SELECT COUNT(*), value, updatedAt FROM Settings WHERE key = 'default_currency'",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .unwrap();
+
+    assert_eq!(count, 1, "Each setting should be stored once");
+    assert_eq!(value, "\"EUR\"");
+    assert_eq!(updated_at, "2026-02-01T00:00:00.000Z");
+}
+
+#[test]
+fn test_budget_constraints_and_cascades() {
+    let db = create_test_db();
+    let conn = db.lock().unwrap();
+
+    conn.execute(
+        "INSERT INTO Card (id, title, amount) VALUES ('card-b', 'Trip', 0)",
+        [],
+    )
+    .unwrap();
+    conn.execute("INSERT INTO Tag (id, name) VALUES ('tag-b', 'food')", [])
+        .unwrap();
+
+    let insert = |id: &str, card_id: Option<&str>, tag_id: Option<&str>, amount: i64| {
+        conn.execute(
+            "INSERT INTO Budget (id, cardId, tagId, amount, currency) VALUES (?1, ?2, ?3, ?4, 'USD')",
+            params![id, card_id, tag_id, amount],
+        )
+    };
+    assert!(insert("card", Some("card-b"), None, 10_000_000).is_ok());
+    assert!(insert("tag", None, Some("tag-b"), 10_000_000).is_ok());
+    assert!(insert("all", None, None, 10_000_000).is_ok());
+    assert!(
+        insert("both", Some("card-b"), Some("tag-b"), 10_000_000).is_err(),
+        "A budget should cover a card or a tag, not both"
+    );
+    assert!(
+        insert("zero", None, None, 0).is_err(),
+        "Budgets need a positive amount"
+    );
+    assert!(
+        insert("orphan", Some("missing"), None, 10_000_000).is_err(),
+        "Budgets should reference an existing card"
+    );
+
+    conn.execute("DELETE FROM Card WHERE id = 'card-b'", [])
+        .unwrap();
+    conn.execute("DELETE FROM Tag WHERE id = 'tag-b'", [])
+        .unwrap();
+
+    let remaining: Vec<String> = conn
+        .prepare("SELECT id FROM Budget ORDER BY id")
+        .unwrap()
+        .query_map([], |row| row.get(0))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(
+        remaining,
+        vec!["all"],
+        "Budgets should go with their card or tag"
+    );
+}
@@ -3,6 +3,11 @@ mod common;
 
 use common::create_test_db;
 use rusqlite::params;
+use tin_lib::store::Store;
+
+fn store() -> Store {
+    Store::open_in_memory().expect("Failed to open in-memory store")
+}
 
 #[test]
 fn test_fts5_indexes_card_on_insert() {
@@ -129,3 +134,244 @@ fn test_fts5_removes_on_card_delete() {
 
     assert_eq!(count, 0, "FTS5 should remove card from index on delete");
 }
+
+#[test]
+fn test_fts5_indexes_todo_tags() {
+    let db = create_test_db();
+    let conn = db.lock().unwrap();
+
+    conn.execute(
+        "INSERT INTO Card (id, title, amount, createdAt, updatedAt) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            "card-search-tags",
+            "Weekly",
+            0_i64,
+            "2024-01-01T00:00:00.000Z",
+            "2024-01-01T00:00:00.000Z"
+        ],
+    )
+    .unwrap();
+    conn.execute(
+        "INSERT INTO Todo (id, cardId, title, createdAt, updatedAt) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            "todo-search-tags",
+            "card-search-tags",
+            "Milk",
+            "2024-01-01T00:00:00.000Z",
+            "2024-01-01T00:00:00.000Z"
+        ],
+    )
+    .unwrap();
+    conn.execute(
+        "INSERT INTO Tag (id, name) VALUES (?1, ?2)",
+        params!["tag-groceries", "Groceries"],
+    )
+    .unwrap();
+    conn.execute(
+        "INSERT INTO TodoTag (todoId, tagId) VALUES (?1, ?2)",
+        params!["todo-search-tags", "tag-groceries"],
+    )
+    .unwrap();
+
+    let count_matches = |query: &str| -> i32 {
+        conn.query_row(
+            "SELECT COUNT(*) FROM search_index WHERE search_index MATCH ?1",
+            params![query],
+            |row| row.get(0),
+        )
+        .unwrap()
+    };
+
+    assert_eq!(
+        count_matches("Groceries*"),
+        1,
+        "Tag name should be searchable"
+    );
+
+    conn.execute(
+        "UPDATE Tag SET name = ?1 WHERE id = ?2",
+        params!["Food", "tag-groceries"],
+    )
+    .unwrap();
+    assert_eq!(
+        count_matches("Groceries*"),
+        0,
+        "Old tag name should be gone"
+    );
+    assert_eq!(
+        count_matches("Food*"),
+        1,
+        "Renamed tag should be searchable"
+    );
+
+    conn.execute("DELETE FROM Tag WHERE id = ?1", params!["tag-groceries"])
+        .unwrap();
+    assert_eq!(
+        count_matches("Food*"),
+        0,
+        "Deleted tag should leave the index"
+    );
+    assert_eq!(count_matches("Milk*"), 1, "Todo itself should stay indexed");
+}
+
+#[test]
+fn test_fts5_match_combined_with_amount_filter() {
+    let db = create_test_db();
+    let conn = db.lock().unwrap();
+
+    conn.execute(
+        "INSERT INTO Card (id, title, amount, createdAt, updatedAt) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            "card-search-5",
+            "Trip",
+            100_000_000_i64,
+            "2024-01-01T00:00:00.000Z",
+            "2024-01-01T00:00:00.000Z"
+        ],
+    )
+    .unwrap();
+
+    for (id, title, amount) in [
+        ("todo-cheap", "coffee", 3_000_000_i64),
+        ("todo-mid", "coffee beans", 7_500_000),
+        ("todo-other", "taxi: airport", 7_500_000),
+    ] {
+        conn.execute(
+            "INSERT INTO Todo (id, cardId, title, amount, createdAt, updatedAt) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![id, "card-search-5", title, amount, "2024-01-01T00:00:00.000Z", "2024-01-01T00:00:00.000Z"],
+        )
+        .unwrap();
+    }
+
+    let matching = |fts: &str, min_amount: i64| -> Vec<String> {
+        let mut stmt = conn
+            .prepare(
+                "SELECT si.todo_id FROM search_index si
+                 JOIN Card c ON c.id = si.card_id
+                 LEFT JOIN Todo td ON td.id = si.todo_id
+                 WHERE search_index MATCH ?1 AND td.amount > ?2
+                 ORDER BY si.todo_id",
+            )
+            .unwrap();
+        stmt.query_map(params![fts, min_amount], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    };
+
+    assert_eq!(matching("\"coffee\"*", 5_000_000), vec!["todo-mid"]);
+    assert_eq!(
+        matching("\"taxi:\"*", 0),
+        vec!["todo-other"],
+        "Quoted terms should match punctuation literally instead of as a column filter"
+    );
+}
+
+#[test]
+fn test_date_bound_matches_cards_and_todos_by_scheduled_or_created() {
+    let db = create_test_db();
+    let conn = db.lock().unwrap();
+
+    conn.execute(
+        "INSERT INTO Card (id, title, amount, createdAt, updatedAt) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            "card-dated",
+            "Lunch budget",
+            0_i64,
+            "2026-10-05T09:00:00.000Z",
+            "2026-10-05T09:00:00.000Z"
+        ],
+    )
+    .unwrap();
+
+    for (id, title, scheduled_at, created_at) in [
+        (
+            "todo-scheduled-late",
+            "lunch out",
+            Some("2026-10-20T12:00:00.000Z"),
+            "2026-09-01T00:00:00.000Z",
+        ),
+        (
+            "todo-scheduled-early",
+            "lunch in",
+            Some("2026-09-20T12:00:00.000Z"),
+            "2026-10-10T00:00:00.000Z",
+        ),
+        (
+            "todo-unscheduled",
+            "lunch snack",
+            None,
+            "2026-10-12T00:00:00.000Z",
+        ),
+    ] {
+        conn.execute(
+            "INSERT INTO Todo (id, cardId, title, scheduledAt, createdAt, updatedAt) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![id, "card-dated", title, scheduled_at, created_at, created_at],
+        )
+        .unwrap();
+    }
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT COALESCE(si.todo_id, si.card_id) FROM search_index si
+             JOIN Card c ON c.id = si.card_id
+             LEFT JOIN Todo td ON td.id = si.todo_id
+             WHERE search_index MATCH ?1
+               AND CASE WHEN si.todo_id IS NULL THEN c.createdAt
+                        ELSE COALESCE(td.scheduledAt, td.createdAt) END >= ?2
+             ORDER BY 1",
+        )
+        .unwrap();
+    let ids: Vec<String> = stmt
+        .query_map(params!["\"lunch\"*", "2026-10-01T00:00:00.000Z"], |row| {
+            row.get(0)
+        })
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+
+    assert_eq!(
+        ids,
+        vec!["card-dated", "todo-scheduled-late", "todo-unscheduled"],
+        "A lone after: bound should match cards and todos, preferring scheduledAt"
+    );
+}
+
+#[test]
+fn test_words_with_colons_are_searched_as_text() {
+    let mut store = store();
+    let card = store.create_card(Some("Links"), "10", None).unwrap();
+    store
+        .add_todo(&card.id, "http://example.com", None, true, None, None)
+        .unwrap();
+    store
+        .add_todo(&card.id, "re: invoice", None, true, None, None)
+        .unwrap();
+
+    assert_eq!(
+        store
+            .search("http://example.com", None, None)
+            .unwrap()
+            .total_count,
+        1
+    );
+    assert_eq!(store.search("re:", None, None).unwrap().total_count, 1);
+    assert_eq!(
+        store.search("links tag:", None, None).unwrap_err().code(),
+        "INVALID_QUERY",
+        "Known filters still need a value"
+    );
+}
+
+#[test]
+fn test_out_of_range_relative_dates_are_invalid() {
+    let store = store();
+    for query in ["after:-999999999999999d", "before:+999999999999999w"] {
+        assert_eq!(
+            store.search(query, None, None).unwrap_err().code(),
+            "INVALID_QUERY",
+            "{}",
+            query
+        );
+    }
+}
@@ -0,0 +1,182 @@
+//! Tests for the rotating database snapshots, run in a temp dir
+use chrono::{DateTime, TimeZone, Utc};
+use rusqlite::Connection;
+use std::path::{Path, PathBuf};
+use tin_lib::errors::AppError;
+use tin_lib::snapshots;
+use tin_lib::store::Store;
+
+/// An empty directory unique to this test process and `name`
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("tin-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).expect("Failed to create temp dir");
+    dir
+}
+
+fn at(day: u32, hour: u32) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2026, 10, day, hour, 0, 0).unwrap()
+}
+
+fn card_titles(store: &Store) -> Vec<String> {
+    store
+        .list_cards(None, None, None, None)
+        .unwrap()
+        .items
+        .into_iter()
+        .filter_map(|c| c.title)
+        .collect()
+}
+
+fn schema_version(conn: &Connection) -> u32 {
+    conn.query_row("PRAGMA user_version", [], |row| row.get(0))
+        .unwrap()
+}
+
+fn file_names(dir: &Path) -> Vec<String> {
+    let mut names: Vec<String> = std::fs::read_dir(dir)
+        .unwrap()
+        .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn test_rotate_keeps_the_newest_per_day_and_week() {
+    let dir = temp_dir("snapshot-rotate");
+    for name in [
+        "tin-20261018-120000-000.db", // Sunday, newest
+        "tin-20261018-080000-000.db", // same day
+        "tin-20261017-120000-000.db", // second day
+        "tin-20261016-120000-000.db", // same week, days used up
+        "tin-20261010-120000-000.db", // newest of the previous week
+        "tin-20261005-120000-000.db", // same week
+        "tin-20260930-120000-000.db", // third week
+        "tin-garbage.db",
+        "notes.txt",
+    ] {
+        std::fs::write(dir.join(name), b"").unwrap();
+    }
+
+    assert_eq!(snapshots::rotate(&dir, 2, 2).unwrap(), 4);
+    assert_eq!(
+        file_names(&dir),
+        vec![
+            "notes.txt",
+            "tin-20261010-120000-000.db",
+            "tin-20261017-120000-000.db",
+            "tin-20261018-120000-000.db",
+            "tin-garbage.db",
+        ]
+    );
+
+    // Weekly retention alone still keeps one per week
+    assert_eq!(snapshots::rotate(&dir, 0, 1).unwrap(), 2);
+    assert_eq!(snapshots::list(&dir).unwrap()[0].id, "20261018-120000-000");
+
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn test_take_if_due_takes_one_snapshot_per_day() {
+    let dir = temp_dir("snapshot-due");
+    let store = Store::open(&dir.join("tin.db")).unwrap();
+    let backups = dir.join("backups");
+
+    assert!(snapshots::take_if_due(store.conn(), &backups, at(18, 8))
+        .unwrap()
+        .is_some());
+    assert!(snapshots::take_if_due(store.conn(), &backups, at(18, 20))
+        .unwrap()
+        .is_none());
+    assert!(snapshots::take_if_due(store.conn(), &backups, at(19, 8))
+        .unwrap()
+        .is_some());
+
+    let ids: Vec<String> = snapshots::list(&backups)
+        .unwrap()
+        .into_iter()
+        .map(|s| s.id)
+        .collect();
+    assert_eq!(ids, vec!["20261019-080000-000", "20261018-080000-000"]);
+
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn test_restore_replaces_the_database_and_can_be_undone() {
+    let dir = temp_dir("snapshot-restore");
+    let mut store = Store::open(&dir.join("tin.db")).unwrap();
+    let backups = dir.join("backups");
+
+    store.create_card(Some("Before"), "10", None).unwrap();
+    let snapshot = snapshots::take(store.conn(), &backups, at(18, 8)).unwrap();
+    store.create_card(Some("After"), "10", None).unwrap();
+
+    snapshots::restore(store.conn_mut(), &backups, &snapshot.id, at(18, 9)).unwrap();
+    assert_eq!(card_titles(&store), vec!["Before"]);
+
+    // The state before the restore was snapshotted first
+    let all = snapshots::list(&backups).unwrap();
+    assert_eq!(all.len(), 2);
+    snapshots::restore(store.conn_mut(), &backups, &all[0].id, at(18, 10)).unwrap();
+    let mut titles = card_titles(&store);
+    titles.sort();
+    assert_eq!(titles, vec!["After", "Before"]);
+
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn test_unusable_snapshots_leave_the_database_untouched() {
+    let dir = temp_dir("snapshot-unusable");
+    let mut store = Store::open(&dir.join("tin.db")).unwrap();
+    let backups = dir.join("backups");
+    store.create_card(Some("Live"), "10", None).unwrap();
+
+    assert!(matches!(
+        snapshots::restore(store.conn_mut(), &backups, "20260101-000000-000", at(18, 8)),
+        Err(AppError::BackupNotFound(_))
+    ));
+
+    let corrupt = snapshots::take(store.conn(), &backups, at(18, 8)).unwrap();
+    std::fs::write(&corrupt.path, vec![0x5a; 4096]).unwrap();
+    let err = snapshots::restore(store.conn_mut(), &backups, &corrupt.id, at(18, 9)).unwrap_err();
+    assert_eq!(err.code(), "INVALID_BACKUP", "{}", err);
+
+    assert_eq!(card_titles(&store), vec!["Live"]);
+    assert_eq!(
+        snapshots::list(&backups).unwrap().len(),
+        1,
+        "No pre-restore snapshot"
+    );
+
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn test_failed_migration_of_a_snapshot_leaves_the_database_untouched() {
+    let dir = temp_dir("snapshot-migration");
+    let mut store = Store::open(&dir.join("tin.db")).unwrap();
+    let backups = dir.join("backups");
+    store.create_card(Some("Old"), "10", None).unwrap();
+    let snapshot = snapshots::take(store.conn(), &backups, at(18, 8)).unwrap();
+    store.create_card(Some("Live"), "10", None).unwrap();
+    let version = schema_version(store.conn());
+
+    // Claiming an older schema makes migration 8 add columns that exist
+    let old = Connection::open(&snapshot.path).unwrap();
+    old.pragma_update(None, "user_version", 7).unwrap();
+    drop(old);
+
+    assert!(snapshots::restore(store.conn_mut(), &backups, &snapshot.id, at(18, 9)).is_err());
+
+    let mut titles = card_titles(&store);
+    titles.sort();
+    assert_eq!(titles, vec!["Live", "Old"]);
+    assert_eq!(schema_version(store.conn()), version);
+    assert_eq!(file_names(&backups), vec!["tin-20261018-080000-000.db"]);
+
+    std::fs::remove_dir_all(&dir).ok();
+}
@@ -0,0 +1,429 @@
+//! Tests for the card, todo, tag, exchange rate and settings operations, run
+//! through `Store` against an in-memory database
+use rusqlite::params;
+use tin_lib::errors::AppError;
+use tin_lib::pagination::CardSort;
+use tin_lib::store::{Store, TodoChanges};
+
+fn store() -> Store {
+    Store::open_in_memory().expect("Failed to open in-memory store")
+}
+
+#[test]
+fn test_todo_changes_update_card_balances() {
+    let mut store = store();
+
+    let card = store.create_card(Some("Groceries"), "100", None).unwrap();
+    let milk = store
+        .add_todo(&card.id, "Milk", Some("2.50"), true, None, None)
+        .unwrap();
+    assert_eq!(milk.updated_card.pending, "2.500000");
+    assert_eq!(milk.updated_card.remaining, "97.500000");
+
+    let bread = store
+        .add_todo(&card.id, "Bread", Some("4"), false, None, None)
+        .unwrap();
+    assert_eq!(bread.todo.order_index, milk.todo.order_index + 1);
+    assert_eq!(bread.todo.scheduled_at, None);
+
+    store
+        .conn()
+        .execute(
+            "UPDATE Todo SET createdAt = '2020-01-01T00:00:00.000Z' WHERE id = ?1",
+            params![milk.todo.id],
+        )
+        .unwrap();
+    let done = store
+        .update_todo(
+            &milk.todo.id,
+            TodoChanges {
+                done: Some(true),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    assert!(done.todo.done);
+    assert_eq!(done.todo.created_at, "2020-01-01T00:00:00.000Z");
+    assert_eq!(done.updated_card.spent, "2.500000");
+    assert_eq!(done.updated_card.pending, "4.000000");
+    assert_eq!(done.updated_card.remaining, "93.500000");
+
+    store.delete_todo(&bread.todo.id).unwrap();
+    let card = store.get_card(&card.id).unwrap();
+    assert_eq!(card.todos.len(), 1);
+    assert_eq!(card.remaining, "97.500000");
+
+    let changes = store.recent_changes(None, None).unwrap();
+    assert_eq!(changes.total_count, 5, "Create, two adds, update, delete");
+}
+
+#[test]
+fn test_missing_rows_are_reported_by_id() {
+    let mut store = store();
+
+    assert!(matches!(
+        store.get_card("nope"),
+        Err(AppError::CardNotFound(id)) if id == "nope"
+    ));
+    assert!(matches!(
+        store.add_todo("nope", "Milk", None, true, None, None),
+        Err(AppError::CardNotFound(_))
+    ));
+    assert!(matches!(
+        store.update_todo("nope", TodoChanges::default()),
+        Err(AppError::TodoNotFound(_))
+    ));
+    assert!(matches!(
+        store.delete_todo("nope"),
+        Err(AppError::TodoNotFound(_))
+    ));
+}
+
+#[test]
+fn test_trashed_cards_leave_lists_and_search() {
+    let mut store = store();
+
+    let rent = store.create_card(Some("Rent"), "900", None).unwrap();
+    let trip = store.create_card(Some("Trip"), "300", None).unwrap();
+    store
+        .add_todo(&trip.id, "Train tickets", Some("80"), true, None, None)
+        .unwrap();
+
+    let hits = store.search("tickets", None, None).unwrap();
+    assert_eq!(hits.total_count, 1);
+    assert_eq!(hits.items[0].card_id, trip.id);
+
+    store.delete_card(&trip.id).unwrap();
+    let cards = store
+        .list_cards(None, None, Some(CardSort::Title), None)
+        .unwrap();
+    assert_eq!(
+        cards.items.iter().map(|c| &c.id).collect::<Vec<_>>(),
+        vec![&rent.id]
+    );
+    assert_eq!(store.list_trashed_cards().unwrap().len(), 1);
+    assert_eq!(store.search("tickets", None, None).unwrap().total_count, 0);
+
+    let restored = store.restore_card(&trip.id).unwrap();
+    assert_eq!(restored.deleted_at, None);
+    assert_eq!(store.search("tickets", None, None).unwrap().total_count, 1);
+}
+
+#[test]
+fn test_archive_policy_skips_recent_and_pinned_cards() {
+    let mut store = store();
+
+    let old = store.create_card(Some("Old"), "10", None).unwrap();
+    let pinned = store.create_card(Some("Pinned"), "10", None).unwrap();
+    let recent = store.create_card(Some("Recent"), "10", None).unwrap();
+    store.pin_card(&pinned.id, true).unwrap();
+    for id in [&old.id, &pinned.id] {
+        store
+            .conn()
+            .execute(
+                "UPDATE Card SET createdAt = ?1, updatedAt = ?1 WHERE id = ?2",
+                params!["2020-01-01T00:00:00.000Z", id],
+            )
+            .unwrap();
+    }
+
+    let result = store.archive_old_cards().unwrap();
+    assert_eq!(result.archived_count, 1);
+
+    let archived = store.list_archived_cards(None, None, None, None).unwrap();
+    assert_eq!(
+        archived.items.iter().map(|c| &c.id).collect::<Vec<_>>(),
+        vec![&old.id]
+    );
+    let active = store.list_cards(None, None, None, None).unwrap();
+    assert_eq!(active.total_count, 2);
+    assert!(active.items.iter().any(|c| c.id == recent.id));
+
+    let card = store.unarchive_card(&old.id).unwrap();
+    assert!(!card.archived);
+    assert_eq!(card.archived_at, None);
+}
+
+#[test]
+fn test_out_of_range_archive_ages_are_rejected() {
+    let mut store = store();
+
+    let mut policy = store.settings().unwrap().archive_policy;
+    policy.age_days = u32::MAX;
+    assert!(matches!(
+        store.save_archive_policy(&policy),
+        Err(AppError::InvalidField { field, .. }) if field == "age_days"
+    ));
+
+    // Saved before ages were bounded
+    store
+        .conn()
+        .execute(
+            "INSERT INTO Settings (key, value, updatedAt) VALUES ('archive_policy', ?1, '2026-01-01')",
+            params![serde_json::to_string(&policy).unwrap()],
+        )
+        .unwrap();
+    assert!(matches!(
+        store.archive_old_cards(),
+        Err(AppError::InvalidField { field, .. }) if field == "age_days"
+    ));
+    store.list_cards(None, None, None, None).unwrap();
+}
+
+#[test]
+fn test_trashed_cards_reject_writes() {
+    let mut store = store();
+
+    let card = store.create_card(Some("Trip"), "300", None).unwrap();
+    let todo = store
+        .add_todo(&card.id, "Tickets", Some("80"), true, None, None)
+        .unwrap()
+        .todo;
+    store.delete_card(&card.id).unwrap();
+
+    assert!(matches!(
+        store.add_todo(&card.id, "Hotel", Some("100"), true, None, None),
+        Err(AppError::CardNotFound(id)) if id == card.id
+    ));
+    assert!(matches!(
+        store.update_todo(&todo.id, TodoChanges::default()),
+        Err(AppError::CardNotFound(id)) if id == card.id
+    ));
+    assert!(matches!(
+        store.update_card(&card.id, Some("Renamed"), None, None),
+        Err(AppError::CardNotFound(_))
+    ));
+    assert!(matches!(
+        store.set_locked_amount(&card.id, Some("50")),
+        Err(AppError::CardNotFound(_))
+    ));
+    assert!(matches!(
+        store.delete_todo(&todo.id),
+        Err(AppError::CardNotFound(id)) if id == card.id
+    ));
+    assert!(matches!(
+        store.set_todo_tags(&todo.id, &[]),
+        Err(AppError::CardNotFound(id)) if id == card.id
+    ));
+    for result in [
+        store.archive_card(&card.id),
+        store.unarchive_card(&card.id),
+        store.pin_card(&card.id, true),
+    ] {
+        assert!(matches!(result, Err(AppError::CardNotFound(_))));
+    }
+
+    // Deleting again keeps the card's place in the trash
+    let deleted_at = store.list_trashed_cards().unwrap()[0].deleted_at.clone();
+    store.delete_card(&card.id).unwrap();
+    assert_eq!(
+        store.list_trashed_cards().unwrap()[0].deleted_at,
+        deleted_at
+    );
+
+    store.restore_card(&card.id).unwrap();
+    store
+        .add_todo(&card.id, "Hotel", Some("100"), true, None, None)
+        .unwrap();
+}
+
+#[test]
+fn test_purge_trash_defaults_to_the_retention_setting() {
+    let mut store = store();
+
+    let card = store.create_card(Some("Old"), "10", None).unwrap();
+    store.delete_card(&card.id).unwrap();
+    let two_days_ago = (chrono::Utc::now() - chrono::Duration::days(2))
+        .format("%Y-%m-%dT%H:%M:%S%.3fZ")
+        .to_string();
+    store
+        .conn()
+        .execute(
+            "UPDATE Card SET deletedAt = ?1 WHERE id = ?2",
+            params![two_days_ago, card.id],
+        )
+        .unwrap();
+
+    assert_eq!(store.purge_trash(None).unwrap().purged_count, 0);
+    for days in [0, -1, i64::MAX] {
+        assert!(matches!(
+            store.purge_trash(Some(days)),
+            Err(AppError::InvalidField { field, .. }) if field == "older_than_days"
+        ));
+    }
+
+    store
+        .conn()
+        .execute(
+            "INSERT INTO Settings (key, value, updatedAt) VALUES ('trash_retention_days', '1', ?1)",
+            params![two_days_ago],
+        )
+        .unwrap();
+    assert_eq!(store.purge_trash(None).unwrap().purged_count, 1);
+    assert!(store.list_trashed_cards().unwrap().is_empty());
+}
+
+#[test]
+fn test_tag_totals_keep_untagged_todos_in_their_own_row() {
+    let mut store = store();
+
+    let card = store.create_card(Some("Groceries"), "100", None).unwrap();
+    let milk = store
+        .add_todo(&card.id, "Milk", Some("2.50"), true, None, None)
+        .unwrap();
+    let cheese = store
+        .add_todo(&card.id, "Cheese", Some("6"), true, None, None)
+        .unwrap();
+    store
+        .add_todo(&card.id, "Bread", Some("4"), true, None, None)
+        .unwrap();
+    store
+        .conn()
+        .execute_batch("INSERT INTO Tag (id, name) VALUES ('dairy', 'Dairy');")
+        .unwrap();
+    for todo in [&milk.todo.id, &cheese.todo.id] {
+        store
+            .conn()
+            .execute(
+                "INSERT INTO TodoTag (todoId, tagId) VALUES (?1, 'dairy')",
+                params![todo],
+            )
+            .unwrap();
+    }
+
+    let totals = store.totals_by_tag(None, None, None).unwrap();
+    assert_eq!(totals.len(), 2);
+    assert_eq!(totals[0].tag_id.as_deref(), Some("dairy"));
+    assert_eq!(totals[0].name.as_deref(), Some("Dairy"));
+    assert_eq!(totals[0].currency, "USD");
+    assert_eq!(totals[0].total, "8.500000");
+    assert_eq!(totals[0].todo_count, 2);
+    assert_eq!(totals[1].tag_id, None);
+    assert_eq!(totals[1].name, None);
+    assert_eq!(totals[1].total, "4.000000");
+    assert_eq!(totals[1].todo_count, 1);
+}
+
+#[test]
+fn test_tag_totals_are_split_by_card_currency() {
+    let mut store = store();
+
+    let paris = store
+        .create_card(Some("Paris"), "500", Some("EUR"))
+        .unwrap();
+    let delhi = store
+        .create_card(Some("Delhi"), "40000", Some("INR"))
+        .unwrap();
+    let todos = [
+        store.add_todo(&paris.id, "Hotel", Some("200"), true, None, None),
+        store.add_todo(&paris.id, "Taxi", Some("30"), true, None, Some("USD")),
+        store.add_todo(&paris.id, "Museum", Some("20"), true, None, Some("GBP")),
+        store.add_todo(&delhi.id, "Hotel", Some("9000"), true, None, None),
+    ];
+    store
+        .conn()
+        .execute_batch(
+            "INSERT INTO Tag (id, name) VALUES ('travel', 'Travel');
+             INSERT INTO ExchangeRate (base, quote, rate) VALUES ('USD', 'EUR', 0.5);",
+        )
+        .unwrap();
+    for todo in todos {
+        store
+            .conn()
+            .execute(
+                "INSERT INTO TodoTag (todoId, tagId) VALUES (?1, 'travel')",
+                params![todo.unwrap().todo.id],
+            )
+            .unwrap();
+    }
+
+    let totals = store.totals_by_tag(None, None, None).unwrap();
+    assert_eq!(totals.len(), 2);
+    assert_eq!(totals[0].currency, "EUR");
+    assert_eq!(totals[0].total, "215.000000");
+    assert_eq!(totals[0].todo_count, 3);
+    assert_eq!(totals[0].unconverted_count, 1, "No GBP rate");
+    assert_eq!(totals[1].currency, "INR");
+    assert_eq!(totals[1].total, "9000.000000");
+    assert_eq!(totals[1].unconverted_count, 0);
+}
+
+#[test]
+fn test_tags_are_unique_and_replace_a_todos_tags() {
+    let mut store = store();
+
+    let food = store.create_tag(" Food ", Some("#0a0")).unwrap();
+    assert_eq!(food.name, "Food");
+    let err = store.create_tag("food", None).unwrap_err();
+    assert_eq!(err.code(), "CONFLICT");
+    let work = store.create_tag("Work", None).unwrap();
+    let renamed = store.update_tag(&work.id, Some("Office"), None).unwrap();
+    assert_eq!(renamed.name, "Office");
+
+    let card = store.create_card(Some("Groceries"), "100", None).unwrap();
+    let todo = store
+        .add_todo(&card.id, "Milk", Some("2"), true, None, None)
+        .unwrap()
+        .todo;
+    let tagged = store
+        .set_todo_tags(&todo.id, &[food.id.clone(), work.id.clone()])
+        .unwrap();
+    assert_eq!(
+        tagged
+            .tags
+            .iter()
+            .map(|t| t.name.as_str())
+            .collect::<Vec<_>>(),
+        ["Food", "Office"]
+    );
+
+    let err = store
+        .set_todo_tags(&todo.id, &["missing".to_string()])
+        .unwrap_err();
+    assert!(matches!(err, AppError::TagNotFound(_)));
+
+    store.delete_tag(&work.id).unwrap();
+    assert_eq!(store.list_tags().unwrap().len(), 1);
+    assert!(matches!(
+        store.delete_tag(&work.id),
+        Err(AppError::TagNotFound(_))
+    ));
+}
+
+#[test]
+fn test_exchange_rates_and_settings_round_trip() {
+    let mut store = store();
+
+    let rate = store.set_exchange_rate("eur", "usd", "1.1").unwrap();
+    assert_eq!((rate.base.as_str(), rate.quote.as_str()), ("EUR", "USD"));
+    assert_eq!(store.list_exchange_rates().unwrap().len(), 1);
+    store.delete_exchange_rate("EUR", "USD").unwrap();
+    assert_eq!(
+        store.delete_exchange_rate("EUR", "USD").unwrap_err().code(),
+        "RATE_NOT_FOUND"
+    );
+
+    let mut settings = store.settings().unwrap();
+    settings.default_currency = "eur".into();
+    let saved = store.save_settings(settings).unwrap();
+    assert_eq!(saved.default_currency, "EUR");
+    assert_eq!(store.settings().unwrap(), saved);
+
+    for days in [0, u32::MAX] {
+        let mut settings = saved.clone();
+        settings.trash_retention_days = days;
+        assert!(matches!(
+            store.save_settings(settings),
+            Err(AppError::InvalidField { field, .. }) if field == "trash_retention_days"
+        ));
+    }
+
+    let mut policy = saved.archive_policy.clone();
+    policy.age_days = 0;
+    assert!(store.save_archive_policy(&policy).is_err());
+    policy.age_days = 90;
+    assert_eq!(
+        store.save_archive_policy(&policy).unwrap().archive_policy,
+        policy
+    );
+}
@@ -15,7 +15,7 @@ describe("listCards", () => {
     });
 
     it("should call invoke with list_cards command", async () => {
-        mockInvoke.mockResolvedValue([]);
+        mockInvoke.mockResolvedValue({ items: [], next_cursor: null, total_count: 0 });
         await api.listCards();
         expect(mockInvoke).toHaveBeenCalledWith("list_cards", {});
     });
@@ -33,15 +33,28 @@ describe("listCards", () => {
                 archived_at: null,
             },
         ];
-        mockInvoke.mockResolvedValue(mockCards);
+        mockInvoke.mockResolvedValue({ items: mockCards, next_cursor: "abc", total_count: 3 });
 
         const result = await api.listCards();
-        expect(result).toHaveLength(1);
-        expect(result[0].id).toBe("card-1");
+        expect(result.items).toHaveLength(1);
+        expect(result.items[0].id).toBe("card-1");
+        expect(result.next_cursor).toBe("abc");
+        expect(result.total_count).toBe(3);
+    });
+
+    it("should pass pagination and sort options", async () => {
+        mockInvoke.mockResolvedValue({ items: [], next_cursor: null, total_count: 0 });
+        await api.listCards({ limit: 20, cursor: "abc", sort: "title", order: "asc" });
+        expect(mockInvoke).toHaveBeenCalledWith("list_cards", {
+            limit: 20,
+            cursor: "abc",
+            sort: "title",
+            order: "asc",
+        });
     });
 
     it("should throw on invalid response", async () => {
-        mockInvoke.mockResolvedValue([{ invalid: true }]);
+        mockInvoke.mockResolvedValue({ items: [{ invalid: true }], next_cursor: null, total_count: 1 });
         await expect(api.listCards()).rejects.toThrow();
     });
 });
@@ -288,15 +301,28 @@ describe("updateTodo", () => {
 
     it("should call invoke with update_todo command", async () => {
         mockInvoke.mockResolvedValue({
-            id: "todo-1",
-            card_id: "card-1",
-            title: "Updated title",
-            amount: "5.000000",
-            done: true,
-            scheduled_at: null,
-            order_index: 1,
-            created_at: "2024-01-01T00:00:00.000Z",
-            updated_at: "2024-01-02T00:00:00.000Z",
+            todo: {
+                id: "todo-1",
+                card_id: "card-1",
+                title: "Updated title",
+                amount: "5.000000",
+                done: true,
+                scheduled_at: null,
+                order_index: 1,
+                created_at: "2024-01-01T00:00:00.000Z",
+                updated_at: "2024-01-02T00:00:00.000Z",
+            },
+            updated_card: {
+                id: "card-1",
+                title: "Test",
+                amount: "100.000000",
+                locked_amount: null,
+                archived: false,
+                created_at: "2024-01-01T00:00:00.000Z",
+                updated_at: "2024-01-02T00:00:00.000Z",
+                archived_at: null,
+            },
+            warning: null,
         });
 
         await api.updateTodo("todo-1", "Updated title", undefined, true);
@@ -330,26 +356,30 @@ describe("search", () => {
     });
 
     it("should call invoke with search command", async () => {
-        mockInvoke.mockResolvedValue([]);
+        mockInvoke.mockResolvedValue({ items: [], next_cursor: null, total_count: 0 });
 
         await api.search("groceries");
         expect(mockInvoke).toHaveBeenCalledWith("search", { query: "groceries" });
     });
 
     it("should return search results", async () => {
-        mockInvoke.mockResolvedValue([
-            {
-                card_id: "card-1",
-                todo_id: null,
-                card_title: "Groceries",
-                todo_title: null,
-                snippet: "...Groceries Budget...",
-            },
-        ]);
+        mockInvoke.mockResolvedValue({
+            items: [
+                {
+                    card_id: "card-1",
+                    todo_id: null,
+                    card_title: "Groceries",
+                    todo_title: null,
+                    snippet: "...Groceries Budget...",
+                },
+            ],
+            next_cursor: null,
+            total_count: 1,
+        });
 
         const result = await api.search("Groceries");
-        expect(result).toHaveLength(1);
-        expect(result[0].card_title).toBe("Groceries");
+        expect(result.items).toHaveLength(1);
+        expect(result.items[0].card_title).toBe("Groceries");
     });
 });
 
@@ -359,14 +389,14 @@ describe("recentChanges", () => {
     });
 
     it("should call invoke with recent_changes command", async () => {
-        mockInvoke.mockResolvedValue([]);
+        mockInvoke.mockResolvedValue({ items: [], next_cursor: null, total_count: 0 });
 
         await api.recentChanges(10);
         expect(mockInvoke).toHaveBeenCalledWith("recent_changes", { limit: 10 });
     });
 
     it("should use default limit if not provided", async () => {
-        mockInvoke.mockResolvedValue([]);
+        mockInvoke.mockResolvedValue({ items: [], next_cursor: null, total_count: 0 });
 
         await api.recentChanges();
         expect(mockInvoke).toHaveBeenCalledWith("recent_changes", { limit: undefined });
@@ -409,4 +439,20 @@ describe("Error handling", () => {
 
         await expect(api.createCard("Test", "abc")).rejects.toThrow("Invalid amount");
     });
+
+    it("should wrap structured errors with their code and details", async () => {
+        mockInvoke.mockRejectedValue({
+            code: "CARD_NOT_FOUND",
+            message: "Card not found: card-999",
+            details: { id: "card-999" },
+        });
+
+        const error = await api.getCard("card-999").catch((e: unknown) => e);
+        expect(error).toBeInstanceOf(api.AppError);
+        expect(error).toMatchObject({
+            code: "CARD_NOT_FOUND",
+            message: "Card not found: card-999",
+            details: { id: "card-999" },
+        });
+    });
 });
@@ -59,8 +59,8 @@ export default function Home() {
         api.listCards(),
         api.recentChanges(10),
       ]);
-      setCards(cardsData);
-      setChanges(changesData);
+      setCards(cardsData.items);
+      setChanges(changesData.items);
     } catch (error) {
       console.error("Failed to load data:", error);
     } finally {
@@ -88,7 +88,7 @@ export default function Home() {
   const loadArchivedCards = useCallback(async () => {
     try {
       const archivedData = await api.listArchivedCards();
-      setArchivedCards(archivedData);
+      setArchivedCards(archivedData.items);
     } catch (error) {
       console.error("Failed to load archived cards:", error);
     }
@@ -247,7 +247,7 @@ export default function Home() {
       setSelectedCard(updated);
     }
     const changesData = await api.recentChanges(10);
-    setChanges(changesData);
+    setChanges(changesData.items);
   }, [selectedCard]);
 
 
@@ -2,40 +2,63 @@ import { invoke } from "@tauri-apps/api/core";
 import type {
     Card,
     CardWithTodos,
-    Todo,
     ChangeLog,
     SearchResult,
     AddTodoResult,
     OkResponse,
     ArchiveResult,
+    Page,
+    PageOptions,
+    CardPageOptions,
+    AppErrorPayload,
 } from "./types";
 import {
     CardSchema,
     CardWithTodosSchema,
-    TodoSchema,
     ChangeLogSchema,
     SearchResultSchema,
     AddTodoResultSchema,
     OkResponseSchema,
     ArchiveResultSchema,
+    pageSchema,
+    AppErrorSchema,
 } from "./types";
 import { z } from "zod";
 
+/** A backend error; branch on `code`, which stays stable across releases. */
+export class AppError extends Error {
+    readonly code: string;
+    readonly details: Record<string, unknown> | null;
+
+    constructor(payload: AppErrorPayload) {
+        super(payload.message);
+        this.name = "AppError";
+        this.code = payload.code;
+        this.details = payload.details;
+    }
+}
+
 async function safeInvoke<T>(
     cmd: string,
     args: Record<string, unknown>,
     schema: z.ZodType<T>
 ): Promise<T> {
-    const result = await invoke(cmd, args);
+    let result: unknown;
+    try {
+        result = await invoke(cmd, args);
+    } catch (error) {
+        const payload = AppErrorSchema.safeParse(error);
+        throw payload.success ? new AppError(payload.data) : error;
+    }
     return schema.parse(result);
 }
 
-export async function listCards(): Promise<Card[]> {
-    return safeInvoke("list_cards", {}, z.array(CardSchema));
+export async function listCards(options: CardPageOptions = {}): Promise<Page<Card>> {
+    return safeInvoke("list_cards", { ...options }, pageSchema(CardSchema));
 }
 
-export async function listArchivedCards(): Promise<Card[]> {
-    return safeInvoke("list_archived_cards", {}, z.array(CardSchema));
+export async function listArchivedCards(options: CardPageOptions = {}): Promise<Page<Card>> {
+    return safeInvoke("list_archived_cards", { ...options }, pageSchema(CardSchema));
 }
 
 export async function getCard(cardId: string): Promise<CardWithTodos> {
@@ -82,11 +105,11 @@ export async function updateTodo(
     done?: boolean,
     scheduledAt?: string | null,
     orderIndex?: number
-): Promise<Todo> {
+): Promise<AddTodoResult> {
     return safeInvoke(
         "update_todo",
         { todoId, title, amount, done, scheduledAt, orderIndex },
-        TodoSchema
+        AddTodoResultSchema
     );
 }
 
@@ -94,12 +117,18 @@ export async function deleteTodo(todoId: string): Promise<OkResponse> {
     return safeInvoke("delete_todo", { todoId }, OkResponseSchema);
 }
 
-export async function search(query: string): Promise<SearchResult[]> {
-    return safeInvoke("search", { query }, z.array(SearchResultSchema));
+export async function search(
+    query: string,
+    options: PageOptions = {}
+): Promise<Page<SearchResult>> {
+    return safeInvoke("search", { query, ...options }, pageSchema(SearchResultSchema));
 }
 
-export async function recentChanges(limit?: number): Promise<ChangeLog[]> {
-    return safeInvoke("recent_changes", { limit }, z.array(ChangeLogSchema));
+export async function recentChanges(
+    limit?: number,
+    cursor?: string | null
+): Promise<Page<ChangeLog>> {
+    return safeInvoke("recent_changes", { limit, cursor }, pageSchema(ChangeLogSchema));
 }
 
 export async function archiveCard(cardId: string): Promise<Card> {
@@ -43,9 +43,50 @@ export const SearchResultSchema = z.object({
     snippet: z.string(),
 });
 
+export const AppErrorSchema = z.object({
+    code: z.string(),
+    message: z.string(),
+    details: z.record(z.string(), z.unknown()).nullable(),
+});
+
+export const BudgetSchema = z.object({
+    id: z.string(),
+    name: z.string().nullable(),
+    card_id: z.string().nullable(),
+    card_title: z.string().nullable(),
+    tag_id: z.string().nullable(),
+    tag_name: z.string().nullable(),
+    monthly: z.boolean(),
+    amount: z.string(),
+    currency: z.string(),
+    created_at: z.string(),
+    updated_at: z.string(),
+});
+
+export const BudgetStatusSchema = z.object({
+    budget: BudgetSchema,
+    period_start: z.string().nullable(),
+    period_end: z.string().nullable(),
+    spent: z.string(),
+    pending: z.string(),
+    used: z.string(),
+    remaining: z.string(),
+    percent_used: z.number(),
+    exceeded: z.boolean(),
+    unconverted_count: z.number(),
+});
+
+export const BudgetWarningSchema = z.object({
+    todo_id: z.string(),
+    card_id: z.string(),
+    message: z.string(),
+    budgets: z.array(BudgetStatusSchema),
+});
+
 export const AddTodoResultSchema = z.object({
     todo: TodoSchema,
     updated_card: CardSchema,
+    warning: BudgetWarningSchema.nullish(),
 });
 
 export const OkResponseSchema = z.object({
@@ -56,11 +97,41 @@ export const ArchiveResultSchema = z.object({
     archived_count: z.number(),
 });
 
+export function pageSchema<T extends z.ZodTypeAny>(item: T) {
+    return z.object({
+        items: z.array(item),
+        next_cursor: z.string().nullable(),
+        total_count: z.number(),
+    });
+}
+
+export type CardSort = "created" | "updated" | "archived" | "title" | "amount" | "remaining";
+export type SortOrder = "asc" | "desc";
+
+export interface PageOptions {
+    limit?: number;
+    cursor?: string | null;
+}
+
+export interface CardPageOptions extends PageOptions {
+    sort?: CardSort;
+    order?: SortOrder;
+}
+
 export type Card = z.infer<typeof CardSchema>;
 export type Todo = z.infer<typeof TodoSchema>;
 export type CardWithTodos = z.infer<typeof CardWithTodosSchema>;
 export type ChangeLog = z.infer<typeof ChangeLogSchema>;
 export type SearchResult = z.infer<typeof SearchResultSchema>;
+export type AppErrorPayload = z.infer<typeof AppErrorSchema>;
 export type AddTodoResult = z.infer<typeof AddTodoResultSchema>;
+export type Budget = z.infer<typeof BudgetSchema>;
+export type BudgetStatus = z.infer<typeof BudgetStatusSchema>;
+export type BudgetWarning = z.infer<typeof BudgetWarningSchema>;
 export type OkResponse = z.infer<typeof OkResponseSchema>;
 export type ArchiveResult = z.infer<typeof ArchiveResultSchema>;
+export type Page<T> = {
+    items: T[];
+    next_cursor: string | null;
+    total_count: number;
+};